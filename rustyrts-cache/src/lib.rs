@@ -0,0 +1,15 @@
+//! Read-only access to the metadata that RustyRTS keeps in the target directory
+//!
+//! This crate includes the sources of the module `rustyrts::cache` and of the modules it uses,
+//! but does not depend on `rustc_private`, such that it can be used on a stable toolchain.
+
+#![allow(unexpected_cfgs)] // Features of the crates that share `fs_utils`
+
+#[path = "../../src/cache.rs"]
+pub mod cache;
+#[path = "../../src/constants.rs"]
+pub mod constants;
+#[path = "../../src/fs_utils.rs"]
+pub mod fs_utils;
+
+pub use cache::{Affected, Cache, CacheError, CachedUnit, Graph, Technique};
@@ -1,9 +1,12 @@
-use fs_utils::{get_cache_path, write_to_file, CacheFileDescr, CacheFileKind, CacheKind};
-use std::path::PathBuf;
+use constants::ENV_TARGET_DIR;
+use fs_utils::{
+    get_cache_path, write_to_file, Accessed, CacheFileDescr, CacheFileKind, CacheKind,
+};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicPtr;
 use std::sync::atomic::Ordering;
-use std::{borrow::Cow, panic::PanicInfo};
-use std::{collections::HashSet, sync::OnceLock};
+use std::{borrow::Cow, ffi::OsStr, panic::PanicInfo, time::Instant};
+use std::{collections::HashSet, sync::Mutex, sync::OnceLock};
 
 #[cfg(unix)]
 use std::fs::read_to_string;
@@ -13,6 +16,10 @@ mod fs_utils;
 
 static LIST: AtomicPtr<Traced> = AtomicPtr::new(std::ptr::null::<Traced>().cast_mut());
 
+static ACCESSED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+static TEST_START: Mutex<Option<Instant>> = Mutex::new(None);
+
 static PANIC_HOOK: OnceLock<Box<dyn Fn(&PanicInfo<'_>) + 'static + Sync + Send>> = OnceLock::new();
 
 //######################################################################################################################
@@ -50,8 +57,64 @@ pub fn trace(input: &'static mut (&str, usize)) {
     }
 }
 
+/// Records that a file or directory is accessed, together with a checksum of its current content
+///
+/// Only files inside the package, but outside the target directory are considered.
+/// Files that do not exist yet are recorded as well, such that creating them selects the test.
+pub fn trace_file(path: &dyn AsRef<Path>) {
+    let path = path.as_ref();
+    let Some(path) = path.canonicalize().ok().or_else(|| {
+        let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+        let parent = parent.unwrap_or(Path::new(".")).canonicalize().ok()?;
+        Some(parent.join(path.file_name()?))
+    }) else {
+        return;
+    };
+
+    let canonical_dir = |var: &str| std::env::var_os(var).and_then(|p| Path::new(&p).canonicalize().ok());
+    if canonical_dir(ENV_TARGET_DIR).is_some_and(|target_dir| path.starts_with(target_dir)) {
+        return;
+    }
+    let Some(root) = canonical_dir("CARGO_MANIFEST_DIR") else {
+        return;
+    };
+    let Some(relative) = path.strip_prefix(&root).ok().and_then(Path::to_str) else {
+        return;
+    };
+
+    let accessed = Accessed::File(relative);
+    record_accessed(accessed.to_trace(accessed.checksum(&root, |name| std::env::var_os(name))));
+}
+
+/// Records that an environment variable is queried, together with a checksum of its current value
+pub fn trace_env(name: &dyn AsRef<OsStr>) {
+    let Some(name) = name.as_ref().to_str() else {
+        return;
+    };
+
+    let accessed = Accessed::Env(name);
+    record_accessed(accessed.to_trace(accessed.checksum(Path::new(""), |name| std::env::var_os(name))));
+}
+
+fn record_accessed(line: String) {
+    if let Ok(mut accessed) = ACCESSED.lock() {
+        accessed.push(line);
+    }
+}
+
+fn reset_accessed() -> Vec<String> {
+    ACCESSED
+        .lock()
+        .map(|mut accessed| std::mem::take(&mut *accessed))
+        .unwrap_or_default()
+}
+
 pub fn pre_test(test_name: &'static str, append: bool) {
     reset_list();
+    reset_accessed();
+    if let Ok(mut start) = TEST_START.lock() {
+        *start = Some(Instant::now());
+    }
 
     let hook = std::panic::take_hook();
     PANIC_HOOK.get_or_init(|| hook);
@@ -68,7 +131,19 @@ pub fn pre_test(test_name: &'static str, append: bool) {
 pub fn pre_main() {}
 
 pub fn post_test(test_name: &'static str, append: bool) {
-    let traces = reset_list();
+    // In case the test panics, this is called twice, but only the first call reports the duration
+    if let Some(start) = TEST_START.lock().ok().and_then(|mut start| start.take()) {
+        let file_descr = CacheFileDescr::new(test_name, None, None, None, CacheFileKind::Duration);
+        write_to_file(
+            start.elapsed().as_secs_f64().to_string(),
+            get_cache_path(CacheKind::Dynamic).unwrap(),
+            |path_buf| file_descr.apply(path_buf),
+            false,
+        );
+    }
+
+    let mut traces = reset_list();
+    traces.extend(reset_accessed().into_iter().map(Cow::Owned));
 
     let file_descr = CacheFileDescr::new(test_name, None, None, None, CacheFileKind::Traces);
     export_traces(traces, |path_buf| file_descr.apply(path_buf), append);
@@ -78,7 +153,10 @@ pub fn post_test(test_name: &'static str, append: bool) {
 pub fn post_main() {
     use std::os::unix::process::parent_id;
 
-    let traces = read_list();
+    let mut traces = read_list();
+    if let Ok(accessed) = ACCESSED.lock() {
+        traces.extend(accessed.iter().cloned().map(Cow::Owned));
+    }
 
     let ppid = format!("{}", parent_id());
     let file_descr = CacheFileDescr::new(&ppid, None, None, None, CacheFileKind::ProcessTraces);
@@ -0,0 +1,315 @@
+//! The `cache` subcommand, which packs the metadata of RustyRTS into a single archive and unpacks it again,
+//! or merges the metadata of several target directories.
+
+use std::{
+    fs::{create_dir_all, read_dir, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use cargo::{
+    core::{compiler::CompileMode, Workspace},
+    ops::{CompileOptions, Packages},
+    util::command_prelude::*,
+    CargoResult,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use itertools::Itertools;
+use rustyrts::constants::{
+    DIR_DYNAMIC, DIR_GENERAL, DIR_SNAPSHOTS, DIR_STATIC, ENDING_CHANGES, ENDING_DURATION,
+};
+
+#[cfg(unix)]
+use rustyrts::constants::ENDING_PROCESS_TRACE;
+
+use super::{
+    basic::BasicMode, dynamic::DynamicMode, merge::merge, r#static::StaticMode, SelectionMode,
+};
+
+/// Name of the entry that identifies the format of an archive
+const ARCHIVE_VERSION_ENTRY: &str = "RUSTYRTS_CACHE_VERSION";
+
+/// Version of the format of the archive, needs to be incremented whenever its layout changes
+const ARCHIVE_VERSION: u32 = 1;
+
+pub fn cli() -> Command {
+    subcommand("cache")
+        .about(
+            r"Export or import the metadata of RustyRTS, e.g. to cache it in CI
+
+`export` packs the metadata of all modes into a single archive, excluding any build artifacts.
+`import` unpacks such an archive into the target directory.
+`merge` combines the metadata of several target directories, e.g. of jobs that executed different shards, into the target directory.
+`gc` removes files of tests and targets that do not exist anymore, which is also done at the end of every run that tests all targets.
+`key` prints a suggested cache key, derived from the toolchain, features, profile and `Cargo.lock`.",
+        )
+        .arg(
+            Arg::new("action")
+                .value_name("ACTION")
+                .help("What to do with the cache")
+                .value_parser(["export", "import", "key", "merge", "gc"])
+                .required(true),
+        )
+        .arg(
+            Arg::new("file")
+                .value_name("FILE")
+                .help("Path of the archive, or target directories to merge")
+                .value_parser(clap::value_parser!(PathBuf))
+                .num_args(1..)
+                .required_if_eq_any([
+                    ("action", "export"),
+                    ("action", "import"),
+                    ("action", "merge"),
+                ]),
+        )
+        .arg_features()
+        .arg_profile("Derive the cache key for the specified profile")
+        .arg_target_dir()
+        .arg_manifest_path()
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
+    let ws = args.workspace(config)?;
+
+    let key = cache_key(config, args, &ws)?;
+    let action = args.get_one::<String>("action").unwrap();
+
+    match action.as_str() {
+        "key" => {
+            cargo::drop_println!(config, "{key}");
+        }
+        "export" => {
+            let file = single_file(args)?;
+            let count = export(&ws, config, file)?;
+            config
+                .shell()
+                .status("Exported", format!("{count} files to {}", file.display()))?;
+            config.shell().status("Cache key", &key)?;
+        }
+        "import" => {
+            let file = single_file(args)?;
+            let count = import(&ws, config, file)?;
+            config
+                .shell()
+                .status("Imported", format!("{count} files from {}", file.display()))?;
+            config.shell().status("Cache key", &key)?;
+        }
+        "merge" => {
+            let dirs = args
+                .get_many::<PathBuf>("file")
+                .unwrap()
+                .cloned()
+                .collect_vec();
+            let count = merge(&ws, config, &dirs)?;
+            config.shell().status(
+                "Merged",
+                format!("{count} files from {} target directories", dirs.len()),
+            )?;
+        }
+        "gc" => {
+            let mut options = CompileOptions::new(config, CompileMode::Test)?;
+            options.spec = Packages::All;
+            options.cli_features = args.cli_features()?;
+            options.build_config.requested_profile =
+                args.get_profile_name(config, "test", ProfileChecking::Custom)?;
+
+            let target_dirs = target_dirs(&ws, config)?
+                .into_iter()
+                .map(|(_, dir)| dir)
+                .unique()
+                .collect_vec();
+            let target_dirs = target_dirs.iter().map(PathBuf::as_path).collect_vec();
+            let removed = crate::ops::collect_garbage(&ws, &options, &target_dirs)?;
+            config.shell().status("Removed", removed)?;
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Path of the archive, only a single one is supported
+fn single_file(args: &ArgMatches) -> CargoResult<&PathBuf> {
+    let files = args.get_many::<PathBuf>("file").unwrap().collect_vec();
+    match files.as_slice() {
+        [file] => Ok(file),
+        _ => anyhow::bail!("Expected a single archive, but got {}", files.len()),
+    }
+}
+
+/// Determines the target directories of all modes, together with the names used for them in the archive
+pub(crate) fn target_dirs(
+    ws: &Workspace<'_>,
+    config: &Config,
+) -> CargoResult<Vec<(&'static str, PathBuf)>> {
+    let target_dir = ws.target_dir().into_path_unlocked();
+    let explicit_target_dir = config.target_dir()?.is_some();
+
+    let basic = BasicMode::new();
+    Ok([
+        ("static", &StaticMode::default() as &dyn SelectionMode),
+        ("dynamic", &DynamicMode as &dyn SelectionMode),
+        ("basic", &basic as &dyn SelectionMode),
+    ]
+    .into_iter()
+    .map(|(name, mode)| {
+        let dir = if explicit_target_dir {
+            target_dir.clone()
+        } else {
+            mode.default_target_dir(target_dir.clone())
+        };
+        (name, dir)
+    })
+    .collect())
+}
+
+/// Whether a file is part of the metadata that is worth caching
+pub(crate) fn is_cached(path: &Path) -> bool {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+
+    #[cfg(unix)]
+    if extension == Some(ENDING_PROCESS_TRACE) {
+        return false;
+    }
+
+    extension != Some(ENDING_CHANGES) && extension != Some(ENDING_DURATION)
+}
+
+fn export(ws: &Workspace<'_>, config: &Config, file: &Path) -> CargoResult<usize> {
+    let archive = File::create(file)
+        .with_context(|| format!("Failed to create archive {}", file.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(archive, Compression::default()));
+
+    let version = ARCHIVE_VERSION.to_string();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(version.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, ARCHIVE_VERSION_ENTRY, version.as_bytes())?;
+
+    let mut count = 0;
+    let mut exported = Vec::new();
+    for (name, target_dir) in target_dirs(ws, config)? {
+        // If several modes use the same target directory, the metadata is only exported once
+        if exported.contains(&target_dir) {
+            continue;
+        }
+
+        for dir in [DIR_GENERAL, DIR_STATIC, DIR_DYNAMIC, DIR_SNAPSHOTS] {
+            let prefix = Path::new(name).join(dir);
+            count += append_dir(&mut builder, &target_dir.join(dir), &prefix)?;
+        }
+        exported.push(target_dir);
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(count)
+}
+
+fn append_dir<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    prefix: &Path,
+) -> CargoResult<usize> {
+    let Ok(entries) = read_dir(dir) else {
+        return Ok(0);
+    };
+
+    let mut count = 0;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let name = prefix.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            count += append_dir(builder, &path, &name)?;
+        } else if is_cached(&path) {
+            builder.append_path_with_name(&path, &name)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn import(ws: &Workspace<'_>, config: &Config, file: &Path) -> CargoResult<usize> {
+    let archive =
+        File::open(file).with_context(|| format!("Failed to open archive {}", file.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+
+    let target_dirs = target_dirs(ws, config)?;
+
+    let mut count = 0;
+    let mut version_checked = false;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+
+        if path == Path::new(ARCHIVE_VERSION_ENTRY) {
+            let mut version = String::new();
+            entry.read_to_string(&mut version)?;
+            if version.trim() != ARCHIVE_VERSION.to_string() {
+                anyhow::bail!(
+                    "Archive {} has version {}, but version {ARCHIVE_VERSION} is required",
+                    file.display(),
+                    version.trim()
+                );
+            }
+            version_checked = true;
+            continue;
+        }
+
+        if !version_checked {
+            anyhow::bail!("{} is not an archive of RustyRTS", file.display());
+        }
+
+        let mut components = path.components();
+        let mode = components.next().and_then(|c| c.as_os_str().to_str());
+        let relative = components.as_path();
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            anyhow::bail!("Invalid entry {} in archive", path.display());
+        }
+        let Some((_, target_dir)) = target_dirs.iter().find(|(name, _)| Some(*name) == mode) else {
+            anyhow::bail!("Unexpected entry {} in archive", path.display());
+        };
+
+        let destination = target_dir.join(relative);
+        create_dir_all(destination.parent().unwrap())?;
+        entry.unpack(&destination)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Derives a key that identifies under which conditions the metadata has been produced
+fn cache_key(config: &Config, args: &ArgMatches, ws: &Workspace<'_>) -> CargoResult<String> {
+    let mut hasher = DefaultHasher::new();
+
+    let rustc = config.load_global_rustc(Some(ws))?;
+    rustc.verbose_version.hash(&mut hasher);
+
+    let mut features = args
+        .get_many::<String>("features")
+        .unwrap_or_default()
+        .flat_map(|f| f.split([' ', ',']))
+        .filter(|f| !f.is_empty())
+        .collect::<Vec<_>>();
+    features.sort_unstable();
+    features.dedup();
+    features.hash(&mut hasher);
+    args.flag("all-features").hash(&mut hasher);
+    args.flag("no-default-features").hash(&mut hasher);
+
+    let profile = args.get_profile_name(config, "test", ProfileChecking::Custom)?;
+    profile.as_str().hash(&mut hasher);
+
+    let lock_file = ws.root().join("Cargo.lock");
+    std::fs::read(lock_file).ok().hash(&mut hasher);
+
+    Ok(format!("rustyrts-{:016x}", hasher.finish()))
+}
@@ -299,7 +299,7 @@ impl<'context> Selector<'context> for BasicSelector<'context> {
             return SelectionUnit::RetestAll;
         }
 
-        let TestUnit(unit, test_info) = test_unit;
+        let TestUnit(unit, test_info, _) = test_unit;
         debug_assert!(test_info.is_none());
 
         let execute_tests = self.compiled_targets.contains(&unit.target);
@@ -308,6 +308,10 @@ impl<'context> Selector<'context> for BasicSelector<'context> {
         SelectionUnit::CrateLevel { execute_tests }
     }
 
+    fn technique(&self) -> &'static str {
+        "crate-level RTS"
+    }
+
     fn cache_kind(&self) -> CacheKind {
         unreachable!()
     }
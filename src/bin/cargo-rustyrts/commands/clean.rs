@@ -1,11 +1,45 @@
-use std::ffi::OsString;
+//! The `clean` subcommand, which cleans the target directories of RustyRTS, or only its metadata.
 
-use cargo::util::command_prelude::*;
+use std::{
+    ffi::OsString,
+    fs::{read_dir, remove_dir_all, remove_file},
+    path::{Path, PathBuf},
+};
 
-use super::{dynamic::DynamicMode, r#static::StaticMode, SelectionMode};
+use anyhow::bail;
+use cargo::{
+    core::{Package, PackageIdSpec, PackageIdSpecQuery, TargetKind, Workspace},
+    util::command_prelude::*,
+    CargoResult,
+};
+use itertools::Itertools;
+use rustyrts::{
+    constants::{DIR_DYNAMIC, DIR_GENERAL, DIR_SNAPSHOTS, DIR_STATIC},
+    fs_utils::{CacheFileDescr, CacheFileKind},
+};
+
+use super::{basic::BasicMode, dynamic::DynamicMode, r#static::StaticMode, SelectionMode};
+use crate::target_hash::get_target_hash;
 
 pub fn cli() -> Command {
-    subcommand("clean").about("Clean cache directory")
+    subcommand("clean")
+        .about(
+            r"Clean the target directories of RustyRTS
+
+By default, `cargo clean` is run in the target directories of all modes.
+With `--metadata-only`, only the metadata of RustyRTS is removed, such that all tests are executed on the next run.
+Compiled dependencies are kept, only the packages whose metadata has been removed are compiled again.",
+        )
+        .arg(flag("static", "Clean the target directory of static mode"))
+        .arg(flag("dynamic", "Clean the target directory of dynamic mode"))
+        .arg(flag("basic", "Clean the target directory of basic mode"))
+        .arg(flag(
+            "metadata-only",
+            "Remove only the metadata of RustyRTS instead of all artifacts",
+        ))
+        .arg_package_spec_simple("Package to clean artifacts and metadata for")
+        .arg_target_dir()
+        .arg_manifest_path()
 }
 
 fn cargo() -> std::process::Command {
@@ -15,17 +49,230 @@ fn cargo() -> std::process::Command {
 pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
     let ws = args.workspace(config)?;
 
-    for mode in [
-        &StaticMode as &dyn SelectionMode,
-        &DynamicMode as &dyn SelectionMode,
-    ] {
-        let target_dir = mode.default_target_dir(ws.target_dir().into_path_unlocked());
+    let specs = args
+        .get_many::<String>("package")
+        .unwrap_or_default()
+        .collect_vec();
+    let packages = if specs.is_empty() {
+        None
+    } else {
+        Some(packages(&ws, &specs)?)
+    };
+    let metadata_only = args.flag("metadata-only");
 
-        let mut cmd = cargo();
-        cmd.arg("clean");
-        cmd.env("CARGO_TARGET_DIR", target_dir);
-        cmd.status().unwrap();
+    for target_dir in target_dirs(&ws, config, args)? {
+        if !metadata_only {
+            let mut cmd = cargo();
+            cmd.arg("clean");
+            cmd.arg("--manifest-path").arg(ws.root_manifest());
+            for spec in &specs {
+                cmd.arg("-p").arg(spec);
+            }
+            cmd.env("CARGO_TARGET_DIR", &target_dir);
+            if !cmd.status()?.success() {
+                return Err(anyhow::format_err!("Failed to clean {}", target_dir.display()).into());
+            }
+        }
+
+        match &packages {
+            // `cargo clean` has removed the metadata already
+            None if !metadata_only => {}
+            None => {
+                for dir in [DIR_GENERAL, DIR_STATIC, DIR_DYNAMIC, DIR_SNAPSHOTS] {
+                    let path = target_dir.join(dir);
+                    if path.is_dir() {
+                        remove_dir_all(path)?;
+                    }
+                }
+                let members = ws.members().collect_vec();
+                remove_fingerprints(&target_dir, &members)?;
+                config.shell().status(
+                    "Removed",
+                    format!("metadata of RustyRTS in {}", target_dir.display()),
+                )?;
+            }
+            Some(packages) => {
+                let files = PackageFiles::new(&ws, packages);
+                for dir in [DIR_GENERAL, DIR_STATIC, DIR_DYNAMIC, DIR_SNAPSHOTS] {
+                    files.remove(&target_dir.join(dir))?;
+                }
+                if metadata_only {
+                    remove_fingerprints(&target_dir, packages)?;
+                }
+                config.shell().status(
+                    "Removed",
+                    format!(
+                        "metadata of RustyRTS for {} in {}",
+                        packages.iter().map(|pkg| pkg.name()).join(", "),
+                        target_dir.display()
+                    ),
+                )?;
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Determines the target directories of the modes that have been selected, or of all modes
+fn target_dirs(
+    ws: &Workspace<'_>,
+    config: &Config,
+    args: &ArgMatches,
+) -> CargoResult<Vec<PathBuf>> {
+    let target_dir = ws.target_dir().into_path_unlocked();
+    if config.target_dir()?.is_some() {
+        return Ok(vec![target_dir]);
+    }
+
+    let basic = BasicMode::new();
+    let modes = [
+        ("static", &StaticMode::default() as &dyn SelectionMode),
+        ("dynamic", &DynamicMode as &dyn SelectionMode),
+        ("basic", &basic as &dyn SelectionMode),
+    ];
+    let all = !modes.iter().any(|(name, _)| args.flag(name));
+
+    Ok(modes
+        .into_iter()
+        .filter(|(name, _)| all || args.flag(name))
+        .map(|(_, mode)| mode.default_target_dir(target_dir.clone()))
+        .collect())
+}
+
+/// Resolves the packages to be cleaned, which need to be members of the workspace
+fn packages<'a>(ws: &'a Workspace<'_>, specs: &[&String]) -> CargoResult<Vec<&'a Package>> {
+    let mut packages = Vec::new();
+    for spec in specs {
+        let id_spec = PackageIdSpec::parse(spec)?;
+        let matching = ws
+            .members()
+            .filter(|pkg| id_spec.matches(pkg.package_id()))
+            .collect_vec();
+        if matching.is_empty() {
+            bail!("Package `{spec}` is not a member of the workspace");
+        }
+        packages.extend(matching);
+    }
+    Ok(packages
+        .into_iter()
+        .unique_by(|pkg| pkg.package_id())
+        .collect())
+}
+
+/// Removes the fingerprints of cargo for the given packages, such that they are compiled again
+///
+/// Compiling them again creates the metadata of RustyRTS that has been removed.
+fn remove_fingerprints(target_dir: &Path, packages: &[&Package]) -> CargoResult<()> {
+    // Fingerprints are located in `<profile>/.fingerprint` or `<target triple>/<profile>/.fingerprint`
+    let mut dirs = Vec::new();
+    for entry in read_dir(target_dir).into_iter().flatten().flatten() {
+        dirs.push(entry.path().join(".fingerprint"));
+        for entry in read_dir(entry.path()).into_iter().flatten().flatten() {
+            dirs.push(entry.path().join(".fingerprint"));
+        }
+    }
+
+    for dir in dirs {
+        let Ok(entries) = read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some((name, hash)) = file_name.to_str().and_then(|name| name.rsplit_once('-'))
+            else {
+                continue;
+            };
+            if hash.len() == 16
+                && hash.chars().all(|c| c.is_ascii_hexdigit())
+                && packages.iter().any(|pkg| pkg.name().as_str() == name)
+            {
+                remove_dir_all(entry.path())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Identifies the files of the metadata that belong to some packages
+struct PackageFiles {
+    /// Names of crates of all members of the workspace, to split the names of doctests off the names of crates
+    crate_names: Vec<String>,
+    /// Names of the crates of the targets of the packages, together with the hashes that distinguish them
+    units: Vec<String>,
+    /// Names of the crates of the packages that may contain doctests
+    doctested: Vec<String>,
+    /// Names of the crates of all targets of the packages, which prefix the names of their tests
+    crates: Vec<String>,
+}
+
+impl PackageFiles {
+    fn new(ws: &Workspace<'_>, packages: &[&Package]) -> Self {
+        let targets = packages
+            .iter()
+            .flat_map(|pkg| pkg.targets().iter().map(move |target| (pkg, target)))
+            .collect_vec();
+
+        Self {
+            crate_names: ws
+                .members()
+                .flat_map(|pkg| pkg.targets().iter().map(|target| target.crate_name()))
+                .collect(),
+            units: targets
+                .iter()
+                .map(|(pkg, target)| {
+                    format!(
+                        "{}-{}",
+                        target.crate_name(),
+                        get_target_hash(pkg.package_id(), pkg.root(), target)
+                    )
+                })
+                .collect(),
+            doctested: targets
+                .iter()
+                .filter(|(_, target)| matches!(target.kind(), TargetKind::Lib(_)))
+                .map(|(_, target)| target.crate_name())
+                .collect(),
+            crates: targets
+                .iter()
+                .map(|(_, target)| target.crate_name())
+                .collect(),
+        }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        let Ok(descr) = CacheFileDescr::try_from(path) else {
+            return false;
+        };
+        let descr = descr.with_crate_names(self.crate_names.iter().map(String::as_str));
+
+        match descr.compile_mode {
+            Some("Doctest") => self.doctested.iter().any(|name| name == descr.crate_name),
+            Some(_) => self.units.iter().any(|name| name == descr.crate_name),
+            // Traces of doctests are not prefixed by the crate name, they are invalidated by the removed checksums
+            None if descr.kind == CacheFileKind::Traces => descr
+                .crate_name
+                .split_once("::")
+                .is_some_and(|(name, _)| self.crates.iter().any(|krate| krate == name)),
+            None => false,
+        }
+    }
+
+    /// Removes the files of the packages from `dir`, including those in subdirectories
+    fn remove(&self, dir: &Path) -> CargoResult<()> {
+        let Ok(entries) = read_dir(dir) else {
+            return Ok(());
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                self.remove(&path)?;
+            } else if self.contains(&path) {
+                remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
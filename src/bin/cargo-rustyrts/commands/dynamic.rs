@@ -32,7 +32,7 @@ use rustyrts::{
         ENDING_CHANGES, ENDING_PROCESS_TRACE, ENV_COMPILE_MODE, ENV_DOCTESTED,
         ENV_ONLY_INSTRUMENTATION, ENV_TARGET, ENV_TARGET_DIR,
     },
-    fs_utils::{CacheFileDescr, CacheFileKind, CacheKind},
+    fs_utils::{Accessed, CacheFileDescr, CacheFileKind, CacheKind, ChecksumKind},
 };
 use test::{test::parse_opts, TestOpts};
 use tracing::debug;
@@ -40,12 +40,12 @@ use tracing::debug;
 use crate::{
     commands::{DoctestName, TestInfo},
     ops::PreciseExecutor,
-    target_hash::get_target_hash,
+    target_hash::get_unit_target_hash,
 };
 
 use super::{
-    cache::HashCache, DependencyUnit, PreciseSelectionMode, Selection, SelectionContext,
-    SelectionMode, SelectionUnit, Selector, TestUnit,
+    cache::HashCache, check_changed_files, located_nodes, DependencyUnit, PreciseSelectionMode,
+    Selection, SelectionContext, SelectionMode, SelectionUnit, Selector, TestEnv, TestUnit,
 };
 
 pub fn cli() -> Command {
@@ -70,6 +70,33 @@ Consider using `cargo rustyrts static` instead if your tests rely on random inpu
         )
         .arg(flag("doc", "Test only this library's documentation"))
         .arg(flag("no-run", "Compile, but don't run tests"))
+        .arg(
+            flag(
+                "list-selected",
+                "Compile and select tests, but only list selected and deselected tests instead of running them",
+            )
+            .conflicts_with("no-run"),
+        )
+        .arg(
+            opt("format", "Output format used with --list-selected")
+                .value_name("FMT")
+                .value_parser(["human", "json"])
+                .requires("list-selected"),
+        )
+        .arg(
+            opt(
+                "baseline",
+                "Select tests affected by changes since the merge-base of HEAD and this git revision",
+            )
+            .value_name("REV"),
+        )
+        .arg(
+            opt(
+                "shard",
+                "Execute only a share of the selected tests, to distribute them over several jobs",
+            )
+            .value_name("INDEX/COUNT"),
+        )
         .arg_ignore_rust_version()
         .arg_future_incompat_report()
         .arg_message_format()
@@ -162,6 +189,24 @@ impl PreciseSelectionMode for DynamicMode {
         }
     }
 
+    fn snapshot_kinds(&self) -> Vec<(CacheKind, CacheFileKind)> {
+        vec![
+            (
+                CacheKind::General,
+                CacheFileKind::Checksums(ChecksumKind::Checksum),
+            ),
+            (
+                CacheKind::General,
+                CacheFileKind::Checksums(ChecksumKind::VtblChecksum),
+            ),
+            (
+                CacheKind::General,
+                CacheFileKind::Checksums(ChecksumKind::ConstChecksum),
+            ),
+            (CacheKind::Dynamic, CacheFileKind::Traces),
+        ]
+    }
+
     fn selection_context<'context, 'arena: 'context>(
         &self,
         _ws: &Workspace<'_>,
@@ -243,8 +288,7 @@ impl<'arena: 'context, 'context> DynamicSelector<'arena, 'context> {
                     u.mode,
                     u
                 );
-                let crate_name =
-                    format!("{}-{}", u.target.crate_name(), get_target_hash(&u.target));
+                let crate_name = format!("{}-{}", u.target.crate_name(), get_unit_target_hash(u));
                 (u, crate_name, None)
             }
             DependencyUnit::DoctestUnit(u, s) => {
@@ -310,6 +354,7 @@ impl<'arena, 'context> DynamicSelector<'arena, 'context> {
 
 fn print_stats(
     shell: &mut Shell,
+    target_dir: &Path,
     maybe_changed_nodes: Option<&HashSet<ArenaIntern<'_, String>>>,
     traced_tests: &HashSet<ArenaIntern<'_, String>>,
     tests_found: &HashSet<ArenaIntern<'_, String>>,
@@ -343,7 +388,9 @@ fn print_stats(
     })?;
     if let Some(changed_nodes) = maybe_changed_nodes {
         shell.verbose(|shell| {
-            shell.print_ansi_stderr(format!("\nChanged: {changed_nodes:?}\n").as_bytes())
+            shell.print_ansi_stderr(
+                format!("\nChanged:\n{}", located_nodes(target_dir, changed_nodes)).as_bytes(),
+            )
         })?;
     }
     shell.verbose(|shell| {
@@ -361,6 +408,7 @@ fn print_stats(
 
 fn print_doctest_stats<T: Display>(
     shell: &mut Shell,
+    target_dir: &Path,
     status: T,
     changed_nodes: &HashSet<ArenaIntern<'_, String>>,
 ) -> CargoResult<()> {
@@ -371,12 +419,26 @@ fn print_doctest_stats<T: Display>(
     })?;
 
     shell.verbose(|shell| {
-        shell.print_ansi_stderr(format!("\nChanged: {changed_nodes:?}\n").as_bytes())
+        shell.print_ansi_stderr(
+            format!("\nChanged:\n{}", located_nodes(target_dir, changed_nodes)).as_bytes(),
+        )
     })?;
 
     Ok(())
 }
 
+/// Whether any file or environment variable that has been accessed by a test
+/// is different from when the test has been traced
+fn accessed_changed(traces: &HashSet<ArenaIntern<'_, String>>, root: &Path, env: &TestEnv) -> bool {
+    traces.iter().any(|line| {
+        let changed = Accessed::has_changed(line, root, |name| env.var(name));
+        if changed {
+            debug!("Accessed {} has changed", line);
+        }
+        changed
+    })
+}
+
 impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context> {
     fn select_tests(
         &mut self,
@@ -388,7 +450,7 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
             return SelectionUnit::RetestAll;
         }
 
-        let TestUnit(unit, test_info) = test_unit;
+        let TestUnit(unit, test_info, env) = test_unit;
         let Some(test_info) = test_info else {
             panic!("Precise selction requires information about tests")
         };
@@ -408,6 +470,9 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
 
                 let dependency_unit = DependencyUnit::Unit(unit);
                 let changed = self.changed_nodes(dependency_unit).clone();
+                if check_changed_files(shell, &changed) {
+                    return SelectionUnit::RetestAll;
+                }
 
                 let traces: HashMap<ArenaIntern<'_, String>, HashSet<ArenaIntern<'_, String>>> = {
                     let mut map = HashMap::new();
@@ -447,6 +512,7 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
                         .iter()
                         .filter(|&(_test, traces)| {
                             traces.intersection(&changed_nodes).next().is_some()
+                                || accessed_changed(traces, unit.pkg.root(), &env)
                         })
                         .map(|(test, _traces)| test.to_string()),
                 );
@@ -455,6 +521,7 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
 
                 print_stats(
                     shell,
+                    self.target_dir,
                     Some(&changed_nodes),
                     &traced_tests,
                     &tests_found,
@@ -487,9 +554,13 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
 
                         let dependency_unit = DependencyUnit::DoctestUnit(unit, cache_name.clone());
                         let changed = self.changed_nodes(dependency_unit).clone();
+                        if check_changed_files(shell, &changed) {
+                            return SelectionUnit::RetestAll;
+                        }
 
                         print_doctest_stats(
                             shell,
+                            self.target_dir,
                             "Doc test ".to_string() + &trimmed_name,
                             &changed,
                         )
@@ -521,6 +592,8 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
                                     intersection.collect::<Vec<_>>()
                                 );
                                 affected_tests.push(trimmed_name);
+                            } else if accessed_changed(&traces, unit.pkg.root(), &env) {
+                                affected_tests.push(trimmed_name);
                             }
                         } else {
                             debug!("Found no traces");
@@ -546,6 +619,7 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
 
                 print_stats(
                     shell,
+                    self.target_dir,
                     None,
                     &traced_tests,
                     &tests_found,
@@ -559,6 +633,10 @@ impl<'arena, 'context> Selector<'context> for DynamicSelector<'arena, 'context>
         SelectionUnit::Precise(affected_tests)
     }
 
+    fn technique(&self) -> &'static str {
+        "dynamic RTS"
+    }
+
     fn cache_kind(&self) -> CacheKind {
         CacheKind::Dynamic
     }
@@ -0,0 +1,68 @@
+use cargo::util::command_prelude::*;
+
+use super::{
+    r#static::{edge_types, StaticMode},
+    Selection,
+};
+
+pub fn cli() -> Command {
+    subcommand("explain")
+        .about(
+            r"Explain why a test is selected by static regression test selection
+
+Compiles and analyzes the tests like `cargo rustyrts static`, but instead of executing them,
+prints one shortest chain of dependencies leading from a changed function to the given test.
+Since no tests are executed, changes are kept for the next invocation of `cargo rustyrts static`.",
+        )
+        .arg(
+            Arg::new("testname")
+                .value_name("TESTNAME")
+                .help("Name of the test, optionally omitting leading path segments (e.g. `test_foo` or `tests::test_foo`)")
+                .required(true),
+        )
+        .arg(
+            opt(
+                "edge-types",
+                "Types of edges that are followed, e.g. `Call,Drop,Static` or `!Contained` [default: all]",
+            )
+            .value_name("TYPES"),
+        )
+        .arg_ignore_rust_version()
+        .arg_message_format()
+        .arg_package_spec(
+            "Package to explain tests for",
+            "Explain tests of all packages in the workspace",
+            "Exclude packages from explaining",
+        )
+        .arg_targets_all(
+            "Explain only this package's library",
+            "Explain only the specified binary",
+            "Explain all binaries",
+            "Explain only the specified example",
+            "Explain all examples",
+            "Explain only the specified test target",
+            "Explain all test targets",
+            "Explain only the specified bench target",
+            "Explain all bench targets",
+            "Explain all targets (does not include doctests)",
+        )
+        .arg_features()
+        .arg_jobs()
+        .arg_release("Build artifacts in release mode, with optimizations")
+        .arg_profile("Build artifacts with the specified profile")
+        .arg_target_triple("Build for the target triple")
+        .arg_target_dir()
+        .arg_manifest_path()
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
+    let mode = StaticMode::new(edge_types(&args.workspace(config)?, args)?);
+    let selection = Selection::Precise(&mode);
+
+    let ws = super::workspace(config, args, &selection)?;
+    let compile_opts = super::compile_options(config, args, &ws)?;
+
+    let test_name = args.get_one::<String>("testname").unwrap();
+
+    crate::ops::explain_test(&ws, &compile_opts, test_name, selection)
+}
@@ -0,0 +1,469 @@
+//! The `graph` subcommand, which exports the dependency graphs of static RTS in a format that other tools can display.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context};
+use cargo::{util::command_prelude::*, CargoResult};
+use itertools::Itertools;
+use rustyrts::{
+    cache::{Cache, Graph, Technique},
+    static_rts::graph::{EdgeType, EdgeTypes},
+};
+use serde_json::json;
+
+use super::archive::target_dirs;
+
+pub fn cli() -> Command {
+    subcommand("graph")
+        .about(
+            r"Export the dependency graphs of static RTS as DOT, JSON or GraphML
+
+By default, the graphs of all units in the target directory of static mode are merged and exported entirely.
+Since these graphs comprise all functions of all crates, you may rather extract a part of them:
+ --changed          nodes that depend on changed nodes, up to a distance of --hops
+ --between T F      nodes on any chain of dependencies from test T to function F
+ --crates           a graph of crates, condensing all nodes of a crate into a single node
+
+Changed nodes are only known after compiling, but before executing tests, e.g. after `cargo rustyrts static --no-run`.",
+        )
+        .arg(
+            opt("format", "Output format")
+                .value_name("FMT")
+                .value_parser(["dot", "json", "graphml"]),
+        )
+        .arg(
+            opt("output", "Write the graph to this file instead of stdout")
+                .short('o')
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            opt(
+                "unit",
+                "Only export the graphs of units of this crate, or of the unit of this name",
+            )
+            .value_name("UNIT")
+            .action(ArgAction::Append),
+        )
+        .arg(flag(
+            "changed",
+            "Extract the nodes that depend on changed nodes",
+        ))
+        .arg(
+            opt(
+                "hops",
+                "Maximum number of edges between a changed node and an extracted node [default: 1]",
+            )
+            .value_name("K")
+            .value_parser(clap::value_parser!(usize))
+            .requires("changed"),
+        )
+        .arg(
+            opt(
+                "between",
+                "Extract the nodes on any chain of dependencies from TEST to FUNCTION",
+            )
+            .value_names(["TEST", "FUNCTION"])
+            .num_args(2)
+            .conflicts_with("changed"),
+        )
+        .arg(flag(
+            "crates",
+            "Condense the graph to a graph of crates",
+        ))
+        .arg(
+            opt(
+                "edge-types",
+                "Types of edges that are exported, e.g. `Call,Drop,Static` or `!Contained` [default: all]",
+            )
+            .value_name("TYPES"),
+        )
+        .arg_target_dir()
+        .arg_manifest_path()
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
+    let ws = args.workspace(config)?;
+
+    let edge_types = match args.get_one::<String>("edge-types") {
+        Some(policy) => policy
+            .parse::<EdgeTypes>()
+            .map_err(|err| anyhow::format_err!("Invalid value for `--edge-types`: {err}"))?,
+        None => EdgeTypes::all(),
+    };
+    let units = args
+        .get_many::<String>("unit")
+        .unwrap_or_default()
+        .map(String::as_str)
+        .collect_vec();
+
+    let (_, dir) = target_dirs(&ws, config)?
+        .into_iter()
+        .find(|(mode, _)| *mode == "static")
+        .unwrap();
+    let mut extract = Extract::load(&Cache::new(&dir), &units, *edge_types)?;
+
+    if args.flag("changed") {
+        let hops = args.get_one::<usize>("hops").copied().unwrap_or(1);
+        extract = extract.neighborhood(hops);
+    }
+    if let Some(between) = args.get_many::<String>("between") {
+        let (test, function) = between.collect_tuple().unwrap();
+        extract = extract.between(test, function)?;
+    }
+    if args.flag("crates") {
+        extract = extract.condense();
+    }
+
+    let output = match args.get_one::<String>("format").map(String::as_str) {
+        Some("json") => serde_json::to_string(&extract.to_json()).map_err(anyhow::Error::from)?,
+        Some("graphml") => extract.to_graphml(),
+        _ => extract.to_dot(),
+    };
+
+    match args.get_one::<PathBuf>("output") {
+        Some(path) => {
+            std::fs::write(path, output)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            config.shell().status(
+                "Exported",
+                format!(
+                    "{} nodes and {} edges to {}",
+                    extract.nodes.len(),
+                    extract.edges.len(),
+                    path.display()
+                ),
+            )?;
+        }
+        None => cargo::drop_print!(config, "{output}"),
+    }
+    Ok(())
+}
+
+/// The part of the dependency graphs that is exported
+#[derive(Default)]
+struct Extract {
+    nodes: BTreeSet<String>,
+    /// Types of every edge, keyed by the node that depends on the other one (e.g. the caller) and the other one
+    edges: BTreeMap<(String, String), u16>,
+    changed: BTreeSet<String>,
+}
+
+impl Extract {
+    /// Merges the graphs and changes of all units, keeping only edges that have any of `edge_types`
+    fn load(cache: &Cache, units: &[&str], edge_types: u16) -> CargoResult<Self> {
+        let units = cache
+            .graph_units()?
+            .into_iter()
+            .filter(|unit| {
+                units.is_empty()
+                    || units
+                        .iter()
+                        .any(|name| unit.name() == *name || unit.crate_name() == *name)
+            })
+            .collect_vec();
+        if units.is_empty() {
+            bail!("Did not find any dependency graph, run `cargo rustyrts static` first");
+        }
+
+        let mut graph = Graph::default();
+        let mut changed = BTreeSet::new();
+        for unit in &units {
+            graph.extend(cache.graph(unit)?);
+            changed.extend(cache.changes(Technique::Static, unit)?);
+        }
+
+        let mut edges = BTreeMap::new();
+        for end in graph.nodes() {
+            for (start, types) in graph.dependents(end) {
+                if types & edge_types != 0 {
+                    edges.insert((start.to_string(), end.to_string()), types & edge_types);
+                }
+            }
+        }
+
+        Ok(Self {
+            nodes: graph.nodes().map(ToString::to_string).collect(),
+            edges,
+            changed,
+        })
+    }
+
+    /// Keeps only the given nodes and the edges between them
+    fn restrict(self, nodes: BTreeSet<String>) -> Self {
+        Self {
+            edges: self
+                .edges
+                .into_iter()
+                .filter(|((start, end), _)| nodes.contains(start) && nodes.contains(end))
+                .collect(),
+            changed: self
+                .changed
+                .into_iter()
+                .filter(|node| nodes.contains(node))
+                .collect(),
+            nodes,
+        }
+    }
+
+    /// Nodes that are reached by following at most `hops` edges from any of the starting points
+    fn reached<'a>(
+        adjacent: &HashMap<&'a str, Vec<&'a str>>,
+        starting_points: impl IntoIterator<Item = &'a str>,
+        hops: Option<usize>,
+    ) -> BTreeSet<String> {
+        let mut queue: VecDeque<(&str, usize)> =
+            starting_points.into_iter().map(|node| (node, 0)).collect();
+        let mut reached = BTreeSet::new();
+
+        while let Some((node, distance)) = queue.pop_front() {
+            if !reached.insert(node.to_string()) || hops.is_some_and(|hops| distance >= hops) {
+                continue;
+            }
+            for next in adjacent.get(node).into_iter().flatten() {
+                queue.push_back((next, distance + 1));
+            }
+        }
+        reached
+    }
+
+    /// For every node, the nodes that depend on it
+    fn dependents(&self) -> HashMap<&str, Vec<&str>> {
+        let mut dependents: HashMap<_, Vec<_>> = HashMap::new();
+        for (start, end) in self.edges.keys() {
+            dependents
+                .entry(end.as_str())
+                .or_default()
+                .push(start.as_str());
+        }
+        dependents
+    }
+
+    /// For every node, the nodes that it depends on
+    fn dependencies(&self) -> HashMap<&str, Vec<&str>> {
+        let mut dependencies: HashMap<_, Vec<_>> = HashMap::new();
+        for (start, end) in self.edges.keys() {
+            dependencies
+                .entry(start.as_str())
+                .or_default()
+                .push(end.as_str());
+        }
+        dependencies
+    }
+
+    /// Nodes whose name is `name`, or ends with `::name`
+    fn find(&self, name: &str) -> CargoResult<Vec<&str>> {
+        let suffix = format!("::{name}");
+        let found = self
+            .nodes
+            .iter()
+            .filter(|node| *node == name || node.ends_with(&suffix))
+            .map(String::as_str)
+            .collect_vec();
+        if found.is_empty() {
+            bail!("Did not find any node named `{name}`");
+        }
+        Ok(found)
+    }
+
+    /// Extracts the changed nodes and the nodes that depend on them, up to a distance of `hops`
+    fn neighborhood(self, hops: usize) -> Self {
+        let mut nodes = Self::reached(
+            &self.dependents(),
+            self.changed.iter().map(String::as_str),
+            Some(hops),
+        );
+        // Changed nodes are kept, even if they are not part of the graph
+        nodes.extend(self.changed.iter().cloned());
+        self.restrict(nodes)
+    }
+
+    /// Extracts the nodes that `test` transitively depends on and that transitively depend on `function`
+    fn between(self, test: &str, function: &str) -> CargoResult<Self> {
+        let from_test = Self::reached(&self.dependencies(), self.find(test)?, None);
+        let to_function = Self::reached(&self.dependents(), self.find(function)?, None);
+
+        let nodes = from_test.intersection(&to_function).cloned().collect();
+        Ok(self.restrict(nodes))
+    }
+
+    /// Replaces all nodes of a crate by a single node, keeping only edges between different crates
+    fn condense(self) -> Self {
+        let mut edges = BTreeMap::new();
+        for ((start, end), types) in self.edges {
+            let (start, end) = (crate_of(&start), crate_of(&end));
+            if start != end {
+                *edges
+                    .entry((start.to_string(), end.to_string()))
+                    .or_default() |= types;
+            }
+        }
+
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| crate_of(node).to_string())
+                .collect(),
+            edges,
+            changed: self
+                .changed
+                .iter()
+                .map(|node| crate_of(node).to_string())
+                .collect(),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| json!({ "name": node, "changed": self.changed.contains(node) }))
+            .collect_vec();
+        let edges = self
+            .edges
+            .iter()
+            .map(|((start, end), types)| {
+                json!({
+                    "from": start,
+                    "to": end,
+                    "types": edge_types_of(*types).iter().map(ToString::to_string).collect_vec(),
+                })
+            })
+            .collect_vec();
+        json!({ "nodes": nodes, "edges": edges })
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph dependencies {{").unwrap();
+        writeln!(out, "    node [shape=box];").unwrap();
+        for node in &self.nodes {
+            if self.changed.contains(node) {
+                writeln!(
+                    out,
+                    "    \"{}\" [style=filled, fillcolor=lightcoral];",
+                    escape_dot(node)
+                )
+                .unwrap();
+            } else {
+                writeln!(out, "    \"{}\";", escape_dot(node)).unwrap();
+            }
+        }
+        for ((start, end), types) in &self.edges {
+            let types = edge_types_of(*types);
+            let colors = types.iter().map(|ty| color(*ty)).collect_vec();
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\", color=\"{}\", fontcolor=\"{}\"];",
+                escape_dot(start),
+                escape_dot(end),
+                types.iter().join(", "),
+                colors.join(":"),
+                colors.first().unwrap_or(&"black")
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    fn to_graphml(&self) -> String {
+        let ids: HashMap<&str, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.as_str(), i))
+            .collect();
+
+        let mut out = String::new();
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"  <key id="changed" for="node" attr.name="changed" attr.type="boolean"/>"#
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"  <key id="types" for="edge" attr.name="types" attr.type="string"/>"#
+        )
+        .unwrap();
+        writeln!(out, r#"  <graph id="dependencies" edgedefault="directed">"#).unwrap();
+        for (i, node) in self.nodes.iter().enumerate() {
+            writeln!(
+                out,
+                r#"    <node id="n{i}"><data key="name">{}</data><data key="changed">{}</data></node>"#,
+                escape_xml(node),
+                self.changed.contains(node)
+            )
+            .unwrap();
+        }
+        for ((start, end), types) in &self.edges {
+            writeln!(
+                out,
+                r#"    <edge source="n{}" target="n{}"><data key="types">{}</data></edge>"#,
+                ids[start.as_str()],
+                ids[end.as_str()],
+                edge_types_of(*types).iter().join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "  </graph>").unwrap();
+        writeln!(out, "</graphml>").unwrap();
+        out
+    }
+}
+
+/// Names of nodes are prefixed by the name of their crate, e.g. `foo::<Bar as Display>::fmt`
+fn crate_of(node: &str) -> &str {
+    node.split_once("::").map_or(node, |(krate, _)| krate)
+}
+
+fn edge_types_of(types: u16) -> Vec<EdgeType> {
+    EdgeType::ALL
+        .into_iter()
+        .filter(|ty| types & u16::from(*ty) != 0)
+        .collect()
+}
+
+fn color(edge_type: EdgeType) -> &'static str {
+    match edge_type {
+        EdgeType::Call => "black",
+        EdgeType::Unsize => "darkorange",
+        EdgeType::Contained => "gray",
+        EdgeType::Drop => "firebrick",
+        EdgeType::Static => "darkgreen",
+        EdgeType::ReifyPtr => "blue",
+        EdgeType::FnPtr => "purple",
+        EdgeType::Asm => "brown",
+        EdgeType::ClosurePtr => "deeppink",
+        EdgeType::Intrinsic => "cadetblue",
+        EdgeType::LangItem => "goldenrod",
+        EdgeType::Trimmed => "lightgray",
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,125 @@
+//! The `history` subcommand, which prints the recorded outcomes and durations of a test.
+
+use std::{
+    collections::BTreeMap,
+    fs::read_dir,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use cargo::{util::command_prelude::*, CargoResult};
+use rustyrts::constants::{DIR_GENERAL, ENDING_OUTCOMES};
+
+use super::archive::target_dirs;
+use crate::ops::outcomes::{HistoryEntry, Outcome, TestHistory};
+
+pub fn cli() -> Command {
+    subcommand("history")
+        .about(
+            r"Print the recorded outcomes and durations of a test
+
+Outcomes are recorded whenever tests are executed by any mode of RustyRTS.
+Durations are available in dynamic mode, or if libtest is asked to report them
+using `-- -Z unstable-options --report-time`.",
+        )
+        .arg(
+            Arg::new("testname")
+                .value_name("TESTNAME")
+                .help("Name of the test, optionally omitting leading path segments (e.g. `test_foo` or `tests::test_foo`)")
+                .required(true),
+        )
+        .arg_target_dir()
+        .arg_manifest_path()
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
+    let ws = args.workspace(config)?;
+    let test_name = args.get_one::<String>("testname").unwrap();
+    let suffix = format!("::{test_name}");
+
+    // Runs of every matching test, grouped by the test unit and the mode that executed it
+    let mut runs: BTreeMap<(String, String, Option<&str>), Vec<HistoryEntry>> = BTreeMap::new();
+
+    let target_dirs = target_dirs(&ws, config)?;
+    // If all modes share the same target directory, the mode that executed a test is not known
+    let shared = target_dirs.iter().all(|(_, dir)| *dir == target_dirs[0].1);
+
+    let mut visited = Vec::new();
+    for (mode, target_dir) in target_dirs {
+        if visited.contains(&target_dir) {
+            continue;
+        }
+        let mode = if shared { None } else { Some(mode) };
+
+        for history in read_histories(&target_dir.join(DIR_GENERAL))? {
+            let TestHistory {
+                description,
+                entries,
+            } = history;
+            for entry in entries {
+                if entry.test == *test_name || entry.test.ends_with(&suffix) {
+                    runs.entry((entry.test.clone(), description.clone(), mode))
+                        .or_default()
+                        .push(entry);
+                }
+            }
+        }
+        visited.push(target_dir);
+    }
+
+    if runs.is_empty() {
+        return Err(anyhow::format_err!(
+            "Did not find any recorded run of a test named `{test_name}`"
+        )
+        .into());
+    }
+
+    for ((test, description, mode), entries) in runs {
+        match mode {
+            Some(mode) => cargo::drop_println!(config, "{test} ({description}, {mode} mode)"),
+            None => cargo::drop_println!(config, "{test} ({description})"),
+        }
+        for entry in &entries {
+            let time = humantime::format_rfc3339_seconds(
+                UNIX_EPOCH + Duration::from_secs(entry.timestamp),
+            );
+            let duration = entry
+                .duration
+                .map_or("-".to_string(), |d| format!("{:.3}s", d.as_secs_f64()));
+            cargo::drop_println!(config, "    {time}  {:<7}  {duration}", entry.outcome);
+        }
+
+        let count = |outcome| entries.iter().filter(|e| e.outcome == outcome).count();
+        let durations: Vec<_> = entries.iter().filter_map(|e| e.duration).collect();
+        let average = if durations.is_empty() {
+            String::new()
+        } else {
+            let average = durations.iter().sum::<Duration>() / durations.len() as u32;
+            format!(", average duration {:.3}s", average.as_secs_f64())
+        };
+        cargo::drop_println!(
+            config,
+            "    {} runs: {} passed, {} failed, {} ignored{average}",
+            entries.len(),
+            count(Outcome::Passed),
+            count(Outcome::Failed),
+            count(Outcome::Ignored),
+        );
+    }
+
+    Ok(())
+}
+
+fn read_histories(dir: &std::path::Path) -> CargoResult<Vec<TestHistory>> {
+    let Ok(entries) = read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut histories = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(ENDING_OUTCOMES) {
+            histories.extend(TestHistory::read(&path));
+        }
+    }
+    Ok(histories)
+}
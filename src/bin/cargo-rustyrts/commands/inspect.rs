@@ -0,0 +1,465 @@
+//! The `inspect` subcommand, which decodes the metadata of RustyRTS into a human-readable form.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{read, read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use cargo::{core::Workspace, util::command_prelude::*, CargoResult};
+use itertools::Itertools;
+use rustyrts::{
+    cache::{Cache, CachedUnit, Checksums, Graph, Span, Technique},
+    constants::{DIR_DYNAMIC, DIR_GENERAL, DIR_SNAPSHOTS, DIR_STATIC},
+    fs_utils::{CacheFileDescr, CacheFileKind},
+    static_rts::graph::EdgeType,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::archive::target_dirs;
+use crate::ops::outcomes::TestHistory;
+
+pub fn cli() -> Command {
+    subcommand("inspect")
+        .about(
+            r"Decode the metadata of RustyRTS into a human-readable form
+
+Without FILE, lists all files of the metadata together with the unit they belong to,
+followed by a summary of every unit: the size of its dependency graph, its tests and its pending changes.
+With FILE, prints the decoded content of this file as JSON.",
+        )
+        .arg(
+            Arg::new("file")
+                .value_name("FILE")
+                .help("Path of a single file to decode, relative to the current directory or to a target directory")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            opt("format", "Output format of the listing")
+                .value_name("FMT")
+                .value_parser(["human", "json"]),
+        )
+        .arg_target_dir()
+        .arg_manifest_path()
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
+    let ws = args.workspace(config)?;
+
+    if let Some(file) = args.get_one::<PathBuf>("file") {
+        let path = resolve(&ws, config, file)?;
+        let decoded = decode(&ws, &path)?;
+        cargo::drop_println!(
+            config,
+            "{}",
+            serde_json::to_string_pretty(&decoded).map_err(anyhow::Error::from)?
+        );
+        return Ok(());
+    }
+
+    let (files, units) = collect(&ws, config)?;
+    match args.get_one::<String>("format").map(String::as_str) {
+        Some("json") => {
+            let listing = json!({ "files": files, "units": units });
+            cargo::drop_println!(
+                config,
+                "{}",
+                serde_json::to_string(&listing).map_err(anyhow::Error::from)?
+            );
+        }
+        _ => print_listing(config, &files, &units),
+    }
+    Ok(())
+}
+
+/// A single file of the metadata
+#[derive(Serialize)]
+struct ListedFile {
+    path: PathBuf,
+    kind: Option<String>,
+    #[serde(rename = "crate")]
+    crate_name: Option<String>,
+    hash: Option<String>,
+    compile_mode: Option<String>,
+    target: Option<String>,
+    doctest: Option<String>,
+    /// Name of the test, in case of files that belong to a single test, e.g. traces
+    test: Option<String>,
+}
+
+/// The size of the dependency graph, the tests and the pending changes of a single unit
+#[derive(Serialize)]
+struct UnitSummary {
+    /// Target directory of the unit, relative to the target directory of the workspace
+    dir: PathBuf,
+    name: String,
+    nodes: usize,
+    edges: usize,
+    edge_types: BTreeMap<String, usize>,
+    tests: Vec<String>,
+    failed: Vec<String>,
+    static_changes: BTreeSet<String>,
+    dynamic_changes: BTreeSet<String>,
+}
+
+/// Names of all crates of the workspace, which are needed to split the names of doctests off the names of crates
+fn crate_names(ws: &Workspace<'_>) -> Vec<String> {
+    ws.members()
+        .flat_map(|pkg| pkg.targets().iter().map(|target| target.crate_name()))
+        .unique()
+        .collect()
+}
+
+fn parse<'a>(path: &'a Path, crate_names: &[String]) -> Option<CacheFileDescr<'a>> {
+    CacheFileDescr::try_from(path)
+        .ok()
+        .map(|descr| descr.with_crate_names(crate_names.iter().map(String::as_str)))
+}
+
+/// Splits the hash that distinguishes targets of the same name off the name of a crate
+fn split_hash(crate_name: &str) -> (&str, Option<&str>) {
+    match crate_name.rsplit_once('-') {
+        Some((name, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            (name, Some(hash))
+        }
+        _ => (crate_name, None),
+    }
+}
+
+fn collect(
+    ws: &Workspace<'_>,
+    config: &Config,
+) -> CargoResult<(Vec<ListedFile>, Vec<UnitSummary>)> {
+    let target_root = ws.target_dir().into_path_unlocked();
+    let crate_names = crate_names(ws);
+
+    let mut files = Vec::new();
+    let mut units = Vec::new();
+    let mut visited = Vec::new();
+    for (_, target_dir) in target_dirs(ws, config)? {
+        // If several modes use the same target directory, the metadata is only listed once
+        if visited.contains(&target_dir) {
+            continue;
+        }
+        let relative = target_dir
+            .strip_prefix(&target_root)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+
+        let mut paths = Vec::new();
+        for dir in [DIR_GENERAL, DIR_STATIC, DIR_DYNAMIC, DIR_SNAPSHOTS] {
+            collect_files(&target_dir.join(dir), &mut paths)?;
+        }
+        paths.sort();
+
+        // Units are identified by the stems of their files, snapshots of previous commits are not summarized
+        let mut unit_names = BTreeSet::new();
+        for path in &paths {
+            let descr = parse(path, &crate_names);
+            if let Some(descr) = &descr {
+                if descr.compile_mode.is_some() && !path.starts_with(target_dir.join(DIR_SNAPSHOTS))
+                {
+                    if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                        unit_names.insert(stem.to_string());
+                    }
+                }
+            }
+
+            let (crate_name, hash, test) = match &descr {
+                Some(descr) if descr.compile_mode.is_some() => {
+                    let (crate_name, hash) = split_hash(descr.crate_name);
+                    (
+                        Some(crate_name.to_string()),
+                        hash.map(ToString::to_string),
+                        None,
+                    )
+                }
+                Some(descr) => (None, None, Some(descr.crate_name.to_string())),
+                None => (None, None, None),
+            };
+            files.push(ListedFile {
+                path: path
+                    .strip_prefix(&target_root)
+                    .unwrap_or(path)
+                    .to_path_buf(),
+                kind: descr.as_ref().map(|descr| descr.kind.as_ref().to_string()),
+                crate_name,
+                hash,
+                compile_mode: descr
+                    .as_ref()
+                    .and_then(|descr| descr.compile_mode.map(ToString::to_string)),
+                target: descr
+                    .as_ref()
+                    .and_then(|descr| descr.target.map(ToString::to_string)),
+                doctest: descr
+                    .as_ref()
+                    .and_then(|descr| descr.doctest_name.map(ToString::to_string)),
+                test,
+            });
+        }
+
+        let cache = Cache::new(&target_dir);
+        for name in unit_names {
+            units.push(summarize(&cache, &relative, CachedUnit::new(name))?);
+        }
+        visited.push(target_dir);
+    }
+
+    Ok((files, units))
+}
+
+/// Collects all files of `dir`, including those of subdirectories
+fn collect_files(dir: &Path, acc: &mut Vec<PathBuf>) -> CargoResult<()> {
+    let Ok(entries) = read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), acc)?;
+        } else {
+            acc.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn summarize(cache: &Cache, dir: &Path, unit: CachedUnit) -> CargoResult<UnitSummary> {
+    let graph = cache.graph(&unit)?;
+
+    let mut edges = 0;
+    let mut edge_types = BTreeMap::new();
+    for node in graph.nodes() {
+        for (_, types) in graph.dependents(node) {
+            edges += 1;
+            for edge_type in edge_types_of(types) {
+                *edge_types.entry(edge_type).or_default() += 1;
+            }
+        }
+    }
+
+    Ok(UnitSummary {
+        dir: dir.to_path_buf(),
+        nodes: graph.nodes().count(),
+        edges,
+        edge_types,
+        tests: cache.tests(&unit)?,
+        failed: cache.failed_tests(&unit)?,
+        static_changes: cache.changes(Technique::Static, &unit)?,
+        dynamic_changes: cache.changes(Technique::Dynamic, &unit)?,
+        name: unit.name().to_string(),
+    })
+}
+
+/// Names of the `EdgeType`s that are contained in a bitmap
+fn edge_types_of(types: u16) -> Vec<String> {
+    (0..u16::BITS)
+        .map(|i| 1 << i)
+        .filter(|bit| types & bit != 0)
+        .map(|bit| match EdgeType::try_from(bit) {
+            Ok(edge_type) => edge_type.to_string(),
+            Err(_) => format!("Unknown({bit})"),
+        })
+        .collect()
+}
+
+fn print_listing(config: &Config, files: &[ListedFile], units: &[UnitSummary]) {
+    for file in files {
+        let kind = file.kind.as_deref().unwrap_or("unknown");
+        match (&file.crate_name, &file.compile_mode, &file.target) {
+            (Some(crate_name), Some(compile_mode), Some(target)) => {
+                let doctest = file
+                    .doctest
+                    .as_ref()
+                    .map(|doctest| format!(", doctest {doctest}"))
+                    .unwrap_or_default();
+                cargo::drop_println!(
+                    config,
+                    "{} ({kind}): {crate_name} ({compile_mode} {target}{doctest})",
+                    file.path.display()
+                );
+            }
+            _ => match &file.test {
+                Some(test) => {
+                    cargo::drop_println!(config, "{} ({kind}): {test}", file.path.display())
+                }
+                None => cargo::drop_println!(config, "{} ({kind})", file.path.display()),
+            },
+        }
+    }
+
+    for unit in units {
+        cargo::drop_println!(config, "");
+        let location = if unit.dir.as_os_str().is_empty() {
+            String::new()
+        } else {
+            format!(" in {}", unit.dir.display())
+        };
+        cargo::drop_println!(config, "{}{location}", unit.name);
+
+        let edge_types = unit
+            .edge_types
+            .iter()
+            .map(|(edge_type, count)| format!("{edge_type}: {count}"))
+            .join(", ");
+        if edge_types.is_empty() {
+            cargo::drop_println!(config, "    {} nodes, {} edges", unit.nodes, unit.edges);
+        } else {
+            cargo::drop_println!(
+                config,
+                "    {} nodes, {} edges ({edge_types})",
+                unit.nodes,
+                unit.edges
+            );
+        }
+        cargo::drop_println!(
+            config,
+            "    {} tests, {} failed",
+            unit.tests.len(),
+            unit.failed.len()
+        );
+        for (technique, changes) in [
+            ("static", &unit.static_changes),
+            ("dynamic", &unit.dynamic_changes),
+        ] {
+            for change in changes {
+                cargo::drop_println!(config, "    changed ({technique}) {change}");
+            }
+        }
+    }
+}
+
+/// Finds a file given either relative to the current directory or to any of the target directories
+fn resolve(ws: &Workspace<'_>, config: &Config, file: &Path) -> CargoResult<PathBuf> {
+    if file.is_file() {
+        return Ok(file.to_path_buf());
+    }
+    let target_root = ws.target_dir().into_path_unlocked();
+    for candidate in std::iter::once(target_root).chain(
+        target_dirs(ws, config)?
+            .into_iter()
+            .map(|(_, target_dir)| target_dir),
+    ) {
+        let path = candidate.join(file);
+        if path.is_file() {
+            return Ok(path);
+        }
+    }
+    bail!("Cache file {} does not exist", file.display())
+}
+
+/// Decodes a single file of the metadata
+fn decode(ws: &Workspace<'_>, path: &Path) -> CargoResult<Value> {
+    let crate_names = crate_names(ws);
+    let Some(descr) = parse(path, &crate_names) else {
+        bail!(
+            "{} is not a file of the metadata of RustyRTS",
+            path.display()
+        );
+    };
+    let read_failed = || format!("Failed to read {}", path.display());
+
+    let content = match descr.kind {
+        CacheFileKind::Graph => {
+            let graph = Graph::deserialize(&read(path).with_context(read_failed)?)
+                .with_context(|| format!("Failed to decode {}", path.display()))?;
+            let edges = graph
+                .nodes()
+                .flat_map(|end| {
+                    graph.dependents(end).map(move |(start, types)| {
+                        json!({ "from": start, "to": end, "types": edge_types_of(types) })
+                    })
+                })
+                .collect_vec();
+            json!({ "nodes": graph.nodes().collect_vec(), "edges": edges })
+        }
+        CacheFileKind::Checksums(_) => {
+            let checksums = Checksums::try_from(read(path).with_context(read_failed)?.as_slice())
+                .with_context(|| format!("Failed to decode {}", path.display()))?;
+            let checksums: BTreeMap<&str, BTreeSet<String>> = checksums
+                .iter()
+                .map(|(node, checksums)| {
+                    let checksums = checksums
+                        .iter()
+                        .map(|(first, second)| format!("{first:016x}{second:016x}"))
+                        .collect();
+                    (node.as_str(), checksums)
+                })
+                .collect();
+            json!(checksums)
+        }
+        CacheFileKind::Outcomes => {
+            let history = TestHistory::read(path).with_context(read_failed)?;
+            let runs = history
+                .entries
+                .iter()
+                .map(|entry| {
+                    json!({
+                        "timestamp": entry.timestamp,
+                        "test": entry.test,
+                        "outcome": entry.outcome.to_string(),
+                        "duration": entry.duration.map(|duration| duration.as_secs_f64()),
+                    })
+                })
+                .collect_vec();
+            json!({ "description": history.description, "runs": runs })
+        }
+        CacheFileKind::Fingerprint => {
+            let content = read_to_string(path).with_context(read_failed)?;
+            let entries: BTreeMap<&str, &str> = content
+                .lines()
+                .filter_map(|line| line.split_once(": "))
+                .collect();
+            json!(entries)
+        }
+        CacheFileKind::Files => {
+            let content = read_to_string(path).with_context(read_failed)?;
+            let checksums: BTreeMap<&str, &str> = content
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .map(|(checksum, path)| (path, checksum))
+                .collect();
+            json!(checksums)
+        }
+        CacheFileKind::Spans => {
+            let content = read_to_string(path).with_context(read_failed)?;
+            let spans: BTreeMap<&str, Value> = content
+                .lines()
+                .filter_map(Span::decode)
+                .map(|(name, span)| {
+                    (
+                        name,
+                        json!({ "file": span.file, "start": span.start, "end": span.end }),
+                    )
+                })
+                .collect();
+            json!(spans)
+        }
+        _ => {
+            let content = read_to_string(path).with_context(read_failed)?;
+            json!(content
+                .lines()
+                .filter(|line| !line.is_empty())
+                .collect_vec())
+        }
+    };
+
+    let (crate_name, hash) = match descr.compile_mode {
+        Some(_) => split_hash(descr.crate_name),
+        None => (descr.crate_name, None),
+    };
+    Ok(json!({
+        "path": path,
+        "kind": descr.kind.as_ref(),
+        "crate": descr.compile_mode.map(|_| crate_name),
+        "test": descr.compile_mode.map_or(Some(crate_name), |_| None),
+        "hash": hash,
+        "compile_mode": descr.compile_mode,
+        "target": descr.target,
+        "doctest": descr.doctest_name,
+        "content": content,
+    }))
+}
@@ -0,0 +1,197 @@
+//! Merging the metadata of several target directories, e.g. of jobs that executed different shards of the tests.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{create_dir_all, read, read_dir},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use cargo::{core::Workspace, util::command_prelude::*, CargoResult};
+use itertools::Itertools;
+use rustyrts::{
+    checksums::Checksums,
+    constants::{
+        DIR_DYNAMIC, DIR_GENERAL, DIR_SNAPSHOTS, DIR_STATIC, ENDING_CHECKSUM,
+        ENDING_CHECKSUM_CONST, ENDING_CHECKSUM_VTBL, ENDING_FAILED, ENDING_FILES,
+        ENDING_FINGERPRINT, ENDING_OUTCOMES, ENDING_TEST,
+    },
+    fs_utils::write_to_file,
+};
+
+use super::archive::{is_cached, target_dirs};
+use crate::ops::outcomes::TestHistory;
+
+/// How the copies of a file, found in several target directories, are combined
+enum Strategy {
+    /// Checksums are the baseline of the next run, they need to be the same everywhere
+    Checksums,
+    /// Build configurations and checksums of declared files, which need to be the same everywhere as well
+    Identical,
+    /// Tests and failed tests of a test unit
+    Union,
+    /// Outcomes and durations of the tests of a test unit
+    History,
+    /// Traces are only updated for tests that have been executed, so the most recent one is kept
+    Newest,
+}
+
+impl Strategy {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ENDING_CHECKSUM | ENDING_CHECKSUM_VTBL | ENDING_CHECKSUM_CONST) => {
+                Strategy::Checksums
+            }
+            Some(ENDING_FINGERPRINT | ENDING_FILES) => Strategy::Identical,
+            Some(ENDING_TEST | ENDING_FAILED) => Strategy::Union,
+            Some(ENDING_OUTCOMES) => Strategy::History,
+            _ => Strategy::Newest,
+        }
+    }
+}
+
+/// Merges the metadata of `sources` and writes the result to the target directory of the workspace
+///
+/// The sources are expected to have the same layout as the target directory of the workspace.
+/// Files that are present in the target directory, but in none of the sources are kept.
+///
+/// Returns the number of files that have been written.
+pub(crate) fn merge(
+    ws: &Workspace<'_>,
+    config: &Config,
+    sources: &[PathBuf],
+) -> CargoResult<usize> {
+    for source in sources {
+        if !source.is_dir() {
+            bail!("Target directory {} does not exist", source.display());
+        }
+    }
+
+    let target_root = ws.target_dir().into_path_unlocked();
+
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut visited = Vec::new();
+    for (_, target_dir) in target_dirs(ws, config)? {
+        // If several modes use the same target directory, the metadata is only merged once
+        if visited.contains(&target_dir) {
+            continue;
+        }
+
+        let relative = target_dir
+            .strip_prefix(&target_root)
+            .unwrap_or(Path::new(""));
+
+        for dir in [DIR_GENERAL, DIR_STATIC, DIR_DYNAMIC, DIR_SNAPSHOTS] {
+            let mut copies: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+            for source in sources {
+                collect_files(&source.join(relative).join(dir), Path::new(""), &mut copies)?;
+            }
+
+            for (file, copies) in copies {
+                let path = Path::new(dir).join(&file);
+                match merge_copies(&path, &copies)? {
+                    Some(content) => merged.push((target_dir.join(&path), content)),
+                    None => conflicts.push(relative.join(&path)),
+                }
+            }
+        }
+        visited.push(target_dir);
+    }
+
+    if !conflicts.is_empty() {
+        bail!(
+            "The target directories have not been built from the same sources, conflicting files:\n    {}",
+            conflicts.iter().map(|path| path.display()).join("\n    ")
+        );
+    }
+
+    let count = merged.len();
+    for (path, content) in merged {
+        create_dir_all(path.parent().unwrap())?;
+        write_to_file(content, path, |_| {}, false);
+    }
+    Ok(count)
+}
+
+/// Collects the files of `dir` that are worth caching, keyed by their path relative to `dir`
+fn collect_files(
+    dir: &Path,
+    prefix: &Path,
+    acc: &mut BTreeMap<PathBuf, Vec<PathBuf>>,
+) -> CargoResult<()> {
+    let Ok(entries) = read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let name = prefix.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, &name, acc)?;
+        } else if is_cached(&path) {
+            acc.entry(name).or_default().push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Combines the copies of a single file
+///
+/// Returns `None` if the copies contradict each other.
+fn merge_copies(path: &Path, copies: &[PathBuf]) -> CargoResult<Option<Vec<u8>>> {
+    let contents = copies
+        .iter()
+        .map(|copy| read(copy).with_context(|| format!("Failed to read {}", copy.display())))
+        .collect::<CargoResult<Vec<_>>>()?;
+
+    let merged = match Strategy::of(path) {
+        Strategy::Checksums => {
+            // Checksums are stored in the order of a `HashMap`, so their serializations may differ
+            let equal = contents
+                .iter()
+                .map(|content| Checksums::try_from(content.as_slice()).ok())
+                .all_equal();
+            equal.then(|| contents.into_iter().next().unwrap())
+        }
+        Strategy::Identical => contents
+            .iter()
+            .all_equal()
+            .then(|| contents.into_iter().next().unwrap()),
+        Strategy::Union => {
+            let lines: BTreeSet<String> = contents
+                .iter()
+                .flat_map(|content| {
+                    String::from_utf8_lossy(content)
+                        .lines()
+                        .map(ToString::to_string)
+                        .collect_vec()
+                })
+                .filter(|line| !line.is_empty())
+                .collect();
+            Some(
+                lines
+                    .into_iter()
+                    .map(|line| line + "\n")
+                    .collect::<String>()
+                    .into_bytes(),
+            )
+        }
+        Strategy::History => {
+            let mut histories = copies.iter().filter_map(|copy| TestHistory::read(copy));
+            let mut history = histories.next().unwrap_or_default();
+            for other in histories {
+                history.merge(other);
+            }
+            Some(history.to_string().into_bytes())
+        }
+        Strategy::Newest => copies
+            .iter()
+            .zip(contents)
+            .max_by_key(|(copy, _)| copy.metadata().and_then(|m| m.modified()).ok())
+            .map(|(_, content)| content),
+    };
+    Ok(merged)
+}
@@ -1,6 +1,11 @@
 extern crate cargo;
 
-use crate::{command_prelude::*, doctest_rts::run_analysis_doctests, target_hash::get_target_hash};
+use crate::{
+    command_prelude::*,
+    doctest_rts::run_analysis_doctests,
+    ops::{ListFormat, Patch, Shard},
+    target_hash::get_unit_target_hash,
+};
 use cargo::{
     core::{
         compiler::{
@@ -9,17 +14,22 @@ use cargo::{
         },
         Shell, Workspace,
     },
+    ops::CompileOptions,
     util::Filesystem,
+    CargoResult,
 };
 use cargo_util::ProcessBuilder;
 use internment::{Arena, ArenaIntern};
+use itertools::Itertools;
 use rustyrts::{
+    cache::{Cache, Span},
     callbacks_shared::DOCTEST_PREFIX,
-    constants::ENV_RETEST_ALL,
+    constants::{ENV_RETEST_ALL, FILE_DRY_RUN, PREFIX_FILE},
     fs_utils::{CacheFileDescr, CacheFileKind, CacheKind},
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
     fs::read_to_string,
     path::{Path, PathBuf},
     sync::Arc,
@@ -28,7 +38,18 @@ use std::{
 };
 
 pub fn commands() -> Vec<Command> {
-    vec![basic::cli(), r#static::cli(), dynamic::cli(), clean::cli()]
+    vec![
+        basic::cli(),
+        r#static::cli(),
+        dynamic::cli(),
+        clean::cli(),
+        explain::cli(),
+        history::cli(),
+        inspect::cli(),
+        archive::cli(),
+        graph::cli(),
+        who_tests::cli(),
+    ]
 }
 
 pub type Exec = fn(&mut Config, &ArgMatches) -> CliResult;
@@ -39,17 +60,30 @@ pub fn command_exec(cmd: &str) -> Option<Exec> {
         "static" => r#static::exec,
         "dynamic" => dynamic::exec,
         "clean" => clean::exec,
+        "explain" => explain::exec,
+        "history" => history::exec,
+        "inspect" => inspect::exec,
+        "cache" => archive::exec,
+        "graph" => graph::exec,
+        "who-tests" => who_tests::exec,
         _ => return None,
     };
     Some(f)
 }
 
+pub(crate) mod archive;
 pub(crate) mod basic;
 pub(crate) mod clean;
 pub(crate) mod dynamic;
+pub(crate) mod explain;
+pub(crate) mod graph;
+pub(crate) mod history;
+pub(crate) mod inspect;
 pub(crate) mod r#static;
+pub(crate) mod who_tests;
 
 mod cache;
+mod merge;
 
 #[derive(PartialEq, Eq, Hash, Clone)]
 enum DependencyUnit<'context> {
@@ -57,6 +91,34 @@ enum DependencyUnit<'context> {
     DoctestUnit(&'context Unit, String),
 }
 
+impl<'context> DependencyUnit<'context> {
+    /// Returns the unit, together with the crate name and doctest name used in the names of its cache files
+    fn names(&self) -> (&'context Unit, String, Option<&str>) {
+        match self {
+            DependencyUnit::Unit(u) => {
+                debug_assert!(
+                    matches!(u.mode, CompileMode::Test | CompileMode::Build),
+                    "Got {:?} in {:?}",
+                    u.mode,
+                    u
+                );
+                let crate_name = format!("{}-{}", u.target.crate_name(), get_unit_target_hash(u));
+                (u, crate_name, None)
+            }
+            DependencyUnit::DoctestUnit(u, s) => {
+                debug_assert!(
+                    matches!(u.mode, CompileMode::Doctest),
+                    "Got {:?} in {:?}",
+                    u.mode,
+                    u
+                );
+                let crate_name = format!("{}", u.target.crate_name());
+                (u, crate_name, Some(s.as_str()))
+            }
+        }
+    }
+}
+
 pub(crate) trait SelectionMode {
     fn default_target_dir(&self, target_dir: PathBuf) -> PathBuf;
 
@@ -114,6 +176,9 @@ pub(crate) trait PreciseSelectionMode: SelectionMode {
     fn prepare_cache(&self, target_dir: &Path, unit_graph: &UnitGraph);
 
     fn clean_cache(&self, target_dir: &Path);
+
+    /// Kinds of cache files that are kept in snapshots of a revision, when using `--baseline <rev>`
+    fn snapshot_kinds(&self) -> Vec<(CacheKind, CacheFileKind)>;
 }
 
 pub(crate) trait CrateLevelSelectionMode: SelectionMode {
@@ -126,7 +191,26 @@ pub trait SelectionContext<'context> {
     fn selector(&mut self) -> &mut dyn Selector<'context>;
 }
 
-pub struct TestUnit<'unit, 'arena>(pub &'unit Unit, pub Option<TestInfo<'arena>>);
+pub struct TestUnit<'unit, 'arena>(pub &'unit Unit, pub Option<TestInfo<'arena>>, pub TestEnv);
+
+/// Environment variables that cargo sets or removes for the process executing the tests of a unit
+///
+/// Tests may depend on these, e.g. on `CARGO_MANIFEST_DIR`, although they are not set for RustyRTS itself.
+pub struct TestEnv(BTreeMap<String, Option<OsString>>);
+
+impl TestEnv {
+    pub(crate) fn new(process: &ProcessBuilder) -> Self {
+        Self(process.get_envs().clone())
+    }
+
+    /// The value of a variable as seen by the tests
+    pub(crate) fn var(&self, name: &str) -> Option<OsString> {
+        match self.0.get(name) {
+            Some(value) => value.clone(),
+            None => std::env::var_os(name),
+        }
+    }
+}
 
 pub enum TestInfo<'arena> {
     Test(HashSet<ArenaIntern<'arena, String>>),
@@ -139,6 +223,47 @@ pub enum SelectionUnit {
     Precise(Vec<String>),
 }
 
+impl SelectionUnit {
+    /// Additionally selects tests that failed or did not complete in the previous run
+    ///
+    /// If the failure could not be attributed to any specific test, all tests are selected.
+    pub fn with_failed(self, failed: Option<Vec<String>>) -> Self {
+        let Some(failed) = failed else {
+            return self;
+        };
+        if failed.is_empty() {
+            return SelectionUnit::RetestAll;
+        }
+
+        self.with_tests(failed)
+    }
+
+    /// Additionally selects the given tests
+    pub fn with_tests(self, tests: Vec<String>) -> Self {
+        if tests.is_empty() {
+            return self;
+        }
+
+        match self {
+            SelectionUnit::RetestAll
+            | SelectionUnit::CrateLevel {
+                execute_tests: true,
+            } => self,
+            SelectionUnit::CrateLevel {
+                execute_tests: false,
+            } => SelectionUnit::Precise(tests),
+            SelectionUnit::Precise(mut affected_tests) => {
+                for test in tests {
+                    if !affected_tests.contains(&test) {
+                        affected_tests.push(test);
+                    }
+                }
+                SelectionUnit::Precise(affected_tests)
+            }
+        }
+    }
+}
+
 pub trait Selector<'context> {
     fn test_info<'arena>(
         &self,
@@ -151,7 +276,7 @@ pub trait Selector<'context> {
         let crate_name = format!(
             "{}-{}",
             unit.target.crate_name(),
-            get_target_hash(&unit.target)
+            get_unit_target_hash(unit)
         );
         let compile_mode = format!("{:?}", unit.mode);
         let target = unit.target.kind().description();
@@ -211,6 +336,9 @@ pub trait Selector<'context> {
         start_time: Instant,
     ) -> SelectionUnit;
 
+    /// Short name of the technique, used to explain why tests are not executed
+    fn technique(&self) -> &'static str;
+
     fn cache_kind(&self) -> CacheKind;
 
     fn note(&self, shell: &mut Shell, test_args: &[&str]);
@@ -220,19 +348,108 @@ pub trait Selector<'context> {
     fn check_retest_all(&self) -> bool {
         std::env::var(ENV_RETEST_ALL).is_ok()
     }
+
+    /// Prints why `test_name` is selected, if it is contained in this test unit
+    ///
+    /// Returns whether the test has been found
+    fn explain(
+        &mut self,
+        _test: TestUnit<'context, 'context>,
+        _test_name: &str,
+        _shell: &mut Shell,
+    ) -> CargoResult<bool> {
+        Err(anyhow::format_err!(
+            "This mode of regression test selection is not able to explain its selection"
+        ))
+    }
 }
 
-pub fn exec(config: &Config, args: &ArgMatches, selection: Selection) -> CliResult {
-    let ws = {
-        let mut ws = args.workspace(config)?;
+/// Checks whether some file changed that could not be attributed to any function
+///
+/// In this case, all tests of the unit are selected.
+fn check_changed_files(
+    shell: &mut Shell,
+    changed_nodes: &HashSet<ArenaIntern<'_, String>>,
+) -> bool {
+    let files = changed_nodes
+        .iter()
+        .filter_map(|node| node.strip_prefix(PREFIX_FILE))
+        .sorted()
+        .collect_vec();
+    if files.is_empty() {
+        return false;
+    }
 
-        if config.target_dir().unwrap().is_none() {
-            let target_dir = selection.default_target_dir(ws.target_dir().into_path_unlocked());
-            ws.set_target_dir(Filesystem::new(target_dir));
-        }
+    shell
+        .note(format!(
+            "Files changed ({}), retesting all tests of this unit",
+            files.join(", ")
+        ))
+        .unwrap();
+    true
+}
 
-        ws
-    };
+/// Names a node together with the location of its body in the source code, if it is known
+///
+/// Instances of generic functions are located at their generic definition.
+pub(crate) fn located(spans: &HashMap<String, Span>, node: &str) -> String {
+    let definition = strip_generic_args(node);
+    match spans.get(node).or_else(|| spans.get(definition)) {
+        Some(span) => format!("{node} ({span})"),
+        None => node.to_string(),
+    }
+}
+
+/// Strips the generic arguments of an instance of a generic function, e.g. `foo::bar::<i32>` becomes `foo::bar`
+pub(crate) fn strip_generic_args(node: &str) -> &str {
+    match node.rsplit_once("::<") {
+        Some((definition, args)) if args.ends_with('>') => definition,
+        _ => node,
+    }
+}
+
+/// Lists nodes one per line, together with the locations of their bodies in the source code
+fn located_nodes(target_dir: &Path, nodes: &HashSet<ArenaIntern<'_, String>>) -> String {
+    let spans = Cache::new(target_dir).spans().unwrap_or_default();
+    nodes
+        .iter()
+        .map(|node| node.as_str())
+        .sorted()
+        .map(|node| format!("    {}\n", located(&spans, node)))
+        .collect()
+}
+
+fn workspace<'cfg>(
+    config: &'cfg Config,
+    args: &ArgMatches,
+    selection: &Selection,
+) -> CargoResult<Workspace<'cfg>> {
+    let mut ws = args.workspace(config)?;
+
+    if config.target_dir().unwrap().is_none() {
+        let target_dir = selection.default_target_dir(ws.target_dir().into_path_unlocked());
+        ws.set_target_dir(Filesystem::new(target_dir));
+    }
+
+    Ok(ws)
+}
+
+fn compile_options(
+    config: &Config,
+    args: &ArgMatches,
+    ws: &Workspace<'_>,
+) -> CargoResult<CompileOptions> {
+    let mut compile_opts =
+        args.compile_options(config, CompileMode::Test, Some(ws), ProfileChecking::Custom)?;
+
+    compile_opts.build_config.requested_profile =
+        args.get_profile_name(config, "test", ProfileChecking::Custom)?;
+
+    Ok(compile_opts)
+}
+
+pub fn exec(config: &Config, args: &ArgMatches, selection: Selection) -> CliResult {
+    let ws = workspace(config, args, &selection)?;
 
     let test_args = args.get_many::<String>("args").unwrap_or_default();
     let test_args = test_args.map(String::as_str).collect::<Vec<_>>();
@@ -244,15 +461,43 @@ pub fn exec(config: &Config, args: &ArgMatches, selection: Selection) -> CliResu
         .into());
     }
 
-    let mut compile_opts = args.compile_options(
-        config,
-        CompileMode::Test,
-        Some(&ws),
-        ProfileChecking::Custom,
-    )?;
+    let mut compile_opts = compile_options(config, args, &ws)?;
 
-    compile_opts.build_config.requested_profile =
-        args.get_profile_name(config, "test", ProfileChecking::Custom)?;
+    let baseline = args
+        .try_get_one::<String>("baseline")
+        .ok()
+        .flatten()
+        .map(String::as_str);
+    if baseline.is_some() {
+        // Checksums of all crates need to be compared to the snapshot of the baseline
+        compile_opts.build_config.force_rebuild = true;
+    }
+
+    let listing = list_format(args);
+    let dry_run = CacheKind::General
+        .map(ws.target_dir().into_path_unlocked())
+        .join(FILE_DRY_RUN);
+    if listing.is_none() && dry_run.exists() {
+        // Checksums have not been updated while tests were only listed
+        compile_opts.build_config.force_rebuild = true;
+    }
+
+    // Changes may be given as a diff instead of being determined by comparing to the previous run
+    let patch = match (
+        args.try_get_one::<PathBuf>("from-diff").ok().flatten(),
+        args.try_get_one::<String>("from-git-diff").ok().flatten(),
+    ) {
+        (Some(path), _) => Some(Patch::read(&ws, path)?),
+        (None, Some(rev)) => Some(Patch::from_git(&ws, rev)?),
+        (None, None) => None,
+    };
+
+    let shard = args
+        .try_get_one::<String>("shard")
+        .ok()
+        .flatten()
+        .map(|s| s.parse::<Shard>())
+        .transpose()?;
 
     let no_run = args.flag("no-run");
     let doc = args.flag("doc");
@@ -283,7 +528,26 @@ Proceed with caution!!!
         compile_opts,
     };
 
-    crate::ops::run_tests(&ws, &ops, &test_args, selection)
+    crate::ops::run_tests(
+        &ws, &ops, &test_args, selection, listing, baseline, patch, shard,
+    )
+}
+
+/// Determines whether selected tests should only be listed instead of being executed
+fn list_format(args: &ArgMatches) -> Option<ListFormat> {
+    let list_selected = args
+        .try_get_one::<bool>("list-selected")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false);
+
+    list_selected.then(
+        || match args.get_one::<String>("format").map(String::as_str) {
+            Some("json") => ListFormat::Json,
+            _ => ListFormat::Human,
+        },
+    )
 }
 
 #[derive(Debug)]
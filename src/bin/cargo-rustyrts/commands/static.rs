@@ -25,17 +25,24 @@ use cargo_util::ProcessBuilder;
 use internment::{Arena, ArenaIntern};
 use itertools::Itertools;
 use rustyrts::{
-    constants::{ENDING_CHANGES, ENV_COMPILE_MODE, ENV_DOCTESTED, ENV_TARGET, ENV_TARGET_DIR},
-    fs_utils::{CacheFileDescr, CacheFileKind, CacheKind},
-    static_rts::graph::{serialize::ArenaDeserializable, DependencyGraph},
+    cache::{Cache, FileStamp, WorkspaceGraph},
+    constants::{
+        ENDING_CHANGES, ENV_COMPILE_MODE, ENV_DOCTESTED, ENV_TARGET, ENV_TARGET_DIR,
+        FILE_WORKSPACE_GRAPH,
+    },
+    fs_utils::{CacheFileDescr, CacheFileKind, CacheKind, ChecksumKind},
+    static_rts::graph::{
+        serialize::{toolchain, ArenaDeserializable, DeserializationError},
+        DependencyGraph, EdgeTypes,
+    },
 };
 use tracing::trace;
 
-use crate::{commands::DoctestName, ops::PreciseExecutor, target_hash::get_target_hash};
+use crate::{commands::DoctestName, ops::PreciseExecutor};
 
 use super::{
-    cache::HashCache, DependencyUnit, PreciseSelectionMode, SelectionContext, SelectionMode,
-    SelectionUnit, Selector, TestInfo, TestUnit,
+    check_changed_files, located, located_nodes, DependencyUnit, PreciseSelectionMode,
+    SelectionContext, SelectionMode, SelectionUnit, Selector, TestInfo, TestUnit,
 };
 
 pub fn cli() -> Command {
@@ -58,6 +65,57 @@ Consider using `cargo rustyrts dynamic` instead if your tests spawn additional p
         )
         .arg(flag("doc", "Test only this library's documentation"))
         .arg(flag("no-run", "Compile, but don't run tests"))
+        .arg(
+            flag(
+                "list-selected",
+                "Compile and select tests, but only list selected and deselected tests instead of running them",
+            )
+            .conflicts_with("no-run"),
+        )
+        .arg(
+            opt("format", "Output format used with --list-selected")
+                .value_name("FMT")
+                .value_parser(["human", "json"])
+                .requires("list-selected"),
+        )
+        .arg(
+            opt(
+                "baseline",
+                "Select tests affected by changes since the merge-base of HEAD and this git revision",
+            )
+            .value_name("REV"),
+        )
+        .arg(
+            opt(
+                "from-diff",
+                "Select tests affected by the changes of a unified diff (e.g. the output of `git diff`), instead of the changes since the last run",
+            )
+            .value_name("FILE")
+            .value_parser(clap::value_parser!(PathBuf))
+            .conflicts_with("baseline"),
+        )
+        .arg(
+            opt(
+                "from-git-diff",
+                "Select tests affected by the changes of the working tree since this git revision, instead of the changes since the last run",
+            )
+            .value_name("REV")
+            .conflicts_with_all(["baseline", "from-diff"]),
+        )
+        .arg(
+            opt(
+                "edge-types",
+                "Types of edges that are followed, e.g. `Call,Drop,Static` or `!Contained` [default: all]",
+            )
+            .value_name("TYPES"),
+        )
+        .arg(
+            opt(
+                "shard",
+                "Execute only a share of the selected tests, to distribute them over several jobs",
+            )
+            .value_name("INDEX/COUNT"),
+        )
         .arg_ignore_rust_version()
         .arg_future_incompat_report()
         .arg_message_format()
@@ -97,7 +155,22 @@ Consider using `cargo rustyrts dynamic` instead if your tests spawn additional p
         .arg_manifest_path()
 }
 
-pub(crate) struct StaticMode;
+pub(crate) struct StaticMode {
+    /// Types of edges that are followed when computing the nodes that are affected by changes
+    edge_types: EdgeTypes,
+}
+
+impl StaticMode {
+    pub(crate) fn new(edge_types: EdgeTypes) -> Self {
+        Self { edge_types }
+    }
+}
+
+impl Default for StaticMode {
+    fn default() -> Self {
+        Self::new(EdgeTypes::all())
+    }
+}
 
 impl SelectionMode for StaticMode {
     fn default_target_dir(&self, target_dir: PathBuf) -> std::path::PathBuf {
@@ -146,6 +219,24 @@ impl PreciseSelectionMode for StaticMode {
         }
     }
 
+    fn snapshot_kinds(&self) -> Vec<(CacheKind, CacheFileKind)> {
+        vec![
+            (
+                CacheKind::General,
+                CacheFileKind::Checksums(ChecksumKind::Checksum),
+            ),
+            (
+                CacheKind::General,
+                CacheFileKind::Checksums(ChecksumKind::VtblChecksum),
+            ),
+            (
+                CacheKind::General,
+                CacheFileKind::Checksums(ChecksumKind::ConstChecksum),
+            ),
+            (CacheKind::Static, CacheFileKind::Graph),
+        ]
+    }
+
     fn selection_context<'context, 'arena: 'context>(
         &self,
         ws: &Workspace<'_>,
@@ -155,13 +246,60 @@ impl PreciseSelectionMode for StaticMode {
     ) -> Box<dyn SelectionContext<'context> + 'context> {
         let verbose = ws.config().extra_verbose();
         Box::new(StaticSelectionContext::new(
-            target_dir, arena, units, verbose,
+            target_dir,
+            arena,
+            units,
+            verbose,
+            self.edge_types,
         ))
     }
 }
 
 pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
-    super::exec(config, args, super::Selection::Precise(&StaticMode))
+    let mode = StaticMode::new(edge_types(&args.workspace(config)?, args)?);
+    super::exec(config, args, super::Selection::Precise(&mode))
+}
+
+/// Determines the types of edges that are followed, either given by `--edge-types` or in the manifest
+///
+/// ```toml
+/// [workspace.metadata.rustyrts]
+/// edge-types = ["!Contained"] # or e.g. ["Call", "Drop", "Static"]
+/// ```
+///
+/// Packages that are not part of a workspace may use `package.metadata.rustyrts` instead.
+pub(crate) fn edge_types(ws: &Workspace<'_>, args: &ArgMatches) -> CargoResult<EdgeTypes> {
+    if let Some(policy) = args.get_one::<String>("edge-types") {
+        return policy
+            .parse()
+            .map_err(|err| anyhow::format_err!("Invalid value for `--edge-types`: {err}"));
+    }
+
+    let root_package = ws
+        .members()
+        .find(|pkg| pkg.manifest_path() == ws.root_manifest());
+    let Some(policy) = ws
+        .custom_metadata()
+        .or_else(|| root_package.and_then(|pkg| pkg.manifest().custom_metadata()))
+        .and_then(|metadata| metadata.get("rustyrts"))
+        .and_then(|rustyrts| rustyrts.get("edge-types"))
+    else {
+        return Ok(EdgeTypes::all());
+    };
+
+    let policy = match (policy.as_str(), policy.as_array()) {
+        (Some(policy), _) => policy.parse(),
+        (_, Some(entries)) => entries
+            .iter()
+            .map(|entry| entry.as_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| "expected an array of strings".to_string())
+            .and_then(EdgeTypes::from_policy),
+        _ => Err("expected a string or an array of strings".to_string()),
+    };
+    policy.map_err(|err| {
+        anyhow::format_err!("Invalid value for `metadata.rustyrts.edge-types`: {err}")
+    })
 }
 
 pub(crate) struct StaticSelectionContext<'arena, 'context> {
@@ -174,9 +312,16 @@ impl<'arena: 'context, 'context> StaticSelectionContext<'arena, 'context> {
         arena: &'arena Arena<String>,
         unit_graph: &'context HashMap<Unit, Vec<UnitDep>>,
         pretty_print_graph: bool,
+        edge_types: EdgeTypes,
     ) -> Self {
         Self {
-            selector: StaticSelector::new(target_dir, arena, unit_graph, pretty_print_graph),
+            selector: StaticSelector::new(
+                target_dir,
+                arena,
+                unit_graph,
+                pretty_print_graph,
+                edge_types,
+            ),
         }
     }
 }
@@ -188,188 +333,366 @@ impl<'arena, 'context> SelectionContext<'context> for StaticSelectionContext<'ar
 }
 
 pub(crate) struct StaticSelector<'arena, 'context> {
-    cache: HashCache<'context, DependencyUnit<'context>, DependencyNode<'arena>>,
+    /// Nodes reached in the merged graph of the workspace, computed once before selecting the tests of the first unit
+    workspace: Option<WorkspaceSelection<'arena, 'context>>,
+    target_dir: &'context Path,
     arena: &'arena Arena<String>,
+    unit_graph: &'context HashMap<Unit, Vec<UnitDep>>,
+    pretty_print_graph: bool,
+    edge_types: EdgeTypes,
 }
 
-struct DependencyNode<'arena> {
-    pub changes: HashSet<ArenaIntern<'arena, String>>,
-    pub reached: HashSet<ArenaIntern<'arena, String>>,
-    pub locally: HashSet<ArenaIntern<'arena, String>>,
+/// The result of a single traversal of the merged graph of the workspace, starting from the changes of all units
+struct WorkspaceSelection<'arena, 'context> {
+    /// Nodes that have changed in every unit
+    changes: HashMap<&'context Unit, HashSet<ArenaIntern<'arena, String>>>,
+    /// Nodes that transitively depend on any changed node
+    reached: HashSet<ArenaIntern<'arena, String>>,
+    /// Describes why the graph of a unit could not be read
+    invalid: HashMap<&'context Unit, String>,
+    graph: WorkspaceGraph,
+    /// Units whose edges are considered, by the stems of the names of their cache files
+    units: Vec<String>,
 }
 
-impl<'arena: 'context, 'context> StaticSelector<'arena, 'context> {
-    pub fn new(
-        target_dir: &'context Path,
+impl<'arena, 'context> WorkspaceSelection<'arena, 'context> {
+    /// Updates the merged graph of the workspace by replacing the graphs of all units that have been compiled again,
+    /// then computes the nodes that are reached from the changes of all units
+    fn new(
+        target_dir: &Path,
         arena: &'arena Arena<String>,
         unit_graph: &'context HashMap<Unit, Vec<UnitDep>>,
         pretty_print_graph: bool,
+        edge_types: EdgeTypes,
     ) -> Self {
-        Self {
-            cache: HashCache::recursive(
-                move |cache: &mut HashCache<'context, _, _>, unit: &DependencyUnit<'context>| {
-                    Self::import_graph(
-                        target_dir.to_path_buf(),
-                        arena,
-                        unit_graph,
-                        cache,
-                        unit,
-                        pretty_print_graph,
-                    )
-                },
-            ),
-            arena,
-        }
-    }
-
-    fn import_graph(
-        target_dir: PathBuf,
-        arena: &'arena Arena<String>,
-        unit_graph: &'context HashMap<Unit, Vec<UnitDep>>,
-        cache: &mut HashCache<'context, DependencyUnit<'context>, DependencyNode<'arena>>,
-        unit: &DependencyUnit<'context>,
-        pretty_print_graph: bool,
-    ) -> DependencyNode<'arena> {
-        let (unit, crate_name, maybe_doctest_name) = match unit {
-            DependencyUnit::Unit(u) => {
-                debug_assert!(
-                    matches!(u.mode, CompileMode::Test | CompileMode::Build),
-                    "Got {:?} in {:?}",
-                    u.mode,
-                    u
-                );
-                let crate_name =
-                    format!("{}-{}", u.target.crate_name(), get_target_hash(&u.target));
-                (u, crate_name, None)
-            }
-            DependencyUnit::DoctestUnit(u, s) => {
-                debug_assert!(
-                    matches!(u.mode, CompileMode::Doctest),
-                    "Got {:?} in {:?}",
-                    u.mode,
-                    u
-                );
-                let crate_name = format!("{}", u.target.crate_name());
-                (u, crate_name, Some(s.as_str()))
-            }
-        };
-
-        let compile_mode = format!("{:?}", unit.mode);
-        let target = unit.target.kind().description();
-
-        let graph_path = {
-            let mut path = CacheKind::Static.map(target_dir.clone());
-            CacheFileDescr::new(
-                &crate_name,
-                Some(&compile_mode),
-                Some(target),
-                maybe_doctest_name,
-                CacheFileKind::Graph,
-            )
-            .apply(&mut path);
-            path
-        };
-        let changes_path = {
-            let mut path = CacheKind::Static.map(target_dir.clone());
-            CacheFileDescr::new(
-                &crate_name,
-                Some(&compile_mode),
-                Some(target),
-                maybe_doctest_name,
-                CacheFileKind::Changes,
-            )
-            .apply(&mut path);
-            path
-        };
-
-        let changed_nodes: HashSet<ArenaIntern<'arena, String>> = read_to_string(changes_path)
+        let path = CacheKind::Static
+            .map(target_dir.to_path_buf())
+            .join(FILE_WORKSPACE_GRAPH);
+        let mut graph = read(&path)
             .ok()
-            .map_or_else(HashSet::new, |s| {
-                s.lines()
-                    .map(ToString::to_string)
-                    .map(|l| Arena::<String>::intern(arena, l))
-                    .collect()
-            });
-
-        let graph = read(graph_path.clone())
-                        .ok()
-                        .map_or_else(
-                       || {
-                            trace!(
-                                "Did not find dependency graph for crate {:?} in mode {:?}\nTried reading from {:?}",
-                                crate_name, compile_mode, graph_path
-                            );
-                            DependencyGraph::new(arena)
-                        }, |s| DependencyGraph::deserialize(arena, &s).unwrap());
-
-        if maybe_doctest_name.is_some() && graph_path.is_file() {
-            std::fs::remove_file(graph_path).unwrap();
-        }
+            .and_then(|input| WorkspaceGraph::deserialize(&input).ok())
+            .unwrap_or_default();
+        let mut modified = false;
+
+        let mut changes = HashMap::new();
+        let mut invalid = HashMap::new();
+        let mut units = Vec::new();
+
+        for unit in unit_graph
+            .keys()
+            .filter(|unit| matches!(unit.mode, CompileMode::Test | CompileMode::Build))
+        {
+            let (unit, crate_name, _) = DependencyUnit::Unit(unit).names();
+            let compile_mode = format!("{:?}", unit.mode);
+            let target = unit.target.kind().description();
+
+            changes.insert(
+                unit,
+                read_changes(target_dir, arena, &crate_name, &compile_mode, target, None),
+            );
+
+            let graph_path = graph_path(target_dir, &crate_name, &compile_mode, target, None);
+            let name = graph_path
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+
+            match FileStamp::of(&graph_path) {
+                // Units without a graph do not contribute any edges
+                Err(_) => modified |= graph.remove(&name),
+                Ok(stamp) if graph.is_current(&name, stamp) => {}
+                Ok(stamp) => {
+                    trace!("Updating merged dependency graph with {:?}", graph_path);
+                    modified = true;
+                    if let Err(err) = graph.replace(
+                        &name,
+                        stamp,
+                        &read(&graph_path).unwrap_or_default(),
+                        Some(toolchain()),
+                    ) {
+                        graph.remove(&name);
+                        invalid.insert(unit, format!("{}: {err}", graph_path.display()));
+                    }
+                }
+            }
 
-        if pretty_print_graph {
-            let pretty_path = {
-                let mut path = CacheKind::Static.map(target_dir.clone());
-                CacheFileDescr::new(
+            if pretty_print_graph {
+                if let Ok(graph) = read_graph(
+                    target_dir,
+                    arena,
                     &crate_name,
-                    Some(&compile_mode),
-                    Some(target),
-                    maybe_doctest_name,
-                    CacheFileKind::PrettyGraph,
+                    &compile_mode,
+                    target,
+                    None,
+                    edge_types,
                 )
-                .apply(&mut path);
-                path
-            };
+                .0
+                {
+                    render_graph(target_dir, &graph, &crate_name, &compile_mode, target, None);
+                }
+            }
 
-            let mut f =
-                File::create(pretty_path).expect("Failed to create file for pretty-printing graph");
-            graph.render_to(&mut f);
+            units.push(name);
         }
 
-        let mut starting_points = vec![changed_nodes.clone()];
-        let mut reached = HashSet::new();
-        let mut changed_nodes = changed_nodes;
-
-        for other in unit_graph.get(unit).unwrap() {
-            if other.unit.mode == CompileMode::Build {
-                let other_unit = DependencyUnit::Unit(&other.unit);
-                let DependencyNode {
-                    changes,
-                    reached: _,
-                    locally,
-                } = cache.get(other_unit);
-
-                starting_points.push(locally.clone());
-                changed_nodes.extend(changes);
-            }
+        // Graphs of units that do not exist anymore are dropped
+        let dir = CacheKind::Static.map(target_dir.to_path_buf());
+        modified |= graph.retain(|unit| {
+            dir.join(unit)
+                .with_extension(CacheFileKind::Graph.as_ref())
+                .is_file()
+        });
+        if modified {
+            std::fs::write(&path, graph.serialize())
+                .expect("Failed to write merged dependency graph");
         }
 
-        let locally = graph.reachable_nodes(starting_points.into_iter().flatten());
-        reached.extend(&locally);
+        let reached = graph
+            .reachable_nodes(
+                units.iter().map(String::as_str),
+                changes.values().flatten().map(|node| node.as_str()),
+                *edge_types,
+            )
+            .into_iter()
+            .map(|node| arena.intern(node))
+            .collect();
 
-        DependencyNode {
-            changes: changed_nodes,
+        Self {
+            changes,
             reached,
-            locally,
+            invalid,
+            graph,
+            units,
+        }
+    }
+}
+
+impl<'arena: 'context, 'context> StaticSelector<'arena, 'context> {
+    pub fn new(
+        target_dir: &'context Path,
+        arena: &'arena Arena<String>,
+        unit_graph: &'context HashMap<Unit, Vec<UnitDep>>,
+        pretty_print_graph: bool,
+        edge_types: EdgeTypes,
+    ) -> Self {
+        Self {
+            workspace: None,
+            target_dir,
+            arena,
+            unit_graph,
+            pretty_print_graph,
+            edge_types,
         }
     }
 }
 
 impl<'arena, 'context> StaticSelector<'arena, 'context> {
-    fn reachble_and_changed_nodes(
+    fn workspace(&mut self) -> &WorkspaceSelection<'arena, 'context> {
+        self.workspace.get_or_insert_with(|| {
+            WorkspaceSelection::new(
+                self.target_dir,
+                self.arena,
+                self.unit_graph,
+                self.pretty_print_graph,
+                self.edge_types,
+            )
+        })
+    }
+
+    /// The unit itself, together with all units that it transitively depends on
+    fn dependencies(&self, unit: &'context Unit) -> Vec<&'context Unit> {
+        let mut dependencies = vec![unit];
+        let mut i = 0;
+        while let Some(unit) = dependencies.get(i) {
+            for other in self.unit_graph.get(unit).unwrap() {
+                if other.unit.mode == CompileMode::Build && !dependencies.contains(&&other.unit) {
+                    dependencies.push(&other.unit);
+                }
+            }
+            i += 1;
+        }
+        dependencies
+    }
+
+    /// Nodes that have changed in the unit or any of its dependencies,
+    /// together with the reason why the graph of any of them could not be read
+    fn changed_nodes(
         &mut self,
-        unit: DependencyUnit<'context>,
-    ) -> (
-        &HashSet<ArenaIntern<'arena, String>>,
-        &HashSet<ArenaIntern<'arena, String>>,
-        &HashSet<ArenaIntern<'arena, String>>,
-    ) {
-        let cached = self.cache.get(unit);
-        (&cached.changes, &cached.locally, &cached.reached)
+        unit: &'context Unit,
+    ) -> (HashSet<ArenaIntern<'arena, String>>, Option<String>) {
+        let dependencies = self.dependencies(unit);
+        let workspace = self.workspace();
+
+        let changes = dependencies
+            .iter()
+            .filter_map(|unit| workspace.changes.get(unit))
+            .flatten()
+            .copied()
+            .collect();
+        let invalid = dependencies
+            .iter()
+            .find_map(|unit| workspace.invalid.get(unit))
+            .cloned();
+        (changes, invalid)
+    }
+
+    /// Computes a chain of nodes leading from a changed node to `node` in the merged graph of the workspace,
+    /// together with the types of the edges that lead to the respective node
+    ///
+    /// Like when selecting tests, the changes of all units are considered.
+    fn explain_node(
+        &mut self,
+        node: &str,
+    ) -> Option<Vec<(ArenaIntern<'arena, String>, Option<EdgeTypes>)>> {
+        let edge_types = *self.edge_types;
+        let arena = self.arena;
+        let workspace = self.workspace();
+
+        let path = workspace.graph.shortest_path(
+            workspace.units.iter().map(String::as_str),
+            workspace
+                .changes
+                .values()
+                .flatten()
+                .map(|node| node.as_str()),
+            node,
+            edge_types,
+        )?;
+
+        Some(
+            path.into_iter()
+                .map(|(node, types)| (arena.intern(node), types.map(EdgeTypes::from_raw)))
+                .collect(),
+        )
     }
 }
 
+fn read_changes<'arena>(
+    target_dir: &Path,
+    arena: &'arena Arena<String>,
+    crate_name: &str,
+    compile_mode: &str,
+    target: &str,
+    maybe_doctest_name: Option<&str>,
+) -> HashSet<ArenaIntern<'arena, String>> {
+    let changes_path = {
+        let mut path = CacheKind::Static.map(target_dir.to_path_buf());
+        CacheFileDescr::new(
+            crate_name,
+            Some(compile_mode),
+            Some(target),
+            maybe_doctest_name,
+            CacheFileKind::Changes,
+        )
+        .apply(&mut path);
+        path
+    };
+
+    read_to_string(changes_path)
+        .ok()
+        .map_or_else(HashSet::new, |s| {
+            s.lines()
+                .map(ToString::to_string)
+                .map(|l| Arena::<String>::intern(arena, l))
+                .collect()
+        })
+}
+
+fn read_graph<'arena>(
+    target_dir: &Path,
+    arena: &'arena Arena<String>,
+    crate_name: &str,
+    compile_mode: &str,
+    target: &str,
+    maybe_doctest_name: Option<&str>,
+    edge_types: EdgeTypes,
+) -> (
+    Result<DependencyGraph<'arena, String>, DeserializationError>,
+    PathBuf,
+) {
+    let graph_path = graph_path(
+        target_dir,
+        crate_name,
+        compile_mode,
+        target,
+        maybe_doctest_name,
+    );
+
+    let graph = read(graph_path.clone())
+                    .ok()
+                    .map_or_else(
+                   || {
+                        trace!(
+                            "Did not find dependency graph for crate {:?} in mode {:?}\nTried reading from {:?}",
+                            crate_name, compile_mode, graph_path
+                        );
+                        Ok(DependencyGraph::new(arena))
+                    }, |s| DependencyGraph::deserialize(arena, &s));
+    let graph = graph.map(|mut graph| {
+        graph.retain_edges(edge_types);
+        graph
+    });
+
+    (graph, graph_path)
+}
+
+fn graph_path(
+    target_dir: &Path,
+    crate_name: &str,
+    compile_mode: &str,
+    target: &str,
+    maybe_doctest_name: Option<&str>,
+) -> PathBuf {
+    let mut path = CacheKind::Static.map(target_dir.to_path_buf());
+    CacheFileDescr::new(
+        crate_name,
+        Some(compile_mode),
+        Some(target),
+        maybe_doctest_name,
+        CacheFileKind::Graph,
+    )
+    .apply(&mut path);
+    path
+}
+
+/// Writes a graph in dot format, to be inspected by humans
+fn render_graph(
+    target_dir: &Path,
+    graph: &DependencyGraph<'_, String>,
+    crate_name: &str,
+    compile_mode: &str,
+    target: &str,
+    maybe_doctest_name: Option<&str>,
+) {
+    let pretty_path = {
+        let mut path = CacheKind::Static.map(target_dir.to_path_buf());
+        CacheFileDescr::new(
+            crate_name,
+            Some(compile_mode),
+            Some(target),
+            maybe_doctest_name,
+            CacheFileKind::PrettyGraph,
+        )
+        .apply(&mut path);
+        path
+    };
+
+    let mut f = File::create(pretty_path).expect("Failed to create file for pretty-printing graph");
+    graph.render_to(&mut f);
+}
+
+fn warn_invalid_graph(shell: &mut Shell, reason: &str) {
+    shell
+        .warn(format!(
+            "Failed to read dependency graph {reason}, retesting all tests of this unit"
+        ))
+        .unwrap();
+}
+
 fn print_stats(
     shell: &mut Shell,
+    target_dir: &Path,
     maybe_changed_nodes: Option<&HashSet<ArenaIntern<'_, String>>>,
     maybe_reachable_nodes: Option<&HashSet<ArenaIntern<'_, String>>>,
     tests_found: &HashSet<ArenaIntern<'_, String>>,
@@ -405,7 +728,9 @@ fn print_stats(
     })?;
     if let Some(changed_nodes) = maybe_changed_nodes {
         shell.verbose(|shell| {
-            shell.print_ansi_stderr(format!("\nChanged: {changed_nodes:?}\n").as_bytes())
+            shell.print_ansi_stderr(
+                format!("\nChanged:\n{}", located_nodes(target_dir, changed_nodes)).as_bytes(),
+            )
         })?;
     }
     if let Some(reachable_nodes) = maybe_reachable_nodes {
@@ -432,6 +757,7 @@ fn print_stats(
 
 fn print_doctest_stats<T: Display>(
     shell: &mut Shell,
+    target_dir: &Path,
     status: T,
     changed_nodes: &HashSet<ArenaIntern<'_, String>>,
     reachable_nodes: &HashSet<ArenaIntern<'_, String>>,
@@ -446,7 +772,9 @@ fn print_doctest_stats<T: Display>(
     })?;
 
     shell.verbose(|shell| {
-        shell.print_ansi_stderr(format!("\nChanged: {changed_nodes:?}\n").as_bytes())
+        shell.print_ansi_stderr(
+            format!("\nChanged:\n{}", located_nodes(target_dir, changed_nodes)).as_bytes(),
+        )
     })?;
     let verbose_reachable = {
         if reachable_nodes.len() < 200 {
@@ -473,7 +801,7 @@ impl<'arena, 'context> Selector<'context> for StaticSelector<'arena, 'context> {
             return SelectionUnit::RetestAll;
         }
 
-        let TestUnit(unit, test_info) = test_unit;
+        let TestUnit(unit, test_info, _) = test_unit;
         let Some(test_info) = test_info else {
             panic!("Precise selection requires information about tests")
         };
@@ -488,22 +816,27 @@ impl<'arena, 'context> Selector<'context> for StaticSelector<'arena, 'context> {
                     unit.mode,
                     unit
                 );
-                let mut changed_nodes = HashSet::new();
-                let mut reachable_nodes = HashSet::new();
-
-                let dependency_unit = DependencyUnit::Unit(unit);
-                let (changed, locally, reachable) =
-                    self.reachble_and_changed_nodes(dependency_unit);
-                let affected = locally.intersection(&tests_found).map(ToString::to_string);
+                let (changed_nodes, invalid) = self.changed_nodes(unit);
+                if let Some(reason) = invalid {
+                    warn_invalid_graph(shell, &reason);
+                    return SelectionUnit::RetestAll;
+                }
+                if check_changed_files(shell, &changed_nodes) {
+                    return SelectionUnit::RetestAll;
+                }
 
-                changed_nodes.extend(changed.clone());
-                reachable_nodes.extend(reachable.clone());
+                let target_dir = self.target_dir;
+                let reachable_nodes = &self.workspace().reached;
+                let affected = reachable_nodes
+                    .intersection(&tests_found)
+                    .map(ToString::to_string);
                 affected_tests.extend(affected);
 
                 print_stats(
                     shell,
+                    target_dir,
                     Some(&changed_nodes),
-                    Some(&reachable_nodes),
+                    Some(reachable_nodes),
                     &tests_found,
                     &affected_tests,
                     start_time,
@@ -525,40 +858,156 @@ impl<'arena, 'context> Selector<'context> for StaticSelector<'arena, 'context> {
                     .map(|t| (t.cache_name(), t.trimmed_name().to_string(), t.fn_name()))
                     .unique();
 
+                let (dependency_changes, dependency_invalid) = self.changed_nodes(unit);
+                let compile_mode = format!("{:?}", unit.mode);
+                let target = unit.target.kind().description();
+                let crate_name = unit.target.crate_name();
+
                 for (cache_name, trimmed_name, fn_name) in tests {
                     let test = self.arena.intern(fn_name.clone());
 
-                    let dependency_unit = DependencyUnit::DoctestUnit(unit, cache_name);
-                    let (changed, locally, reachable) =
-                        self.reachble_and_changed_nodes(dependency_unit);
+                    // Graphs of doctests are not part of the merged graph, they are rewritten whenever doctests are analyzed
+                    let (graph, graph_path) = read_graph(
+                        self.target_dir,
+                        self.arena,
+                        &crate_name,
+                        &compile_mode,
+                        target,
+                        Some(&cache_name),
+                        self.edge_types,
+                    );
+                    let invalid = graph
+                        .as_ref()
+                        .err()
+                        .map(|err| format!("{}: {err}", graph_path.display()))
+                        .or_else(|| dependency_invalid.clone());
+                    if let Some(reason) = invalid {
+                        warn_invalid_graph(shell, &reason);
+                        return SelectionUnit::RetestAll;
+                    }
+                    let graph = graph.unwrap();
+                    if self.pretty_print_graph {
+                        render_graph(
+                            self.target_dir,
+                            &graph,
+                            &crate_name,
+                            &compile_mode,
+                            target,
+                            Some(&cache_name),
+                        );
+                    }
+
+                    let mut changed = read_changes(
+                        self.target_dir,
+                        self.arena,
+                        &crate_name,
+                        &compile_mode,
+                        target,
+                        Some(&cache_name),
+                    );
+                    let starting_points = changed
+                        .iter()
+                        .chain(&self.workspace().reached)
+                        .copied()
+                        .collect_vec();
+                    changed.extend(&dependency_changes);
+                    if check_changed_files(shell, &changed) {
+                        return SelectionUnit::RetestAll;
+                    }
+                    let reachable = graph.reachable_nodes(starting_points);
 
                     print_doctest_stats(
                         shell,
+                        self.target_dir,
                         "Doc test ".to_string() + &trimmed_name,
                         &changed,
                         &reachable,
                     )
                     .unwrap();
 
-                    if locally.contains(&test) {
+                    if reachable.contains(&test) {
                         affected_tests.push(trimmed_name);
                     }
                     tests_found.insert(test);
                 }
 
-                print_stats(shell, None, None, &tests_found, &affected_tests, start_time).unwrap();
+                print_stats(
+                    shell,
+                    self.target_dir,
+                    None,
+                    None,
+                    &tests_found,
+                    &affected_tests,
+                    start_time,
+                )
+                .unwrap();
             }
         }
 
         SelectionUnit::Precise(affected_tests)
     }
 
+    fn explain(
+        &mut self,
+        test_unit: TestUnit<'context, 'context>,
+        test_name: &str,
+        shell: &mut Shell,
+    ) -> CargoResult<bool> {
+        let TestUnit(unit, Some(TestInfo::Test(tests_found)), _) = test_unit else {
+            return Ok(false);
+        };
+
+        let suffix = format!("::{test_name}");
+        let tests = tests_found
+            .iter()
+            .filter(|t| t.as_str() == test_name || t.ends_with(&suffix))
+            .sorted_by(|t1, t2| Ord::cmp(t1.as_str(), t2.as_str()))
+            .collect_vec();
+
+        if let (_, Some(reason)) = self.changed_nodes(unit) {
+            anyhow::bail!("Failed to read dependency graph {reason}");
+        }
+
+        let spans = Cache::new(self.target_dir).spans()?;
+        for test in &tests {
+            let description = format!("{} ({})", test, unit.target.description_named());
+            match self.explain_node(test) {
+                Some(chain) => {
+                    shell.status("Explaining", format!("{description} is affected"))?;
+                    for (node, edge_types) in chain {
+                        let label = edge_types
+                            .map_or_else(|| "changed".to_string(), |types| format!("<- [{types}]"));
+                        shell.print_ansi_stderr(
+                            format!("    {label:<24} {}\n", located(&spans, &node)).as_bytes(),
+                        )?;
+                    }
+                }
+                None => {
+                    shell.status("Explaining", format!("{description} is not affected"))?;
+                }
+            }
+        }
+
+        Ok(!tests.is_empty())
+    }
+
+    fn technique(&self) -> &'static str {
+        "static RTS"
+    }
+
     fn cache_kind(&self) -> CacheKind {
         CacheKind::Static
     }
 
     fn note(&self, shell: &mut Shell, _test_args: &[&str]) {
-        let message = r"Regression Test Selection using a dependency graph";
+        let message = if self.edge_types == EdgeTypes::all() {
+            "Regression Test Selection using a dependency graph, following all edges".to_string()
+        } else {
+            format!(
+                "Regression Test Selection using a dependency graph, following only edges of types {}",
+                self.edge_types
+            )
+        };
 
         shell.print_ansi_stderr(b"\n").unwrap();
         shell
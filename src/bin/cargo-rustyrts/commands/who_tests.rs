@@ -0,0 +1,236 @@
+//! The `who-tests` subcommand, which prints the tests that exercise a function or a source location.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::bail;
+use cargo::{util::command_prelude::*, CargoResult};
+use itertools::Itertools;
+use rustyrts::{
+    cache::{Cache, Graph},
+    static_rts::graph::EdgeTypes,
+};
+
+use super::{archive::target_dirs, located, r#static::edge_types, strip_generic_args};
+
+pub fn cli() -> Command {
+    subcommand("who-tests")
+        .about(
+            r"Print the tests that exercise a function or a source location
+
+In static mode (default), the dependency graphs of the latest run of `cargo rustyrts static` are searched
+for tests that transitively depend on the function, and the chain of dependencies is printed for every test.
+In dynamic mode, the traces of the latest run of `cargo rustyrts dynamic` are searched for tests
+that have executed the function.",
+        )
+        .arg(
+            Arg::new("target")
+                .value_name("FUNCTION|FILE:LINE")
+                .help("Path of the function, optionally omitting leading path segments (e.g. `foo::bar` or `bar`), or a source location (e.g. `src/lib.rs:42`)")
+                .required(true),
+        )
+        .arg(flag("static", "Search the dependency graphs of static mode"))
+        .arg(
+            flag("dynamic", "Search the traces of dynamic mode")
+                .conflicts_with_all(["static", "edge-types"]),
+        )
+        .arg(
+            opt(
+                "edge-types",
+                "Types of edges that are followed, e.g. `Call,Drop,Static` or `!Contained` [default: all]",
+            )
+            .value_name("TYPES"),
+        )
+        .arg_target_dir()
+        .arg_manifest_path()
+}
+
+pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
+    who_tests(config, args)?;
+    Ok(())
+}
+
+fn who_tests(config: &mut Config, args: &ArgMatches) -> CargoResult<()> {
+    let ws = args.workspace(config)?;
+    let target = Target::parse(args.get_one::<String>("target").unwrap());
+    let dynamic = args.flag("dynamic");
+
+    let (_, dir) = target_dirs(&ws, config)?
+        .into_iter()
+        .find(|(mode, _)| *mode == if dynamic { "dynamic" } else { "static" })
+        .unwrap();
+    let cache = Cache::new(&dir);
+
+    let units = cache.units()?;
+    if units.is_empty() {
+        bail!(
+            "Did not find any tests, run `cargo rustyrts {}` first",
+            if dynamic { "dynamic" } else { "static" }
+        );
+    }
+
+    let spans = cache.spans()?;
+    let (description, functions): (String, Vec<String>) = match target {
+        Target::Function(name) => (format!("`{name}`"), vec![name]),
+        Target::Location(file, line) => {
+            let functions = spans
+                .iter()
+                .filter(|(_, span)| span.contains(&file, line))
+                .map(|(name, _)| name.clone())
+                .sorted()
+                .collect_vec();
+            if functions.is_empty() {
+                bail!("Did not find any function spanning {file}:{line}");
+            }
+            (format!("{file}:{line}"), functions)
+        }
+    };
+    let matches_any = |node: &str| functions.iter().any(|function| matches(node, function));
+
+    // Tests that exercise the function, grouped by their unit
+    let mut found: BTreeMap<String, Vec<(String, Vec<(String, Option<EdgeTypes>)>)>> =
+        BTreeMap::new();
+
+    if dynamic {
+        for unit in &units {
+            for test in cache.tests(unit)? {
+                let Some(trace) = cache.trace(&test)? else {
+                    continue;
+                };
+                if trace.iter().any(|node| matches_any(node)) {
+                    found
+                        .entry(unit.name().to_string())
+                        .or_default()
+                        .push((test, Vec::new()));
+                }
+            }
+        }
+    } else {
+        let edge_types = edge_types(&ws, args)?;
+        let mut graph = Graph::default();
+        for unit in cache.graph_units()? {
+            graph.extend(cache.graph(&unit)?);
+        }
+
+        let starting_points = graph.nodes().filter(|node| matches_any(node)).collect_vec();
+        if starting_points.is_empty() {
+            match functions.as_slice() {
+                [function] => {
+                    bail!("Did not find any function named `{function}` in the dependency graphs")
+                }
+                _ => bail!("Did not find any function at {description} in the dependency graphs"),
+            }
+        }
+        let dependents = Dependents::new(&graph, starting_points, *edge_types);
+
+        for unit in &units {
+            for test in cache.tests(unit)? {
+                if let Some(chain) = dependents.chain(&test) {
+                    found
+                        .entry(unit.name().to_string())
+                        .or_default()
+                        .push((test, chain));
+                }
+            }
+        }
+    }
+
+    if found.is_empty() {
+        config
+            .shell()
+            .status("Found", format!("no tests that exercise {description}"))?;
+        return Ok(());
+    }
+
+    let count = found.values().map(Vec::len).sum::<usize>();
+    config.shell().status(
+        "Found",
+        format!(
+            "{count} tests in {} units that exercise {description}",
+            found.len()
+        ),
+    )?;
+    for (unit, tests) in found {
+        cargo::drop_println!(config, "{unit}:");
+        for (test, chain) in tests {
+            cargo::drop_println!(config, "    {test}");
+            for (node, edge_types) in chain {
+                let label = edge_types
+                    .map_or_else(|| "function".to_string(), |types| format!("<- [{types}]"));
+                cargo::drop_println!(config, "        {label:<24} {}", located(&spans, &node));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What to search tests for
+enum Target {
+    Function(String),
+    Location(String, usize),
+}
+
+impl Target {
+    fn parse(target: &str) -> Self {
+        match target.rsplit_once(':') {
+            Some((file, line)) if file.ends_with(".rs") => match line.parse() {
+                Ok(line) => Target::Location(file.to_string(), line),
+                Err(_) => Target::Function(target.to_string()),
+            },
+            _ => Target::Function(target.to_string()),
+        }
+    }
+}
+
+/// Whether a node is named `name`, optionally omitting leading path segments or generic arguments
+fn matches(node: &str, name: &str) -> bool {
+    [node, strip_generic_args(node)].into_iter().any(|node| {
+        node == name
+            || node
+                .strip_suffix(name)
+                .is_some_and(|prefix| prefix.ends_with("::"))
+    })
+}
+
+/// All nodes that transitively depend on the starting points, remembering along which edge they have been reached first
+struct Dependents<'graph> {
+    predecessors: HashMap<&'graph str, Option<(&'graph str, u16)>>,
+}
+
+impl<'graph> Dependents<'graph> {
+    fn new(graph: &'graph Graph, starting_points: Vec<&'graph str>, edge_types: u16) -> Self {
+        let mut predecessors: HashMap<_, _> =
+            starting_points.iter().map(|node| (*node, None)).collect();
+        let mut queue: VecDeque<&str> = starting_points.into_iter().collect();
+
+        while let Some(node) = queue.pop_front() {
+            for (start, types) in graph.dependents(node) {
+                if types & edge_types != 0 && !predecessors.contains_key(start) {
+                    predecessors.insert(start, Some((node, types & edge_types)));
+                    queue.push_back(start);
+                }
+            }
+        }
+
+        Self { predecessors }
+    }
+
+    /// One shortest chain of dependencies from the function to `node`, if `node` depends on the function
+    fn chain(&self, node: &str) -> Option<Vec<(String, Option<EdgeTypes>)>> {
+        let mut chain = Vec::new();
+        let mut current = self.predecessors.get_key_value(node)?;
+        loop {
+            match current {
+                (node, Some((predecessor, types))) => {
+                    chain.push((node.to_string(), Some(EdgeTypes::from_raw(*types))));
+                    current = self.predecessors.get_key_value(predecessor).unwrap();
+                }
+                (node, None) => {
+                    chain.push((node.to_string(), None));
+                    break;
+                }
+            }
+        }
+        chain.reverse();
+        Some(chain)
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    fs::{copy, create_dir_all, read_dir, remove_file},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use cargo::{core::Workspace, CargoResult};
+use git2::{Oid, Repository, StatusOptions};
+use rustyrts::{
+    constants::DIR_SNAPSHOTS,
+    fs_utils::{CacheFileKind, CacheKind},
+};
+
+/// The revision that tests are selected against, when using `--baseline <rev>`
+///
+/// Snapshots of the cache are kept per commit id.
+/// Tests are selected against the snapshot of the merge-base of `HEAD` and `<rev>`.
+pub(crate) struct Baseline {
+    head: Oid,
+    merge_base: Oid,
+    clean: bool,
+}
+
+impl Baseline {
+    pub(crate) fn resolve(ws: &Workspace<'_>, rev: &str) -> CargoResult<Self> {
+        let repo = Repository::discover(ws.root())
+            .with_context(|| format!("Failed to open git repository at {}", ws.root().display()))?;
+
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .context("Failed to resolve HEAD")?
+            .id();
+        let baseline = repo
+            .revparse_single(rev)
+            .and_then(|object| object.peel_to_commit())
+            .with_context(|| format!("Failed to resolve baseline revision `{rev}`"))?
+            .id();
+        let merge_base = repo
+            .merge_base(head, baseline)
+            .with_context(|| format!("Failed to find merge-base of HEAD and `{rev}`"))?;
+
+        // Files in the target directory do not count as changes, even if they are not ignored
+        let target_dir = ws.target_dir().into_path_unlocked();
+        let workdir = repo.workdir().unwrap_or(ws.root()).to_path_buf();
+        let clean = repo
+            .statuses(Some(
+                StatusOptions::new()
+                    .include_untracked(false)
+                    .include_ignored(false),
+            ))?
+            .iter()
+            .filter_map(|entry| entry.path().map(|path| workdir.join(path)))
+            .all(|path| path.starts_with(&target_dir));
+
+        Ok(Self {
+            head,
+            merge_base,
+            clean,
+        })
+    }
+
+    pub(crate) fn merge_base(&self) -> Oid {
+        self.merge_base
+    }
+
+    pub(crate) fn head(&self) -> Oid {
+        self.head
+    }
+
+    /// Whether the working tree corresponds to `HEAD`, such that a snapshot can be recorded
+    pub(crate) fn is_clean(&self) -> bool {
+        self.clean
+    }
+
+    /// Replaces the cache files of the given kinds by those of the snapshot of the merge-base
+    ///
+    /// Returns `false` if there is no snapshot for the merge-base
+    pub(crate) fn restore(
+        &self,
+        target_dir: &Path,
+        file_kinds: &[(CacheKind, CacheFileKind)],
+    ) -> CargoResult<bool> {
+        let snapshot_dir = snapshot_dir(target_dir, self.merge_base);
+        if !snapshot_dir.is_dir() {
+            return Ok(false);
+        }
+
+        for (cache_kind, file_kind) in file_kinds {
+            let target = cache_kind.map(target_dir.to_path_buf());
+            let snapshot = cache_kind.map(snapshot_dir.clone());
+
+            for path in cache_files(&target, file_kind)? {
+                remove_file(path)?;
+            }
+            create_dir_all(&target)?;
+            for path in cache_files(&snapshot, file_kind)? {
+                copy(&path, target.join(path.file_name().unwrap()))?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Records the cache files of the given kinds as the snapshot of `HEAD`
+    pub(crate) fn record(
+        &self,
+        target_dir: &Path,
+        file_kinds: &[(CacheKind, CacheFileKind)],
+    ) -> CargoResult<()> {
+        let snapshot_dir = snapshot_dir(target_dir, self.head);
+
+        for (cache_kind, file_kind) in file_kinds {
+            let source = cache_kind.map(target_dir.to_path_buf());
+            let snapshot = cache_kind.map(snapshot_dir.clone());
+
+            for path in cache_files(&snapshot, file_kind)? {
+                remove_file(path)?;
+            }
+            create_dir_all(&snapshot)?;
+            for path in cache_files(&source, file_kind)? {
+                copy(&path, snapshot.join(path.file_name().unwrap()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn snapshot_dir(target_dir: &Path, commit: Oid) -> PathBuf {
+    let mut path = target_dir.join(DIR_SNAPSHOTS);
+    path.push(commit.to_string());
+    path
+}
+
+fn cache_files(dir: &Path, file_kind: &CacheFileKind) -> CargoResult<Vec<PathBuf>> {
+    let Ok(entries) = read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file()
+            && path.extension().and_then(|ext| ext.to_str()) == Some(file_kind.as_ref())
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
@@ -1,5 +1,3 @@
-#![allow(dead_code)]
-
 use anyhow::format_err;
 use cargo::ops;
 use cargo::{
@@ -16,7 +14,8 @@ use cargo::{
             unit_graph::{self},
             Executor,
         },
-        shell::Verbosity,
+        shell::{ColorChoice, Verbosity},
+        Shell,
     },
     util::profile,
 };
@@ -25,22 +24,34 @@ use cargo::{
     ops::TestOptions,
 };
 use cargo::{
+    drop_eprintln, drop_println,
     ops::CompileOptions,
     util::{add_path_args, CliError, CliResult, Config},
 };
 use cargo_util::{ProcessBuilder, ProcessError};
 use internment::Arena;
 use itertools::Itertools;
-use rustyrts::constants::ENV_TARGET_DIR;
-use std::{ffi::OsString, sync::Arc};
+use rustyrts::constants::{ENV_DRY_RUN, ENV_RETEST_ALL, ENV_TARGET_DIR, FILE_DRY_RUN};
+use rustyrts::fs_utils::CacheKind;
+use serde::Serialize;
+use std::{collections::HashSet, ffi::OsString, sync::Arc};
 use std::{fmt::Write, time::Instant};
 use std::{
+    fs::{create_dir_all, remove_file, write},
     path::{Path, PathBuf},
     string::String,
 };
 use tracing::trace;
 
-use crate::commands::{Selection, Selector, TestUnit};
+use super::declared::DeclaredFiles;
+use super::deselected::DeselectedTests;
+use super::fingerprint::BuildFingerprint;
+use super::gc::LiveNames;
+use super::outcomes::{read_failed_tests, write_failed_tests, TestOutcomes};
+use super::{baseline::Baseline, list_tests, Patch, Shard};
+use crate::commands::{
+    DoctestName, Selection, SelectionUnit, Selector, TestEnv, TestInfo, TestUnit,
+};
 
 //#####################################################################################################################
 // Source: https://github.com/rust-lang/cargo/blob/d0390c22b16ea6c800754fb7620ab8ee31debcc7/src/cargo/ops/cargo_test.rs
@@ -48,16 +59,77 @@ use crate::commands::{Selection, Selector, TestUnit};
 //#####################################################################################################################
 
 /// The kind of test.
-///
-/// This is needed because `Unit` does not track whether or not something is a
-/// benchmark.
 #[derive(Copy, Clone)]
 enum TestKind {
     Test,
-    Bench,
     Doctest,
 }
 
+/// The output format used when selected tests are only listed instead of being executed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ListFormat {
+    Human,
+    Json,
+}
+
+/// The selected and deselected tests of a single test unit.
+#[derive(Serialize)]
+struct ListedUnit {
+    #[serde(rename = "crate")]
+    crate_name: String,
+    compile_mode: String,
+    target: String,
+    doctest: bool,
+    selected: Vec<String>,
+    deselected: Vec<String>,
+}
+
+impl ListedUnit {
+    fn new(unit: &Unit, tests: Vec<(String, String)>, selection: &SelectionUnit) -> Self {
+        let (selected, deselected) = partition_tests(tests, selection);
+
+        Self {
+            crate_name: unit.target.crate_name(),
+            compile_mode: format!("{:?}", unit.mode),
+            target: unit.target.kind().description().to_string(),
+            doctest: unit.mode.is_doc_test(),
+            selected,
+            deselected,
+        }
+    }
+}
+
+/// Partitions `tests` into selected and deselected tests according to `selection`.
+///
+/// Every test is given as a tuple of the name that is displayed and the name that is used in `selection`.
+/// Only the names that are displayed are returned.
+fn partition_tests(
+    tests: Vec<(String, String)>,
+    selection: &SelectionUnit,
+) -> (Vec<String>, Vec<String>) {
+    let affected_tests: HashSet<&str> = match selection {
+        SelectionUnit::Precise(affected_tests) => {
+            affected_tests.iter().map(String::as_str).collect()
+        }
+        _ => HashSet::new(),
+    };
+
+    let (selected, deselected): (Vec<_>, Vec<_>) =
+        tests
+            .into_iter()
+            .sorted()
+            .partition(|(_, name)| match selection {
+                SelectionUnit::RetestAll => true,
+                SelectionUnit::CrateLevel { execute_tests } => *execute_tests,
+                SelectionUnit::Precise(_) => affected_tests.contains(name.as_str()),
+            });
+
+    (
+        selected.into_iter().map(|(display, _)| display).collect(),
+        deselected.into_iter().map(|(display, _)| display).collect(),
+    )
+}
+
 /// A unit that failed to run.
 struct UnitTestError {
     unit: Unit,
@@ -75,7 +147,7 @@ impl UnitTestError {
         let mut add = |which| write!(args, "--{which} {}", self.unit.target.name()).unwrap();
 
         match self.kind {
-            TestKind::Test | TestKind::Bench => match self.unit.target.kind() {
+            TestKind::Test => match self.unit.target.kind() {
                 TargetKind::Lib(_) => args.push_str("--lib"),
                 TargetKind::Bin => add("bin"),
                 TargetKind::Test => add("test"),
@@ -98,6 +170,10 @@ pub fn run_tests(
     options: &TestOptions,
     test_args: &[&str],
     selection: Selection,
+    listing: Option<ListFormat>,
+    baseline: Option<&str>,
+    patch: Option<Patch>,
+    shard: Option<Shard>,
 ) -> CliResult {
     let target_dir = ws.target_dir().into_path_unlocked();
 
@@ -114,8 +190,33 @@ pub fn run_tests(
         mode.prepare_cache(&target_dir, unit_graph);
     }
 
-    let exec = selection.executor(&target_dir);
-    let compilation = compile_tests(ws, &options.compile_opts, &bcx, exec)?;
+    let baseline = match (&selection, baseline) {
+        (Selection::Precise(mode), Some(rev)) => {
+            let baseline = Baseline::resolve(ws, rev)?;
+            if !baseline.restore(&target_dir, &mode.snapshot_kinds())? {
+                ws.config().shell().warn(format!(
+                    "Did not find a snapshot for merge-base {}, executing all tests",
+                    baseline.merge_base()
+                ))?;
+                std::env::set_var(ENV_RETEST_ALL, "true");
+            }
+            Some(baseline)
+        }
+        _ => None,
+    };
+
+    let compilation = compile_selecting_tests(
+        ws,
+        &options.compile_opts,
+        &bcx,
+        &selection,
+        &target_dir,
+        listing.is_some(),
+    )?;
+
+    if let (Selection::Precise(_), Some(patch)) = (&selection, patch) {
+        patch.apply(ws, &target_dir, unit_graph)?;
+    }
 
     if options.no_run {
         if !options.compile_opts.build_config.emit_json() {
@@ -128,15 +229,20 @@ pub fn run_tests(
     let mut selection_context = selection.selection_context(ws, &target_dir, &arena, unit_graph);
     let selector = selection_context.selector();
 
+    let mut listed = listing.map(|_| Vec::new());
+    let mut live = LiveNames::new(unit_graph.keys());
+
     let mut errors: Vec<UnitTestError> = run_unit_tests(
         ws,
         &options.compile_opts,
         test_args,
         &compilation,
-        TestKind::Test,
+        &bcx,
         selector,
         &arena,
         &target_dir,
+        shard,
+        listed.as_mut(),
     )?;
 
     let doctest_errors = run_doc_tests(
@@ -144,22 +250,115 @@ pub fn run_tests(
         &options.compile_opts,
         test_args,
         &compilation,
+        &bcx,
         selector,
         &target_dir,
+        shard,
+        listed.as_mut(),
+        &mut live,
     )?;
 
     errors.extend(doctest_errors);
 
+    if let (Some(format), Some(listed)) = (listing, listed) {
+        // The cache is not cleaned, such that changes are still considered on the next invocation
+        print_listing(ws.config(), format, &listed)?;
+        ws.config()
+            .shell()
+            .note("Tests have not been executed, changes are kept for the next invocation")?;
+        return Ok(());
+    }
+
     if !options.no_run {
         if let Selection::Precise(mode) = selection {
             mode.clean_cache(&target_dir);
             mode.clean_cache(&target_dir);
+
+            if let Some(baseline) = baseline {
+                if baseline.is_clean() {
+                    baseline.record(&target_dir, &mode.snapshot_kinds())?;
+                } else {
+                    ws.config().shell().note(format!(
+                        "Working tree has uncommitted changes, not recording a snapshot for {}",
+                        baseline.head()
+                    ))?;
+                }
+            }
+        }
+
+        // If only some targets are tested, the files of other targets cannot be told apart from stale ones
+        let all_targets = !options.compile_opts.filter.is_specific()
+            && ws.members().all(|pkg| {
+                bcx.roots
+                    .iter()
+                    .any(|unit| unit.pkg.package_id() == pkg.package_id())
+            });
+        if all_targets {
+            let removed = live.collect_garbage(&target_dir)?;
+            if removed.files > 0 {
+                ws.config().shell().status("Removed", removed)?;
+            }
         }
     }
 
     no_fail_fast_err(ws, &options.compile_opts, &errors)
 }
 
+/// Compiles tests and explains why a specific test is selected, without executing any tests.
+///
+/// Since no tests are executed, the cache is not cleaned afterwards.
+pub fn explain_test(
+    ws: &Workspace<'_>,
+    options: &CompileOptions,
+    test_name: &str,
+    selection: Selection,
+) -> CliResult {
+    let target_dir = ws.target_dir().into_path_unlocked();
+
+    let interner = UnitInterner::new();
+    let bcx = create_bcx(ws, options, &interner)?;
+    let unit_graph = &bcx.unit_graph;
+
+    if unit_graph.keys().any(|unit| !unit.target.harness()) {
+        return Err(anyhow::Error::msg("RustyRTS is incompatible with using a custom test harness. Please use the default test harness instead!").into());
+    }
+
+    let Selection::Precise(mode) = selection else {
+        return Err(anyhow::Error::msg(
+            "Explaining the selection of tests requires a precise selection mode",
+        )
+        .into());
+    };
+    mode.prepare_cache(&target_dir, unit_graph);
+
+    let compilation = compile_selecting_tests(ws, options, &bcx, &selection, &target_dir, true)?;
+
+    let arena = Arena::new();
+    let mut selection_context = selection.selection_context(ws, &target_dir, &arena, unit_graph);
+    let selector = selection_context.selector();
+
+    let config = ws.config();
+    let mut found = false;
+
+    for UnitOutput {
+        unit,
+        path,
+        script_meta,
+    } in &compilation.tests
+    {
+        let env =
+            TestEnv::new(&compilation.target_process(path, unit.kind, &unit.pkg, *script_meta)?);
+        let test_unit = TestUnit(unit, selector.test_info(unit, &arena, &target_dir), env);
+        found |= selector.explain(test_unit, test_name, &mut config.shell())?;
+    }
+
+    if !found {
+        return Err(anyhow::format_err!("Did not find any test named `{test_name}`").into());
+    }
+
+    Ok(())
+}
+
 /// Runs the unit and integration tests of a package.
 ///
 /// Returns a `Vec` of tests that failed when `--no-fail-fast` is used.
@@ -169,10 +368,12 @@ fn run_unit_tests<'compilation, 'context, 'arena: 'context>(
     options: &CompileOptions,
     test_args: &[&str],
     compilation: &'context Compilation<'compilation>,
-    test_kind: TestKind,
+    bcx: &BuildContext<'_, '_>,
     selector: &mut dyn Selector<'context>,
     arena: &'arena Arena<String>,
     target_dir: &Path,
+    shard: Option<Shard>,
+    mut listed: Option<&mut Vec<ListedUnit>>,
 ) -> Result<Vec<UnitTestError>, CliError> {
     let config = ws.config();
     let cwd = config.cwd();
@@ -189,11 +390,44 @@ fn run_unit_tests<'compilation, 'context, 'arena: 'context>(
     } in &compilation.tests
     {
         let start_time = Instant::now();
-        let test_unit = TestUnit(unit, selector.test_info(unit, arena, target_dir));
+        let test_info = selector.test_info(unit, arena, target_dir);
+
+        let prefix = unit.target.crate_name().to_string() + "::";
+        let tests_found = match &test_info {
+            Some(TestInfo::Test(tests)) => tests
+                .iter()
+                .map(|t| {
+                    (
+                        t.strip_prefix(&prefix).unwrap_or(t).to_string(),
+                        t.to_string(),
+                    )
+                })
+                .collect_vec(),
+            _ => Vec::new(),
+        };
+
+        let process = compilation.target_process(path, unit.kind, &unit.pkg, *script_meta)?;
+        let test_unit = TestUnit(unit, test_info, TestEnv::new(&process));
         let mut test_args = test_args.clone();
 
         let selected = selector.select_tests(test_unit, &mut config.shell(), start_time);
-        match &selected {
+        let run = UnitRun::new(
+            bcx,
+            &mut config.shell(),
+            target_dir,
+            unit,
+            selected,
+            &tests_found,
+            shard,
+            // To distribute the tests of a unit whose tests are not known, they are listed by the test binary
+            || {
+                Ok(list_tests(unit, process)?
+                    .into_iter()
+                    .map(|test| prefix.clone() + &test)
+                    .collect())
+            },
+        )?;
+        match &run.selected {
             crate::commands::SelectionUnit::RetestAll => {}
             crate::commands::SelectionUnit::CrateLevel { execute_tests } => {
                 if !execute_tests {
@@ -204,7 +438,6 @@ fn run_unit_tests<'compilation, 'context, 'arena: 'context>(
             crate::commands::SelectionUnit::Precise(affected_tests) => {
                 test_args.push("--exact");
 
-                let prefix = unit.target.crate_name().to_string() + "::";
                 trace!("Stripping crate name {:?}", prefix);
                 let mut affected_tests = affected_tests
                     .iter()
@@ -219,6 +452,17 @@ fn run_unit_tests<'compilation, 'context, 'arena: 'context>(
             }
         };
 
+        if let Some(listed) = listed.as_deref_mut() {
+            listed.push(ListedUnit::new(unit, tests_found, &run.selected));
+            continue;
+        }
+
+        let expected_tests = expected_tests(&run.selected, tests_found)
+            .filter(|t| t.starts_with(&prefix))
+            .collect_vec();
+        let color_arg = color_arg(config, &test_args);
+        test_args.extend(color_arg);
+
         let (exe_display, mut cmd) = cmd_builds(
             config,
             cwd,
@@ -243,14 +487,17 @@ fn run_unit_tests<'compilation, 'context, 'arena: 'context>(
             .shell()
             .verbose(|shell| shell.status("Running", &cmd))?;
 
-        if let Err(e) = cmd.exec() {
-            let unit_err = UnitTestError {
-                unit: unit.clone(),
-                kind: test_kind,
-            };
-            report_test_error(ws, &test_args, options, &unit_err, e);
-            errors.push(unit_err);
-        }
+        errors.extend(run.execute(
+            ws,
+            options,
+            &test_args,
+            target_dir,
+            unit,
+            TestKind::Test,
+            &cmd,
+            selector.technique(),
+            expected_tests,
+        ));
     }
     Ok(errors)
 }
@@ -264,8 +511,12 @@ fn run_doc_tests<'compilation, 'context, 'arena: 'context>(
     options: &CompileOptions,
     test_args: &[&str],
     compilation: &'context Compilation<'compilation>,
+    bcx: &BuildContext<'_, '_>,
     selector: &mut dyn Selector<'context>,
     target_dir: &Path,
+    shard: Option<Shard>,
+    mut listed: Option<&mut Vec<ListedUnit>>,
+    live: &mut LiveNames,
 ) -> Result<Vec<UnitTestError>, CliError> {
     let config = ws.config();
     let mut errors = Vec::new();
@@ -296,14 +547,39 @@ fn run_doc_tests<'compilation, 'context, 'arena: 'context>(
         args.push(rlib_source.into_os_string());
 
         let start = Instant::now();
-        let test_unit = TestUnit(
-            unit,
-            selector.doctest_info(ws, test_args, compilation, target_dir, doctest_info)?,
-        );
+        let test_info =
+            selector.doctest_info(ws, test_args, compilation, target_dir, doctest_info)?;
+
+        let tests_found = match &test_info {
+            Some(TestInfo::Doctest(tests)) => {
+                live.add_doctests(unit, tests);
+                tests
+                    .iter()
+                    .map(|t| (t.clone(), DoctestName::new(t.clone()).trimmed_name()))
+                    .collect_vec()
+            }
+            _ => Vec::new(),
+        };
+
+        let mut process = compilation.rustdoc_process(unit, *script_meta)?;
+        for (var, value) in env {
+            process.env(var, value);
+        }
+        let test_unit = TestUnit(unit, test_info, TestEnv::new(&process));
         let mut test_args = Vec::from(test_args);
 
         let selected = selector.select_tests(test_unit, &mut config.shell(), start);
-        match &selected {
+        let run = UnitRun::new(
+            bcx,
+            &mut config.shell(),
+            target_dir,
+            unit,
+            selected,
+            &tests_found,
+            shard,
+            || Ok(Vec::new()),
+        )?;
+        match &run.selected {
             crate::commands::SelectionUnit::RetestAll => {}
             crate::commands::SelectionUnit::CrateLevel { execute_tests } => {
                 if !execute_tests {
@@ -321,12 +597,17 @@ fn run_doc_tests<'compilation, 'context, 'arena: 'context>(
             }
         };
 
-        config.shell().status("Doc-tests", unit.target.name())?;
-        let mut p = compilation.rustdoc_process(unit, *script_meta)?;
-
-        for (var, value) in env {
-            p.env(var, value);
+        if let Some(listed) = listed.as_deref_mut() {
+            listed.push(ListedUnit::new(unit, tests_found, &run.selected));
+            continue;
         }
+
+        let expected_tests = expected_tests(&run.selected, tests_found).collect_vec();
+        let color_arg = color_arg(config, &test_args);
+        test_args.extend(color_arg);
+
+        config.shell().status("Doc-tests", unit.target.name())?;
+        let mut p = process;
         p.arg("--crate-name").arg(&unit.target.crate_name());
         p.arg("--test");
 
@@ -378,18 +659,237 @@ fn run_doc_tests<'compilation, 'context, 'arena: 'context>(
             .shell()
             .verbose(|shell| shell.status("Running", p.to_string()))?;
 
-        if let Err(e) = p.exec() {
-            let unit_err = UnitTestError {
-                unit: unit.clone(),
-                kind: TestKind::Doctest,
-            };
-            report_test_error(ws, &test_args, options, &unit_err, e);
-            errors.push(unit_err);
-        }
+        errors.extend(run.execute(
+            ws,
+            options,
+            &test_args,
+            target_dir,
+            unit,
+            TestKind::Doctest,
+            &p,
+            selector.technique(),
+            expected_tests,
+        ));
     }
     Ok(errors)
 }
 
+/// The selection of the tests of a single unit, together with the state that is recorded once they have been executed
+struct UnitRun {
+    selected: SelectionUnit,
+    /// Tests that are neither selected nor executed by another shard, as displayed by libtest
+    deselected: Vec<String>,
+    fingerprint: BuildFingerprint,
+    declared: DeclaredFiles,
+}
+
+impl UnitRun {
+    /// Extends the selection of a unit by the tests that failed previously and the tests that depend on changed files,
+    /// falls back to retesting all tests if the build configuration changed, and restricts it to the tests of `shard`
+    ///
+    /// `list_tests` provides the tests of the unit in case they are not known and have to be distributed over the shards.
+    fn new(
+        bcx: &BuildContext<'_, '_>,
+        shell: &mut Shell,
+        target_dir: &Path,
+        unit: &Unit,
+        selected: SelectionUnit,
+        tests_found: &[(String, String)],
+        shard: Option<Shard>,
+        list_tests: impl FnOnce() -> CargoResult<Vec<String>>,
+    ) -> CargoResult<Self> {
+        let fingerprint = BuildFingerprint::new(bcx, unit);
+        let declared = DeclaredFiles::new(unit)?;
+        let selected = selected
+            .with_failed(read_failed_tests(target_dir, unit))
+            .with_tests(declared.affected_tests(
+                target_dir,
+                unit,
+                tests_found.iter().map(|(_, name)| name.as_str()),
+            ));
+        let selected = fingerprint.check(target_dir, unit, shell, selected)?;
+
+        // Tests that are executed by other shards are not reported as ignored
+        let (_, deselected) = partition_tests(tests_found.to_vec(), &selected);
+        let selected = match shard {
+            Some(shard) => {
+                let listed_tests = match &selected {
+                    SelectionUnit::RetestAll
+                    | SelectionUnit::CrateLevel {
+                        execute_tests: true,
+                    } if tests_found.is_empty() => list_tests()?,
+                    _ => Vec::new(),
+                };
+                shard.apply(
+                    target_dir,
+                    unit,
+                    selected,
+                    tests_found
+                        .iter()
+                        .map(|(_, name)| name.as_str())
+                        .chain(listed_tests.iter().map(String::as_str)),
+                )
+            }
+            None => selected,
+        };
+
+        Ok(Self {
+            selected,
+            deselected,
+            fingerprint,
+            declared,
+        })
+    }
+
+    /// Executes the selected tests and records their outcomes, the tests that did not pass,
+    /// the build configuration and the checksums of the declared files
+    ///
+    /// Returns an error if the tests of the unit did not pass.
+    fn execute(
+        &self,
+        ws: &Workspace<'_>,
+        options: &CompileOptions,
+        test_args: &[&str],
+        target_dir: &Path,
+        unit: &Unit,
+        kind: TestKind,
+        cmd: &ProcessBuilder,
+        technique: &str,
+        expected_tests: Vec<String>,
+    ) -> Option<UnitTestError> {
+        let config = ws.config();
+        let deselected = DeselectedTests::new(self.deselected.clone(), technique, test_args);
+
+        // Tests that failed previously and are not executed now, e.g. because they belong to another shard, are kept
+        let executed: Option<HashSet<&str>> = match &self.selected {
+            SelectionUnit::Precise(tests) => Some(tests.iter().map(String::as_str).collect()),
+            SelectionUnit::CrateLevel {
+                execute_tests: false,
+            } => Some(HashSet::new()),
+            _ => None,
+        };
+        let mut failed: Vec<String> = match executed {
+            Some(executed) => read_failed_tests(target_dir, unit)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|test| !executed.contains(test.as_str()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mut outcomes = TestOutcomes::new(unit);
+        let result = exec_recording_outcomes(config, cmd, &mut outcomes);
+        deselected.report(config);
+        outcomes.collect_reported_durations(target_dir);
+        outcomes.record(target_dir, unit);
+        let error = match result {
+            Err(e) => {
+                failed.extend(outcomes.not_passed(expected_tests));
+                write_failed_tests(target_dir, unit, Some(failed));
+
+                let unit_err = UnitTestError {
+                    unit: unit.clone(),
+                    kind,
+                };
+                report_test_error(ws, test_args, options, &unit_err, e);
+                Some(unit_err)
+            }
+            Ok(()) => {
+                write_failed_tests(target_dir, unit, (!failed.is_empty()).then_some(failed));
+                None
+            }
+        };
+        self.fingerprint.write(target_dir, unit);
+        self.declared.write(target_dir, unit);
+        error
+    }
+}
+
+/// Computes the names of the tests that are expected to be executed, given the selection
+fn expected_tests(
+    selected: &SelectionUnit,
+    tests_found: Vec<(String, String)>,
+) -> impl Iterator<Item = String> {
+    let expected = match selected {
+        SelectionUnit::Precise(affected_tests) => affected_tests.clone(),
+        SelectionUnit::RetestAll
+        | SelectionUnit::CrateLevel {
+            execute_tests: true,
+        } => tests_found.into_iter().map(|(_, name)| name).collect(),
+        SelectionUnit::CrateLevel {
+            execute_tests: false,
+        } => Vec::new(),
+    };
+    expected.into_iter()
+}
+
+/// Forwards an explicit `--color` of cargo to the test binary, unless colors are configured in the arguments of libtest
+///
+/// Otherwise, libtest decides on its own, which disables colors since its output is captured.
+fn color_arg(config: &Config, test_args: &[&str]) -> Option<&'static str> {
+    if test_args.iter().any(|arg| arg.starts_with("--color")) {
+        return None;
+    }
+    match config.shell().color_choice() {
+        ColorChoice::Always => Some("--color=always"),
+        ColorChoice::Never => Some("--color=never"),
+        ColorChoice::CargoAuto => None,
+    }
+}
+
+/// Executes a test binary, forwarding its output while recording the outcomes of the tests
+fn exec_recording_outcomes(
+    config: &Config,
+    cmd: &ProcessBuilder,
+    outcomes: &mut TestOutcomes,
+) -> CargoResult<()> {
+    cmd.exec_with_streaming(
+        &mut |line| {
+            outcomes.parse_line(line);
+            drop_println!(config, "{line}");
+            Ok(())
+        },
+        &mut |line| {
+            drop_eprintln!(config, "{line}");
+            Ok(())
+        },
+        false,
+    )
+    .map(|_| ())
+}
+
+/// Displays the selected and deselected tests of every test unit.
+///
+/// This is used when `--list-selected` is used.
+fn print_listing(config: &Config, format: ListFormat, listed: &[ListedUnit]) -> CargoResult<()> {
+    match format {
+        ListFormat::Json => {
+            drop_println!(config, "{}", serde_json::to_string(listed)?);
+        }
+        ListFormat::Human => {
+            for unit in listed {
+                drop_println!(
+                    config,
+                    "{} ({} {}): {} selected, {} deselected",
+                    unit.crate_name,
+                    unit.compile_mode,
+                    unit.target,
+                    unit.selected.len(),
+                    unit.deselected.len()
+                );
+                for test in &unit.selected {
+                    drop_println!(config, "    selected   {test}");
+                }
+                for test in &unit.deselected {
+                    drop_println!(config, "    deselected {test}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Displays human-readable descriptions of the test executables.
 ///
 /// This is used when `cargo test --no-run` is used.
@@ -501,7 +1001,6 @@ fn report_test_error(
 ) {
     let which = match unit_err.kind {
         TestKind::Test => "test failed",
-        TestKind::Bench => "bench failed",
         TestKind::Doctest => "doctest failed",
     };
 
@@ -534,6 +1033,38 @@ fn report_test_error(
 // Adapted to use custom executor and re-use existing context
 //#####################################################################################################################
 
+/// Compiles the tests using the executor of `selection`
+///
+/// In a dry run, checksums and changes are not updated, such that the next run still considers the same changes.
+/// Since the next run has to recompile all units to update their checksums, a marker is left in the target directory.
+fn compile_selecting_tests<'a>(
+    ws: &Workspace<'a>,
+    options: &CompileOptions,
+    bcx: &BuildContext<'a, 'a>,
+    selection: &Selection,
+    target_dir: &Path,
+    dry_run: bool,
+) -> CargoResult<Compilation<'a>> {
+    let marker = CacheKind::General
+        .map(target_dir.to_path_buf())
+        .join(FILE_DRY_RUN);
+    if dry_run {
+        std::env::set_var(ENV_DRY_RUN, "true");
+    }
+
+    let exec = selection.executor(&target_dir.to_path_buf());
+    let compilation = compile_tests(ws, options, bcx, exec)?;
+
+    if dry_run {
+        create_dir_all(marker.parent().unwrap())?;
+        write(&marker, "")?;
+    } else if marker.exists() {
+        remove_file(&marker)?;
+    }
+
+    Ok(compilation)
+}
+
 fn compile_tests<'a>(
     ws: &Workspace<'a>,
     options: &CompileOptions,
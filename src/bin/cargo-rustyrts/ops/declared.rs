@@ -0,0 +1,165 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::read_to_string,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+};
+
+use anyhow::Context;
+use cargo::{core::compiler::Unit, CargoResult};
+use glob::Pattern;
+use rustyrts::fs_utils::{write_to_file, CacheFileKind};
+
+use super::general_cache_path;
+
+/// Files that tests depend on at runtime, as declared in the manifest of a package
+///
+/// ```toml
+/// [package.metadata.rustyrts.file-dependencies]
+/// "tests::parse_fixture" = ["tests/fixtures/*.json"]
+/// "*" = ["config/**/*.toml"]
+/// ```
+///
+/// Keys are names of tests, where leading path segments may be omitted. The key `*` applies to all tests.
+/// Globs are relative to the root of the package.
+pub(crate) struct DeclaredFiles {
+    declarations: Vec<(String, Vec<Pattern>)>,
+    checksums: BTreeMap<String, u64>,
+}
+
+impl DeclaredFiles {
+    pub(crate) fn new(unit: &Unit) -> CargoResult<Self> {
+        let root = unit.pkg.root();
+
+        let mut declarations = Vec::new();
+        if let Some(table) = unit
+            .pkg
+            .manifest()
+            .custom_metadata()
+            .and_then(|metadata| metadata.get("rustyrts"))
+            .and_then(|rustyrts| rustyrts.get("file-dependencies"))
+        {
+            let table = table
+                .as_table()
+                .context("`package.metadata.rustyrts.file-dependencies` must be a table")?;
+            for (test, globs) in table {
+                let globs = globs
+                    .as_array()
+                    .and_then(|globs| globs.iter().map(|g| g.as_str()).collect::<Option<Vec<_>>>())
+                    .with_context(|| {
+                        format!("File dependencies of `{test}` must be an array of strings")
+                    })?;
+                let patterns = globs
+                    .into_iter()
+                    .map(Pattern::new)
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("Invalid glob in file dependencies of `{test}`"))?;
+                declarations.push((test.clone(), patterns));
+            }
+        }
+
+        let mut checksums = BTreeMap::new();
+        for pattern in declarations.iter().flat_map(|(_, patterns)| patterns) {
+            let full_pattern = root.join(pattern.as_str());
+            for path in glob::glob(&full_pattern.to_string_lossy())?.flatten() {
+                if let (Ok(relative), Ok(content)) = (path.strip_prefix(root), std::fs::read(&path))
+                {
+                    let mut hasher = DefaultHasher::new();
+                    content.hash(&mut hasher);
+                    checksums.insert(relative.display().to_string(), hasher.finish());
+                }
+            }
+        }
+
+        Ok(Self {
+            declarations,
+            checksums,
+        })
+    }
+
+    /// Determines the tests that depend on a file that has been added, removed or modified since the previous run
+    ///
+    /// `tests` are given in the same form as they appear in `SelectionUnit::Precise`.
+    pub(crate) fn affected_tests<'a>(
+        &self,
+        target_dir: &Path,
+        unit: &Unit,
+        tests: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        if self.declarations.is_empty() {
+            return Vec::new();
+        }
+        let Some(previous) = read_checksums(target_dir, unit) else {
+            return Vec::new();
+        };
+
+        let changed: HashSet<&str> = self
+            .checksums
+            .keys()
+            .chain(previous.keys())
+            .filter(|path| self.checksums.get(*path) != previous.get(*path))
+            .map(String::as_str)
+            .collect();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+
+        let changed_patterns: Vec<&str> = self
+            .declarations
+            .iter()
+            .filter(|(_, patterns)| {
+                patterns
+                    .iter()
+                    .any(|pattern| changed.iter().any(|path| pattern.matches(path)))
+            })
+            .map(|(test, _)| test.as_str())
+            .collect();
+
+        tests
+            .into_iter()
+            .filter(|test| {
+                changed_patterns.iter().any(|declared| {
+                    *declared == "*"
+                        || test == declared
+                        || test
+                            .strip_suffix(declared)
+                            .is_some_and(|prefix| prefix.ends_with("::"))
+                })
+            })
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Records the checksums of the declared files, to compare against in the next run
+    pub(crate) fn write(&self, target_dir: &Path, unit: &Unit) {
+        let path = general_cache_path(target_dir, unit, CacheFileKind::Files);
+        if self.declarations.is_empty() {
+            let _ = std::fs::remove_file(path);
+            return;
+        }
+
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create cache directory");
+        let content = self
+            .checksums
+            .iter()
+            .map(|(path, checksum)| format!("{checksum:016x} {path}\n"))
+            .collect::<String>();
+        write_to_file(content, path, |_| {}, false);
+    }
+}
+
+fn read_checksums(target_dir: &Path, unit: &Unit) -> Option<BTreeMap<String, u64>> {
+    let content =
+        read_to_string(general_cache_path(target_dir, unit, CacheFileKind::Files)).ok()?;
+    Some(
+        content
+            .lines()
+            .filter_map(|l| l.split_once(' '))
+            .filter_map(|(checksum, path)| {
+                u64::from_str_radix(checksum, 16)
+                    .ok()
+                    .map(|checksum| (path.to_string(), checksum))
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,53 @@
+use cargo::{drop_println, util::Config};
+
+/// Reports tests that have not been selected, after the output of libtest
+///
+/// Deselected tests are excluded using filters, such that libtest only counts them as filtered out.
+/// To distinguish them from tests that do not exist anymore, a separate summary line names them,
+/// which is a json object of type `deselected` if libtest reports in json format.
+pub(crate) struct DeselectedTests {
+    names: Vec<String>,
+    reason: String,
+    json: bool,
+}
+
+impl DeselectedTests {
+    /// `names` are given as displayed by libtest, i.e. without the name of the crate
+    pub(crate) fn new(names: Vec<String>, technique: &str, test_args: &[&str]) -> Self {
+        let json = test_args
+            .iter()
+            .zip(test_args.iter().skip(1).chain([&""]))
+            .any(|(arg, next)| *arg == "--format=json" || (*arg == "--format" && *next == "json"));
+
+        Self {
+            names,
+            reason: format!("not affected by changes ({technique})"),
+            json,
+        }
+    }
+
+    /// Prints the summary, unless all tests have been selected
+    pub(crate) fn report(&self, config: &Config) {
+        if self.names.is_empty() {
+            return;
+        }
+
+        if self.json {
+            let event = serde_json::json!({
+                "type": "deselected",
+                "reason": self.reason,
+                "names": self.names,
+            });
+            drop_println!(config, "{event}");
+        } else {
+            drop_println!(
+                config,
+                "deselected {} test{}, {}: {}",
+                self.names.len(),
+                if self.names.len() == 1 { "" } else { "s" },
+                self.reason,
+                self.names.join(", ")
+            );
+        }
+    }
+}
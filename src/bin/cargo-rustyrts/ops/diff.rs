@@ -0,0 +1,511 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use cargo::{
+    core::{
+        compiler::{unit_graph::UnitGraph, CompileMode},
+        PackageId, Workspace,
+    },
+    CargoResult,
+};
+use git2::{DiffFormat, DiffOptions, Repository};
+use itertools::Itertools;
+use rustyrts::{
+    cache::{Cache, Span},
+    constants::PREFIX_FILE,
+    fs_utils::{CacheFileKind, CacheKind},
+};
+
+use super::unit_cache_path;
+
+/// How a file has been changed by a patch
+#[derive(Debug, PartialEq, Eq)]
+enum FileChange {
+    /// Lines of the file in the working tree that have been added or modified, or next to which lines have been removed
+    Lines(BTreeSet<usize>),
+    /// The file has been deleted, renamed or is binary, such that the changes cannot be attributed to lines
+    Whole,
+}
+
+/// Changes that are given as a unified diff, when using `--from-diff <file>` or `--from-git-diff <rev>`
+///
+/// Instead of comparing checksums to those of the previous run, the functions whose bodies span changed lines
+/// are considered to be changed. Thus, tests can be selected without keeping the metadata of a previous run.
+pub(crate) struct Patch {
+    files: BTreeMap<PathBuf, FileChange>,
+}
+
+impl Patch {
+    /// Reads a unified diff from a file, e.g. the output of `git diff`
+    ///
+    /// Paths are relative to the root of the git repository that contains the workspace,
+    /// or relative to the root of the workspace.
+    pub(crate) fn read(ws: &Workspace<'_>, path: &Path) -> CargoResult<Self> {
+        let diff = read_to_string(path)
+            .with_context(|| format!("Failed to read diff {}", path.display()))?;
+        let root = Repository::discover(ws.root())
+            .ok()
+            .and_then(|repo| repo.workdir().map(Path::to_path_buf))
+            .unwrap_or_else(|| ws.root().to_path_buf());
+
+        Ok(Self::parse(&diff, &[&root, ws.root()]))
+    }
+
+    /// Computes the changes of the working tree since a git revision, including untracked files
+    pub(crate) fn from_git(ws: &Workspace<'_>, rev: &str) -> CargoResult<Self> {
+        let repo = Repository::discover(ws.root())
+            .with_context(|| format!("Failed to open git repository at {}", ws.root().display()))?;
+        let tree = repo
+            .revparse_single(rev)
+            .and_then(|object| object.peel_to_tree())
+            .with_context(|| format!("Failed to resolve revision `{rev}`"))?;
+
+        let mut options = DiffOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+        let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?;
+
+        let mut text = String::new();
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            if matches!(line.origin(), ' ' | '+' | '-') {
+                text.push(line.origin());
+            }
+            text.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+
+        let root = repo.workdir().unwrap_or(ws.root()).to_path_buf();
+        Ok(Self::parse(&text, &[&root]))
+    }
+
+    /// Parses a unified diff, resolving paths relative to the first of `roots` at which the file exists
+    fn parse(diff: &str, roots: &[&Path]) -> Self {
+        let resolve = |path: &str| {
+            let path = path.split('\t').next().unwrap().trim_end();
+            let path = path
+                .strip_prefix("a/")
+                .or_else(|| path.strip_prefix("b/"))
+                .unwrap_or(path);
+            roots
+                .iter()
+                .map(|root| root.join(path))
+                .find(|path| path.exists())
+                .unwrap_or_else(|| roots[0].join(path))
+        };
+
+        let mut files = BTreeMap::new();
+        let mut with_hunks = HashSet::new();
+        let mut old_file = None;
+        let mut current = None;
+
+        // Remaining lines of the current hunk in the old and the new file, and the current line of the new file
+        let (mut old_remaining, mut new_remaining, mut line) = (0usize, 0usize, 0usize);
+
+        for text in diff.lines() {
+            if old_remaining > 0 || new_remaining > 0 {
+                // Every changed line is relevant, since even a line that looks like a comment may be part of a string literal
+                let (origin, _) = text.split_at(text.len().min(1));
+                let lines = match current.as_ref().and_then(|file| files.get_mut(file)) {
+                    Some(FileChange::Lines(lines)) => Some(lines),
+                    _ => None,
+                };
+                match origin {
+                    "+" => {
+                        if let Some(lines) = lines {
+                            lines.insert(line);
+                        }
+                        line += 1;
+                        new_remaining = new_remaining.saturating_sub(1);
+                    }
+                    "-" => {
+                        // Removed lines are attributed to both of the adjacent lines in the new file
+                        if let Some(lines) = lines {
+                            lines.insert(line.saturating_sub(1).max(1));
+                            lines.insert(line);
+                        }
+                        old_remaining = old_remaining.saturating_sub(1);
+                    }
+                    "\\" => {}
+                    _ => {
+                        line += 1;
+                        old_remaining = old_remaining.saturating_sub(1);
+                        new_remaining = new_remaining.saturating_sub(1);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(header) = text.strip_prefix("diff --git ") {
+                if let Some((_, path)) = header.rsplit_once(" b/") {
+                    let path = resolve(path);
+                    files
+                        .entry(path.clone())
+                        .or_insert_with(|| FileChange::Lines(BTreeSet::new()));
+                    current = Some(path);
+                }
+            } else if let Some(path) = text.strip_prefix("--- ") {
+                old_file = (path != "/dev/null").then(|| resolve(path));
+            } else if let Some(path) = text.strip_prefix("+++ ") {
+                if path == "/dev/null" {
+                    current = old_file.take();
+                    if let Some(path) = &current {
+                        files.insert(path.clone(), FileChange::Whole);
+                    }
+                } else {
+                    let path = resolve(path);
+                    files
+                        .entry(path.clone())
+                        .or_insert_with(|| FileChange::Lines(BTreeSet::new()));
+                    current = Some(path);
+                }
+            } else if let Some(ranges) = text.strip_prefix("@@ ") {
+                let (old, new) = parse_ranges(ranges).unwrap_or((0, (0, 0)));
+                old_remaining = old;
+                (line, new_remaining) = new;
+                if new_remaining == 0 {
+                    // Without any line in the new file, the hunk starts after the given line
+                    line += 1;
+                }
+                with_hunks.extend(current.clone());
+            } else if let Some(path) = text
+                .strip_prefix("rename from ")
+                .or_else(|| text.strip_prefix("rename to "))
+            {
+                files.insert(resolve(path), FileChange::Whole);
+            } else if text.starts_with("Binary files ") {
+                if let Some(path) = &current {
+                    files.insert(path.clone(), FileChange::Whole);
+                }
+            }
+        }
+
+        // Files without any hunk have changed in some other way, e.g. their mode
+        for (path, change) in &mut files {
+            if !with_hunks.contains(path) {
+                *change = FileChange::Whole;
+            }
+        }
+
+        Self { files }
+    }
+
+    /// Replaces the changes of all units by the functions whose bodies span changed lines
+    ///
+    /// If some change of a package cannot be attributed to any function, e.g. a change of `Cargo.toml`,
+    /// the file is marked as changed, such that all tests of the package and of its dependents are executed.
+    pub(crate) fn apply(
+        &self,
+        ws: &Workspace<'_>,
+        target_dir: &Path,
+        unit_graph: &UnitGraph,
+    ) -> CargoResult<()> {
+        let spans = Cache::new(target_dir).spans()?;
+
+        // Spans of the functions of every file, keyed by the canonical path of the file
+        let mut canonical = HashMap::new();
+        let mut spans_by_file: HashMap<PathBuf, Vec<(&str, &Span)>> = HashMap::new();
+        for (name, span) in &spans {
+            let file = canonical
+                .entry(span.file.as_str())
+                .or_insert_with(|| canonicalize(&ws.root().join(&span.file)));
+            spans_by_file
+                .entry(file.clone())
+                .or_default()
+                .push((name.as_str(), span));
+        }
+
+        let packages = ws
+            .members()
+            .map(|pkg| (canonicalize(pkg.root()), pkg.package_id()))
+            .collect_vec();
+        let target_dir_canonical = canonicalize(target_dir);
+
+        let mut changed_functions: HashMap<PackageId, BTreeSet<String>> = HashMap::new();
+        let mut changed_files: HashMap<PackageId, BTreeSet<String>> = HashMap::new();
+
+        for (path, change) in &self.files {
+            let path = canonicalize(path);
+            if path.starts_with(&target_dir_canonical) {
+                continue;
+            }
+
+            // The configuration of the workspace affects all packages
+            let ws_root = canonicalize(ws.root());
+            if is_workspace_config(&ws_root, &path) {
+                let file = format!("{}", relative(&path, &ws_root).display());
+                for (_, package) in &packages {
+                    changed_files
+                        .entry(*package)
+                        .or_default()
+                        .insert(file.clone());
+                }
+                continue;
+            }
+
+            let Some((root, package)) = packages
+                .iter()
+                .filter(|(root, _)| path.starts_with(root))
+                .max_by_key(|(root, _)| root.components().count())
+            else {
+                continue;
+            };
+
+            let functions = match change {
+                FileChange::Lines(lines)
+                    if path.extension().is_some_and(|extension| extension == "rs") =>
+                {
+                    let in_file = spans_by_file.get(&path).map_or(&[][..], Vec::as_slice);
+                    lines
+                        .iter()
+                        .map(|line| {
+                            let functions = in_file
+                                .iter()
+                                .filter(|(_, span)| span.start <= *line && *line <= span.end)
+                                .map(|(name, _)| name.to_string())
+                                .collect_vec();
+                            (!functions.is_empty()).then_some(functions)
+                        })
+                        .collect::<Option<Vec<_>>>()
+                }
+                _ => None,
+            };
+
+            match functions {
+                Some(functions) => changed_functions
+                    .entry(*package)
+                    .or_default()
+                    .extend(functions.into_iter().flatten()),
+                None => {
+                    let file = format!("{}", relative(&path, root).display());
+                    changed_files.entry(*package).or_default().insert(file);
+                }
+            }
+        }
+
+        ws.config().shell().status(
+            "Diff",
+            format!(
+                "{} changed functions, {} changed files that cannot be attributed to functions",
+                changed_functions.values().map(BTreeSet::len).sum::<usize>(),
+                changed_files.values().map(BTreeSet::len).sum::<usize>()
+            ),
+        )?;
+
+        for unit in unit_graph.keys() {
+            if !matches!(unit.mode, CompileMode::Build | CompileMode::Test)
+                || unit.target.is_custom_build()
+            {
+                continue;
+            }
+
+            let package = unit.pkg.package_id();
+            let changes = changed_files
+                .get(&package)
+                .into_iter()
+                .flatten()
+                .map(|file| format!("{PREFIX_FILE}{file}"))
+                .chain(
+                    changed_functions
+                        .get(&package)
+                        .into_iter()
+                        .flatten()
+                        .cloned(),
+                )
+                .join("\n");
+
+            let path = unit_cache_path(target_dir, unit, CacheKind::Static, CacheFileKind::Changes);
+            create_dir_all(path.parent().unwrap())?;
+            write(&path, changes).with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the ranges of the header of a hunk, e.g. `-1,5 +1,6 @@`,
+/// into the number of lines in the old file and the first line and number of lines in the new file
+fn parse_ranges(ranges: &str) -> Option<(usize, (usize, usize))> {
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+
+    let mut parts = ranges.split_whitespace();
+    let (_, old) = range(parts.next()?.strip_prefix('-')?)?;
+    let new = range(parts.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// Files in the root of a workspace that configure how all of its packages are compiled
+fn is_workspace_config(ws_root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(ws_root) else {
+        return false;
+    };
+    relative == Path::new("Cargo.toml")
+        || relative == Path::new("Cargo.lock")
+        || relative.starts_with(".cargo")
+        || relative.starts_with("rust-toolchain")
+        || relative == Path::new("rust-toolchain.toml")
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Expresses a path relative to `base` if possible
+fn relative(path: &Path, base: &Path) -> PathBuf {
+    path.strip_prefix(base).unwrap_or(path).to_path_buf()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{parse_ranges, FileChange, Patch};
+
+    fn lines(patch: &Patch, file: &str) -> Option<Vec<usize>> {
+        match patch.files.get(&Path::new("/repo").join(file))? {
+            FileChange::Lines(lines) => Some(lines.iter().copied().collect()),
+            FileChange::Whole => None,
+        }
+    }
+
+    fn parse(diff: &str) -> Patch {
+        Patch::parse(diff, &[Path::new("/repo")])
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(parse_ranges("-1,5 +1,6 @@"), Some((5, (1, 6))));
+        assert_eq!(parse_ranges("-3 +4 @@ fn foo() {"), Some((1, (4, 1))));
+        assert_eq!(parse_ranges("-0,0 +1,3 @@"), Some((0, (1, 3))));
+        assert_eq!(parse_ranges("-7,2 +6,0 @@"), Some((2, (6, 0))));
+        assert_eq!(parse_ranges("+1,2 -1,2 @@"), None);
+        assert_eq!(parse_ranges("-a,1 +1,1 @@"), None);
+    }
+
+    #[test]
+    fn test_parse_hunks() {
+        let patch = parse(
+            "diff --git a/src/lib.rs b/src/lib.rs
+index 0123456..789abcd 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,3 +2,3 @@ fn foo() {
+ fn foo() {
+-    1
++    2
+ }
+@@ -20,2 +20,3 @@ fn bar() {
+ fn bar() {
++    println!(\"bar\");
+ }
+@@ -30,2 +31,0 @@
+-fn removed() {
+-}
+",
+        );
+        assert_eq!(lines(&patch, "src/lib.rs"), Some(vec![2, 3, 21, 31, 32]));
+    }
+
+    #[test]
+    fn test_parse_new_and_empty_files() {
+        let patch = parse(
+            "diff --git a/src/new.rs b/src/new.rs
+new file mode 100644
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1,2 @@
++fn new() {
++}
+diff --git a/src/empty.rs b/src/empty.rs
+--- a/src/empty.rs
++++ b/src/empty.rs
+@@ -1,1 +0,0 @@
+-fn gone() {}
+",
+        );
+        assert_eq!(lines(&patch, "src/new.rs"), Some(vec![1, 2]));
+        assert_eq!(lines(&patch, "src/empty.rs"), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_parse_whole_files() {
+        let patch = parse(
+            "diff --git a/src/deleted.rs b/src/deleted.rs
+deleted file mode 100644
+--- a/src/deleted.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-fn deleted() {
+-}
+diff --git a/src/old.rs b/src/renamed.rs
+similarity index 100%
+rename from src/old.rs
+rename to src/renamed.rs
+diff --git a/data.bin b/data.bin
+index 0123456..789abcd 100644
+Binary files a/data.bin and b/data.bin differ
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+",
+        );
+        for file in [
+            "src/deleted.rs",
+            "src/old.rs",
+            "src/renamed.rs",
+            "data.bin",
+            "run.sh",
+        ] {
+            assert_eq!(
+                patch.files.get(&Path::new("/repo").join(file)),
+                Some(&FileChange::Whole)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_no_newline_at_end_of_file() {
+        let patch = parse(
+            "--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -4,2 +4,2 @@
+ fn foo() {}
+-fn bar() {}
+\\ No newline at end of file
++fn bar() { baz() }
+\\ No newline at end of file
+",
+        );
+        assert_eq!(lines(&patch, "src/lib.rs"), Some(vec![4, 5]));
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let patch = parse(
+            "--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,4 @@
+ fn foo() {
++    // Only a comment
++
+     1
+@@ -10,3 +11,3 @@
+ const TEXT: &str = \"
+-// Part of a string
++// Part of a changed string
+ \";
+",
+        );
+        assert_eq!(lines(&patch, "src/lib.rs"), Some(vec![2, 3, 11, 12]));
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::read_to_string,
+    path::Path,
+};
+
+use cargo::{
+    core::{
+        compiler::{BuildContext, Unit},
+        Shell,
+    },
+    CargoResult,
+};
+use itertools::Itertools;
+use rustyrts::{
+    constants::{ENV_BLACKBOX_IGNORE_FEATURES, ENV_BLACKBOX_TEST},
+    fs_utils::{write_to_file, CacheFileKind},
+};
+
+use super::general_cache_path;
+use crate::commands::SelectionUnit;
+
+/// The configuration that a test unit has been built with
+///
+/// Checksums of MIR bodies do not reflect changes of the compiler or of its options,
+/// although these may alter the behavior of any function.
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) struct BuildFingerprint {
+    entries: Vec<(String, String)>,
+}
+
+impl BuildFingerprint {
+    pub(crate) fn new(bcx: &BuildContext<'_, '_>, unit: &Unit) -> Self {
+        let rustc = bcx.rustc();
+        let info = bcx.target_data.info(unit.kind);
+
+        let flags = if unit.mode.is_doc_test() {
+            info.rustflags.iter().chain(&info.rustdocflags).join(" ")
+        } else {
+            info.rustflags.join(" ")
+        };
+
+        let ignore_features = std::env::var(ENV_BLACKBOX_TEST).is_ok()
+            && std::env::var(ENV_BLACKBOX_IGNORE_FEATURES).is_ok();
+        let (features, dependency_features) = if ignore_features {
+            (String::new(), String::new())
+        } else {
+            (
+                unit.features.iter().join(","),
+                Self::dependency_features(bcx, unit),
+            )
+        };
+
+        let entries = [
+            (
+                "rustc",
+                rustc
+                    .commit_hash
+                    .clone()
+                    .unwrap_or_else(|| rustc.version.to_string()),
+            ),
+            ("target", bcx.target_data.short_name(&unit.kind).to_string()),
+            ("features", features),
+            ("dependency-features", dependency_features),
+            (
+                "cfgs",
+                bcx.target_data
+                    .cfg(unit.kind)
+                    .iter()
+                    .map(ToString::to_string)
+                    .sorted()
+                    .join(","),
+            ),
+            ("rustflags", flags),
+            ("opt-level", unit.profile.opt_level.to_string()),
+            (
+                "debug-assertions",
+                unit.profile.debug_assertions.to_string(),
+            ),
+            ("overflow-checks", unit.profile.overflow_checks.to_string()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+
+        Self { entries }
+    }
+
+    /// Enabled features of all transitive dependencies, since these may alter the behavior of the unit as well
+    fn dependency_features(bcx: &BuildContext<'_, '_>, unit: &Unit) -> String {
+        let mut visited = HashSet::new();
+        let mut queue = vec![unit];
+        let mut features = BTreeSet::new();
+        while let Some(current) = queue.pop() {
+            for dep in bcx.unit_graph.get(current).into_iter().flatten() {
+                if visited.insert(&dep.unit) {
+                    if !dep.unit.features.is_empty() {
+                        features.insert(format!(
+                            "{} {}",
+                            dep.unit.pkg.name(),
+                            dep.unit.features.iter().join("+")
+                        ));
+                    }
+                    queue.push(&dep.unit);
+                }
+            }
+        }
+        features.into_iter().join(",")
+    }
+
+    /// Reads the fingerprint of the previous run of a test unit
+    pub(crate) fn read(target_dir: &Path, unit: &Unit) -> Option<Self> {
+        let content = read_to_string(general_cache_path(
+            target_dir,
+            unit,
+            CacheFileKind::Fingerprint,
+        ))
+        .ok()?;
+        let entries = content
+            .lines()
+            .filter_map(|l| l.split_once(": "))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Some(Self { entries })
+    }
+
+    pub(crate) fn write(&self, target_dir: &Path, unit: &Unit) {
+        let path = general_cache_path(target_dir, unit, CacheFileKind::Fingerprint);
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create cache directory");
+        let content = self
+            .entries
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}\n"))
+            .collect::<String>();
+        write_to_file(content, path, |_| {}, false);
+    }
+
+    /// Names of the parts of the configuration that differ from `other`
+    fn differences<'a>(&'a self, other: &'a Self) -> Vec<&'a str> {
+        let keys = self
+            .entries
+            .iter()
+            .chain(&other.entries)
+            .map(|(key, _)| key.as_str())
+            .unique();
+        let value = |entries: &[(String, String)], key: &str| {
+            entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+
+        keys.filter(|key| value(&self.entries, key) != value(&other.entries, key))
+            .collect()
+    }
+
+    /// Falls back to retesting all tests if the configuration differs from the one of the previous run
+    ///
+    /// In case there has not been any previous run, `selected` is kept as is.
+    pub(crate) fn check(
+        &self,
+        target_dir: &Path,
+        unit: &Unit,
+        shell: &mut Shell,
+        selected: SelectionUnit,
+    ) -> CargoResult<SelectionUnit> {
+        let Some(previous) = Self::read(target_dir, unit) else {
+            return Ok(selected);
+        };
+
+        let differences = self.differences(&previous);
+        if differences.is_empty() || matches!(selected, SelectionUnit::RetestAll) {
+            return Ok(selected);
+        }
+
+        shell.note(format!(
+            "Build configuration of {} changed ({}), retesting all tests",
+            unit.target.description_named(),
+            differences.join(", ")
+        ))?;
+        Ok(SelectionUnit::RetestAll)
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
+    fs::{read_dir, read_to_string, remove_file},
+    path::Path,
+};
+
+use cargo::{
+    core::{
+        compiler::{Unit, UnitInterner},
+        Workspace,
+    },
+    ops::{create_bcx, CompileOptions},
+    util::human_readable_bytes,
+    CargoResult,
+};
+use rustyrts::{
+    constants::{ENDING_DURATION, ENDING_TRACE},
+    fs_utils::{CacheFileDescr, CacheFileKind, CacheKind},
+};
+
+#[cfg(unix)]
+use rustyrts::constants::ENDING_PROCESS_TRACE;
+
+use super::general_cache_path;
+use crate::commands::DoctestName;
+
+/// The names that the cache files of the current units and tests are referred to by
+///
+/// Files of units are named after the unit, e.g. `Test_lib_foo-0123456789abcdef.graph`,
+/// except for the files of single doctests, e.g. `Doctest_lib_foo_src_lib_rs.checksum`.
+/// Traces are named after the test, e.g. `foo::tests::test_bar.trace`, or after the doctest, e.g. `src_lib_rs.trace`.
+pub(crate) struct LiveNames {
+    /// Stems of the names of the files of units
+    units: HashSet<String>,
+    /// Names of the doctests of a doctest unit, as they appear in the names of files, keyed by the stem of the unit
+    doctests: HashMap<String, Option<HashSet<String>>>,
+}
+
+impl LiveNames {
+    pub(crate) fn new<'a>(units: impl IntoIterator<Item = &'a Unit>) -> Self {
+        let mut live = Self {
+            units: HashSet::new(),
+            doctests: HashMap::new(),
+        };
+        for unit in units {
+            let stem = unit_stem(unit);
+            if unit.mode.is_doc_test() {
+                // Until the doctests are known, the files of all doctests of this unit are kept
+                live.doctests.insert(stem.clone(), None);
+            }
+            live.units.insert(stem);
+        }
+        live
+    }
+
+    /// Records the doctests of a doctest unit, such that the files of doctests that do not exist anymore are removed
+    pub(crate) fn add_doctests<'a>(
+        &mut self,
+        unit: &Unit,
+        doctests: impl IntoIterator<Item = &'a String>,
+    ) {
+        let names = doctests
+            .into_iter()
+            .map(|name| DoctestName::new(name.clone()).cache_name())
+            .collect();
+        self.doctests.insert(unit_stem(unit), Some(names));
+    }
+
+    /// Removes the files of units and tests in `target_dir` that are not referred to anymore,
+    /// e.g. because a test has been removed or the path of a target has changed
+    pub(crate) fn collect_garbage(&self, target_dir: &Path) -> CargoResult<Removed> {
+        let tests = self.tests(target_dir);
+        let all_doctests_known = self.doctests.values().all(Option::is_some);
+        let doctests: HashSet<&str> = self
+            .doctests
+            .values()
+            .flatten()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        let mut removed = Removed::default();
+        for kind in [CacheKind::General, CacheKind::Static, CacheKind::Dynamic] {
+            let Ok(entries) = read_dir(kind.map(target_dir.to_path_buf())) else {
+                continue;
+            };
+
+            for entry in entries {
+                let entry = entry?;
+                let path = entry.path();
+                let (Some(stem), Some(extension)) = (
+                    path.file_stem().and_then(|stem| stem.to_str()),
+                    path.extension().and_then(|ext| ext.to_str()),
+                ) else {
+                    continue;
+                };
+
+                let is_live = match extension {
+                    // These are named after tests or processes and are removed after every run anyway
+                    ENDING_DURATION => true,
+                    #[cfg(unix)]
+                    ENDING_PROCESS_TRACE => true,
+
+                    // Names of unit tests are prefixed by the crate name, whereas names of doctests are not
+                    ENDING_TRACE if stem.contains("::") => tests.contains(stem),
+                    ENDING_TRACE => !all_doctests_known || doctests.contains(stem),
+
+                    _ => match CacheFileDescr::try_from(path.as_path()) {
+                        Ok(descr) if descr.compile_mode.is_some() => self.is_live_unit(stem),
+                        // Files that do not belong to a unit are kept
+                        _ => true,
+                    },
+                };
+
+                if !is_live {
+                    removed.bytes += entry.metadata()?.len();
+                    remove_file(&path)?;
+                    removed.files += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    fn is_live_unit(&self, stem: &str) -> bool {
+        if self.units.contains(stem) {
+            return true;
+        }
+
+        self.doctests.iter().any(|(unit, doctests)| {
+            stem.strip_prefix(unit.as_str())
+                .and_then(|rest| rest.strip_prefix('_'))
+                .is_some_and(|doctest| {
+                    doctests
+                        .as_ref()
+                        .map_or(true, |doctests| doctests.contains(doctest))
+                })
+        })
+    }
+
+    /// Names of the tests of all current units, as they have been found in the previous run
+    fn tests(&self, target_dir: &Path) -> HashSet<String> {
+        let mut tests = HashSet::new();
+        for unit in &self.units {
+            let mut path = CacheKind::General.map(target_dir.to_path_buf());
+            path.push(unit);
+            path.set_extension(CacheFileKind::Tests.as_ref());
+
+            if let Ok(content) = read_to_string(path) {
+                tests.extend(
+                    content
+                        .lines()
+                        .filter(|line| !line.is_empty())
+                        .map(ToString::to_string),
+                );
+            }
+        }
+        tests
+    }
+}
+
+/// The number of files and bytes that have been removed
+#[derive(Default)]
+pub(crate) struct Removed {
+    pub(crate) files: usize,
+    pub(crate) bytes: u64,
+}
+
+impl Display for Removed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.files {
+            1 => write!(f, "1 stale file")?,
+            n => write!(f, "{n} stale files")?,
+        }
+        if self.bytes < 1024 {
+            write!(f, ", {}B total", self.bytes)
+        } else {
+            let (bytes, unit) = human_readable_bytes(self.bytes);
+            write!(f, ", {bytes:.1}{unit} total")
+        }
+    }
+}
+
+/// Stem of the names of the files that belong to a unit
+fn unit_stem(unit: &Unit) -> String {
+    let path = general_cache_path(Path::new(""), unit, CacheFileKind::Tests);
+    path.file_stem().unwrap().to_string_lossy().into_owned()
+}
+
+/// Removes the files of units and tests that are not part of the workspace anymore from all `target_dirs`
+///
+/// Since doctests are not known without compiling them, the files of all doctests of the current crates are kept.
+pub(crate) fn collect_garbage(
+    ws: &Workspace<'_>,
+    options: &CompileOptions,
+    target_dirs: &[&Path],
+) -> CargoResult<Removed> {
+    let interner = UnitInterner::new();
+    let bcx = create_bcx(ws, options, &interner)?;
+    let live = LiveNames::new(bcx.unit_graph.keys());
+
+    let mut removed = Removed::default();
+    for target_dir in target_dirs {
+        let Removed { files, bytes } = live.collect_garbage(target_dir)?;
+        removed.files += files;
+        removed.bytes += bytes;
+    }
+    Ok(removed)
+}
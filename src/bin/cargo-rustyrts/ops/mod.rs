@@ -1,23 +1,73 @@
-pub use cargo_test::run_tests;
+pub use cargo_test::{explain_test, run_tests, ListFormat};
 pub mod cargo_test;
 
-use rustyrts::constants::{
-    ENV_COMPILE_MODE, ENV_DOCTESTED, ENV_SKIP_ANALYSIS, ENV_SKIP_INSTRUMENTATION, ENV_TARGET,
-    ENV_TARGET_DIR, ENV_TARGET_HASH,
+mod baseline;
+mod declared;
+mod deselected;
+mod diff;
+mod fingerprint;
+mod gc;
+pub(crate) mod outcomes;
+mod shard;
+
+pub(crate) use diff::Patch;
+pub(crate) use gc::collect_garbage;
+pub(crate) use shard::{list_tests, Shard};
+
+use rustyrts::{
+    constants::{
+        ENV_COMPILE_MODE, ENV_DOCTESTED, ENV_SKIP_ANALYSIS, ENV_SKIP_INSTRUMENTATION, ENV_TARGET,
+        ENV_TARGET_DIR, ENV_TARGET_HASH,
+    },
+    fs_utils::{CacheFileDescr, CacheFileKind, CacheKind},
 };
 use tracing::debug;
 
-use std::{collections::HashSet, path::PathBuf, sync::Mutex};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
 use cargo::{
     core::{
-        compiler::{DefaultExecutor, Executor},
+        compiler::{DefaultExecutor, Executor, Unit},
         Target,
     },
     CargoResult,
 };
 
-use crate::target_hash::get_target_hash;
+use crate::target_hash::{get_target_hash, get_unit_target_hash};
+
+/// Path of a file in the general cache that belongs to a single test unit
+pub(crate) fn general_cache_path(target_dir: &Path, unit: &Unit, kind: CacheFileKind) -> PathBuf {
+    unit_cache_path(target_dir, unit, CacheKind::General, kind)
+}
+
+/// Path of a file in the given cache that belongs to a single unit
+pub(crate) fn unit_cache_path(
+    target_dir: &Path,
+    unit: &Unit,
+    cache_kind: CacheKind,
+    kind: CacheFileKind,
+) -> PathBuf {
+    let crate_name = if unit.mode.is_doc_test() {
+        unit.target.crate_name()
+    } else {
+        format!(
+            "{}-{}",
+            unit.target.crate_name(),
+            get_unit_target_hash(unit)
+        )
+    };
+    let compile_mode = format!("{:?}", unit.mode);
+    let target = unit.target.kind().description();
+
+    let mut path = cache_kind.map(target_dir.to_path_buf());
+    CacheFileDescr::new(&crate_name, Some(&compile_mode), Some(target), None, kind)
+        .apply(&mut path);
+    path
+}
 
 pub(crate) struct PreciseExecutor {
     cmd: PathBuf,
@@ -55,7 +105,12 @@ impl Executor for PreciseExecutor {
             cmd.program(&self.cmd);
 
             cmd.env(ENV_TARGET_DIR, &self.target_dir);
-            cmd.env(ENV_TARGET_HASH, get_target_hash(target));
+            // Cargo sets the manifest directory to the root of the package that is compiled
+            let package_root = cmd
+                .get_env("CARGO_MANIFEST_DIR")
+                .map(PathBuf::from)
+                .expect("Cargo sets the manifest directory of every compiled package");
+            cmd.env(ENV_TARGET_HASH, get_target_hash(id, &package_root, target));
             cmd.env(ENV_COMPILE_MODE, format!("{mode:?}"));
             cmd.env(ENV_TARGET, format!("{}", target.kind().description()));
             if target.doctested() {
@@ -0,0 +1,361 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    fs::read_to_string,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use cargo::core::compiler::Unit;
+use itertools::Itertools;
+use rustyrts::fs_utils::{write_to_file, CacheFileDescr, CacheFileKind, CacheKind};
+
+use super::general_cache_path;
+use crate::commands::DoctestName;
+
+/// Maximum number of runs of a single test that are kept in the history
+const HISTORY_LENGTH: usize = 20;
+
+/// Outcome of a single test, as reported by libtest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Passed => "passed",
+            Outcome::Failed => "failed",
+            Outcome::Ignored => "ignored",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "passed" => Some(Outcome::Passed),
+            "failed" => Some(Outcome::Failed),
+            "ignored" => Some(Outcome::Ignored),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Outcomes of the tests of a single test unit, as reported by libtest
+///
+/// Names of tests are given in the same form as they appear in `SelectionUnit::Precise`,
+/// i.e. prefixed by the crate name in case of unit tests and trimmed in case of doctests.
+pub(crate) struct TestOutcomes {
+    prefix: Option<String>,
+    outcomes: BTreeMap<String, (Outcome, Option<Duration>)>,
+}
+
+impl TestOutcomes {
+    pub(crate) fn new(unit: &Unit) -> Self {
+        let prefix = (!unit.mode.is_doc_test()).then(|| unit.target.crate_name() + "::");
+        Self {
+            prefix,
+            outcomes: BTreeMap::new(),
+        }
+    }
+
+    /// Parses a single line of output of the test binary
+    ///
+    /// Both the human-readable and the json output format of libtest are supported.
+    /// Durations are only available if libtest has been asked to report them, using `-Z unstable-options --report-time`.
+    pub(crate) fn parse_line(&mut self, line: &str) {
+        let line = strip_ansi_escapes(line);
+
+        let (name, outcome, duration) = if line.starts_with('{') {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(&line) else {
+                return;
+            };
+            if event["type"] != "test" {
+                return;
+            }
+            let Some(name) = event["name"].as_str() else {
+                return;
+            };
+            let outcome = match event["event"].as_str() {
+                Some("ok") => Outcome::Passed,
+                Some("ignored") => Outcome::Ignored,
+                Some("failed") => Outcome::Failed,
+                // `timeout` only warns that a test is running for long, its actual outcome is reported later
+                _ => return,
+            };
+            let duration = event["exec_time"]
+                .as_f64()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+            (name.to_string(), outcome, duration)
+        } else {
+            let Some((name, result)) = line
+                .strip_prefix("test ")
+                .and_then(|l| l.rsplit_once(" ... "))
+            else {
+                return;
+            };
+            let (result, time) = match result.split_once(" <") {
+                Some((result, time)) => (result, time.strip_suffix("s>")),
+                None => (result, None),
+            };
+            let outcome = if result == "ok" {
+                Outcome::Passed
+            } else if result.starts_with("ignored") {
+                Outcome::Ignored
+            } else if result == "FAILED" {
+                Outcome::Failed
+            } else {
+                return;
+            };
+            let duration = time
+                .and_then(|secs| secs.parse().ok())
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+            (name.to_string(), outcome, duration)
+        };
+
+        let name = match &self.prefix {
+            Some(prefix) => prefix.clone() + &name,
+            None if name.contains(" - ") => DoctestName::new(name).trimmed_name(),
+            None => name,
+        };
+
+        self.outcomes.insert(name, (outcome, duration));
+    }
+
+    /// Completes the durations of unit tests with the durations that have been measured while tracing them
+    ///
+    /// In dynamic mode, the instrumented tests report their duration on their own,
+    /// such that it is not necessary to request libtest to report durations.
+    pub(crate) fn collect_reported_durations(&mut self, target_dir: &Path) {
+        if self.prefix.is_none() {
+            return;
+        }
+
+        for (name, (_, duration)) in &mut self.outcomes {
+            let mut path = CacheKind::Dynamic.map(target_dir.to_path_buf());
+            CacheFileDescr::new(name, None, None, None, CacheFileKind::Duration).apply(&mut path);
+
+            let reported = read_to_string(&path)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+            let _ = std::fs::remove_file(&path);
+
+            if duration.is_none() {
+                *duration = reported;
+            }
+        }
+    }
+
+    /// Computes the tests that did not pass
+    ///
+    /// Tests that have been expected to run, but did not report any outcome are considered to have failed.
+    pub(crate) fn not_passed(&self, expected: impl IntoIterator<Item = String>) -> Vec<String> {
+        let failed = self
+            .outcomes
+            .iter()
+            .filter(|(_, (outcome, _))| *outcome == Outcome::Failed)
+            .map(|(name, _)| name.clone());
+        let missing = expected
+            .into_iter()
+            .filter(|t| !self.outcomes.contains_key(t));
+        failed.chain(missing).sorted().dedup().collect()
+    }
+
+    /// Appends the outcomes to the history of the test unit
+    pub(crate) fn record(&self, target_dir: &Path, unit: &Unit) {
+        if self.outcomes.is_empty() {
+            return;
+        }
+
+        let path = general_cache_path(target_dir, unit, CacheFileKind::Outcomes);
+        let mut history = TestHistory::read(&path).unwrap_or_default();
+        history.description = format!(
+            "{:?} {} of package `{}`",
+            unit.mode,
+            unit.target.description_named(),
+            unit.pkg.name()
+        );
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        history
+            .entries
+            .extend(
+                self.outcomes
+                    .iter()
+                    .map(|(name, (outcome, duration))| HistoryEntry {
+                        timestamp,
+                        test: name.clone(),
+                        outcome: *outcome,
+                        duration: *duration,
+                    }),
+            );
+        history.truncate(HISTORY_LENGTH);
+
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create cache directory");
+        write_to_file(history.to_string(), path, |_| {}, false);
+    }
+}
+
+/// A single run of a test
+#[derive(PartialEq)]
+pub(crate) struct HistoryEntry {
+    /// Seconds since the unix epoch
+    pub(crate) timestamp: u64,
+    pub(crate) test: String,
+    pub(crate) outcome: Outcome,
+    pub(crate) duration: Option<Duration>,
+}
+
+/// The outcomes and durations of the latest runs of the tests of a single test unit
+///
+/// The history is stored as one line per run of a test, preceded by a description of the unit:
+/// ```text
+/// # Test lib of package `foo`
+/// 1700000000 passed 0.001234 foo::tests::test_bar
+/// 1700000000 failed - foo::tests::test_baz
+/// ```
+#[derive(Default)]
+pub(crate) struct TestHistory {
+    pub(crate) description: String,
+    pub(crate) entries: Vec<HistoryEntry>,
+}
+
+impl TestHistory {
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        let content = read_to_string(path).ok()?;
+
+        let mut history = Self::default();
+        for line in content.lines() {
+            if let Some(description) = line.strip_prefix("# ") {
+                history.description = description.to_string();
+                continue;
+            }
+
+            let mut parts = line.splitn(4, ' ');
+            let (Some(timestamp), Some(outcome), Some(duration), Some(test)) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let (Ok(timestamp), Some(outcome)) = (timestamp.parse(), Outcome::parse(outcome))
+            else {
+                continue;
+            };
+            let duration = duration
+                .parse()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+
+            history.entries.push(HistoryEntry {
+                timestamp,
+                test: test.to_string(),
+                outcome,
+                duration,
+            });
+        }
+        Some(history)
+    }
+
+    /// Adds the runs recorded in `other`, e.g. by another machine
+    pub(crate) fn merge(&mut self, other: TestHistory) {
+        if self.description.is_empty() {
+            self.description = other.description;
+        }
+        for entry in other.entries {
+            if !self.entries.contains(&entry) {
+                self.entries.push(entry);
+            }
+        }
+        self.entries.sort_by_key(|entry| entry.timestamp);
+        self.truncate(HISTORY_LENGTH);
+    }
+
+    /// Keeps only the latest `length` runs of every test
+    fn truncate(&mut self, length: usize) {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for entry in &self.entries {
+            *counts.entry(entry.test.clone()).or_default() += 1;
+        }
+        self.entries.retain(|entry| {
+            let count = counts.get_mut(&entry.test).unwrap();
+            *count -= 1;
+            *count < length
+        });
+    }
+}
+
+impl Display for TestHistory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# {}", self.description)?;
+        for entry in &self.entries {
+            let duration = entry
+                .duration
+                .map_or("-".to_string(), |d| format!("{:.6}", d.as_secs_f64()));
+            writeln!(
+                f,
+                "{} {} {} {}",
+                entry.timestamp, entry.outcome, duration, entry.test
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_ansi_escapes(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Reads the tests that did not pass in the previous run of a test unit
+///
+/// Returns `None` if the previous run has been successful.
+/// An empty `Vec` indicates that the failure could not be attributed to any specific test.
+pub(crate) fn read_failed_tests(target_dir: &Path, unit: &Unit) -> Option<Vec<String>> {
+    read_to_string(general_cache_path(target_dir, unit, CacheFileKind::Failed))
+        .ok()
+        .map(|s| {
+            s.lines()
+                .filter(|l| !l.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        })
+}
+
+/// Records the tests that did not pass, or removes the record if `failed` is `None`
+pub(crate) fn write_failed_tests(target_dir: &Path, unit: &Unit, failed: Option<Vec<String>>) {
+    let path = general_cache_path(target_dir, unit, CacheFileKind::Failed);
+    match failed {
+        Some(failed) => {
+            std::fs::create_dir_all(path.parent().unwrap())
+                .expect("Failed to create cache directory");
+            let content = failed.into_iter().map(|t| t + "\n").collect::<String>();
+            write_to_file(content, path, |_| {}, false);
+        }
+        None => {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{collections::HashMap, path::Path, str::FromStr, time::Duration};
+
+use anyhow::{bail, Context};
+use cargo::{core::compiler::Unit, util::errors::CargoResult};
+use cargo_util::ProcessBuilder;
+use itertools::Itertools;
+use rustyrts::fs_utils::{fnv1a, CacheFileKind};
+
+use super::{general_cache_path, outcomes::TestHistory};
+use crate::commands::SelectionUnit;
+
+/// A share of the selected tests, when distributing tests over several machines using `--shard <index>/<count>`
+///
+/// The partition only depends on the names of the tests and their recorded durations,
+/// such that all shards agree on it as long as they start off the same cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    /// Starting at 1
+    index: usize,
+    count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .with_context(|| format!("Invalid shard `{s}`, expected `<index>/<count>`"))?;
+        let index: usize = index
+            .parse()
+            .with_context(|| format!("Invalid index of shard `{s}`"))?;
+        let count: usize = count
+            .parse()
+            .with_context(|| format!("Invalid number of shards `{s}`"))?;
+        if count == 0 || index == 0 || index > count {
+            bail!("Invalid shard `{s}`, the index must be between 1 and the number of shards");
+        }
+        Ok(Self { index, count })
+    }
+}
+
+impl Shard {
+    /// Restricts a selection to the tests that belong to this shard
+    ///
+    /// `tests` are all tests of the test unit, in the form in which they appear in `SelectionUnit::Precise`.
+    /// If the durations of all selected tests have been recorded, tests are distributed such that the shards take about the same time,
+    /// otherwise they are distributed according to the hashes of their names.
+    /// If the tests of the unit are not known, the unit is assigned to a single shard as a whole.
+    pub(crate) fn apply<'a>(
+        &self,
+        target_dir: &Path,
+        unit: &Unit,
+        selected: SelectionUnit,
+        tests: impl IntoIterator<Item = &'a str>,
+    ) -> SelectionUnit {
+        let tests: Vec<&str> = tests.into_iter().collect();
+        let selected = match selected {
+            SelectionUnit::Precise(selected) => selected,
+            SelectionUnit::RetestAll
+            | SelectionUnit::CrateLevel {
+                execute_tests: true,
+            } if !tests.is_empty() => tests.iter().map(ToString::to_string).collect(),
+            SelectionUnit::RetestAll
+            | SelectionUnit::CrateLevel {
+                execute_tests: true,
+            } => {
+                // Without knowing the tests, only the unit as a whole can be assigned to a shard
+                let name = format!(
+                    "{} {} {}",
+                    unit.pkg.name(),
+                    unit.target.kind().description(),
+                    unit.target.name()
+                );
+                return if self.is_assigned(&name) {
+                    selected
+                } else {
+                    SelectionUnit::Precise(Vec::new())
+                };
+            }
+            selected => return selected,
+        };
+
+        let durations = average_durations(target_dir, unit);
+
+        let known: Vec<Duration> = selected
+            .iter()
+            .filter_map(|test| durations.get(test))
+            .copied()
+            .collect();
+
+        // Durations are only used if they are complete, since tests without any recorded duration
+        // indicate that the jobs may not start off the same history
+        let retained = if known.len() < selected.len() {
+            selected
+                .into_iter()
+                .filter(|test| self.is_assigned(test))
+                .collect()
+        } else {
+            // Longest processing time first
+            let mut loads = vec![Duration::ZERO; self.count];
+            selected
+                .into_iter()
+                .map(|test| (durations[&test], test))
+                .sorted_by(|(d1, t1), (d2, t2)| d2.cmp(d1).then_with(|| t1.cmp(t2)))
+                .filter(|(duration, _)| {
+                    let (shard, load) = loads
+                        .iter_mut()
+                        .enumerate()
+                        .min_by_key(|(_, load)| **load)
+                        .unwrap();
+                    *load += *duration;
+                    shard == self.index - 1
+                })
+                .map(|(_, test)| test)
+                .collect()
+        };
+
+        SelectionUnit::Precise(retained)
+    }
+
+    /// Whether a test or unit belongs to this shard, according to the hash of its name
+    fn is_assigned(&self, name: &str) -> bool {
+        (fnv1a(name.as_bytes()) % self.count as u64) as usize == self.index - 1
+    }
+}
+
+/// Lists the tests of a test binary that uses libtest, such that they can be distributed over the shards
+/// even if RustyRTS has not recorded them
+pub(crate) fn list_tests(unit: &Unit, mut process: ProcessBuilder) -> CargoResult<Vec<String>> {
+    if !unit.target.harness() {
+        return Ok(Vec::new());
+    }
+
+    process.args(&["--list", "--format", "terse"]);
+    let output = process.exec_with_output()?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Computes the average of the recorded durations of every test of a test unit
+fn average_durations(target_dir: &Path, unit: &Unit) -> HashMap<String, Duration> {
+    let Some(history) = TestHistory::read(&general_cache_path(
+        target_dir,
+        unit,
+        CacheFileKind::Outcomes,
+    )) else {
+        return HashMap::new();
+    };
+
+    let mut durations: HashMap<String, Vec<Duration>> = HashMap::new();
+    for entry in history.entries {
+        if let Some(duration) = entry.duration {
+            durations.entry(entry.test).or_default().push(duration);
+        }
+    }
+    durations
+        .into_iter()
+        .map(|(test, durations)| {
+            let average = durations.iter().sum::<Duration>() / durations.len() as u32;
+            (test, average)
+        })
+        .collect()
+}
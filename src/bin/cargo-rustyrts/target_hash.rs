@@ -1,12 +1,34 @@
-use cargo::core::Target;
-use std::hash::{DefaultHasher, Hash, Hasher};
+use cargo::core::{compiler::Unit, PackageId, Target};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+};
 
-pub(crate) fn get_target_hash(target: &Target) -> String {
+/// Computes a hash that distinguishes targets with the same crate name
+///
+/// Only paths relative to the root of the package, or the file names of sources outside of the package, are considered,
+/// such that the hash does not change when the project is moved to a different directory.
+pub(crate) fn get_target_hash(
+    package_id: PackageId,
+    package_root: &Path,
+    target: &Target,
+) -> String {
     let mut hasher = DefaultHasher::new();
+    package_id.name().as_str().hash(&mut hasher);
+    package_id.version().to_string().hash(&mut hasher);
     target.kind().hash(&mut hasher);
     // target.name().hash(&mut hasher);
     target.binary_filename().hash(&mut hasher);
-    target.src_path().path().hash(&mut hasher);
+    if let Some(src_path) = target.src_path().path() {
+        match src_path.strip_prefix(package_root) {
+            Ok(relative) => relative.hash(&mut hasher),
+            // Sources outside of the package are identified without their location
+            Err(_) => {
+                src_path.file_name().hash(&mut hasher);
+                target.name().hash(&mut hasher);
+            }
+        }
+    }
     // target.tested().hash(&mut hasher);
     // target.benched().hash(&mut hasher);
     // target.documented().hash(&mut hasher);
@@ -18,3 +40,7 @@ pub(crate) fn get_target_hash(target: &Target) -> String {
 
     format!("{:016x}", hasher.finish())
 }
+
+pub(crate) fn get_unit_target_hash(unit: &Unit) -> String {
+    get_target_hash(unit.pkg.package_id(), unit.pkg.root(), &unit.target)
+}
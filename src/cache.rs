@@ -0,0 +1,1178 @@
+//! Read-only access to the metadata that RustyRTS keeps in the target directory
+//!
+//! In contrast to the remaining modules, this module only depends on `std`,
+//! such that other tools like IDE plugins or review bots can query the metadata without `rustc_private`.
+//! The crate `rustyrts-cache` provides the very same module and builds on a stable toolchain.
+//!
+//! ```ignore
+//! use rustyrts::cache::{Cache, Technique};
+//!
+//! let cache = Cache::new("target/static");
+//! for unit in cache.units()? {
+//!     println!("{unit}: {:?}", cache.affected_tests(Technique::Static, &unit)?);
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    fs::{read, read_dir, read_to_string, File},
+    hash::Hash,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    str::Utf8Error,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    constants::PREFIX_FILE,
+    fs_utils::{fnv1a, CacheFileDescr, CacheFileKind, CacheKind, ChecksumKind},
+};
+
+//######################################################################################################################
+// Checksums
+
+/// Wrapper of `HashMap` to provide serialization and deserialization of checksums
+/// (Newtype Pattern)
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Checksums {
+    inner: HashMap<String, HashSet<(u64, u64)>>, // key: name of node - value: checksum(s) of length 128 bit in two u64s
+}
+
+//##### Explanation of why we have multiple checksums per node:
+// There may in fact be multiple bodies with the same def_path and different checksums
+// (def_paths only differ in a disambiguator that is unfortunately NOT stable across compiler sessions)
+// The only solution is to aggregate all available checksums
+
+impl Checksums {
+    pub fn new() -> Self {
+        Checksums {
+            inner: HashMap::new(),
+        }
+    }
+}
+
+impl Default for Checksums {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Checksums {
+    type Target = HashMap<String, HashSet<(u64, u64)>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for Checksums {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+/// Identifies a file containing serialized checksums
+const MAGIC: &[u8; 4] = b"RTSC";
+
+/// Version of the on-disk format, needs to be incremented whenever the format changes
+const FORMAT_VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum ChecksumsError {
+    UnexpectedEof,
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    ConversionError(Utf8Error),
+}
+
+impl Display for ChecksumsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "unexpected end of file"),
+            Self::InvalidMagic => write!(
+                f,
+                "not a checksum file, probably created by an older version"
+            ),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {version}, expected {FORMAT_VERSION}"
+            ),
+            Self::ConversionError(err) => write!(f, "invalid name: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ChecksumsError {}
+
+/// Every serialized set of checksums consists of
+/// 1. a header: magic, format version (`u16`)
+/// 2. the number of entries (`u64`)
+/// 3. the entries: name (string), checksum (two `u64`)
+///
+/// Integers are little-endian, strings are prefixed by their length (`u32`).
+/// Multiple sets of checksums may be concatenated in a single file.
+impl From<&Checksums> for Vec<u8> {
+    fn from(val: &Checksums) -> Self {
+        let mut output = Vec::new();
+
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+        let count: usize = val.inner.values().map(HashSet::len).sum();
+        output.extend_from_slice(&(count as u64).to_le_bytes());
+
+        for (name, checksums) in &val.inner {
+            for (first, second) in checksums {
+                output.extend_from_slice(&u32::try_from(name.len()).unwrap().to_le_bytes());
+                output.extend_from_slice(name.as_bytes());
+                output.extend_from_slice(&first.to_le_bytes());
+                output.extend_from_slice(&second.to_le_bytes());
+            }
+        }
+        output
+    }
+}
+
+impl TryFrom<&[u8]> for Checksums {
+    type Error = ChecksumsError;
+
+    fn try_from(mut value: &[u8]) -> Result<Self, Self::Error> {
+        fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], ChecksumsError> {
+            if input.len() < len {
+                return Err(ChecksumsError::UnexpectedEof);
+            }
+            let (taken, rest) = input.split_at(len);
+            *input = rest;
+            Ok(taken)
+        }
+
+        fn take_u64(input: &mut &[u8]) -> Result<u64, ChecksumsError> {
+            Ok(u64::from_le_bytes(take(input, 8)?.try_into().unwrap()))
+        }
+
+        let mut output = Self::new();
+
+        while !value.is_empty() {
+            if take(&mut value, MAGIC.len())? != MAGIC {
+                return Err(ChecksumsError::InvalidMagic);
+            }
+            let version = u16::from_le_bytes(take(&mut value, 2)?.try_into().unwrap());
+            if version != FORMAT_VERSION {
+                return Err(ChecksumsError::UnsupportedVersion(version));
+            }
+
+            let count = take_u64(&mut value)?;
+            for _ in 0..count {
+                let len = u32::from_le_bytes(take(&mut value, 4)?.try_into().unwrap());
+                let name = std::str::from_utf8(take(&mut value, len as usize)?)
+                    .map_err(ChecksumsError::ConversionError)?;
+                let first = take_u64(&mut value)?;
+                let second = take_u64(&mut value)?;
+
+                insert_hashmap(&mut output.inner, &name.to_string(), (first, second));
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+pub(crate) fn insert_hashmap<K: Hash + Eq + Clone, V: Hash + Eq>(
+    map: &mut HashMap<K, HashSet<V>>,
+    key: &K,
+    value: V,
+) {
+    if map.get(key).is_none() {
+        map.insert(key.clone(), HashSet::new()).unwrap_or_default();
+    }
+    map.get_mut(key).unwrap().insert(value);
+}
+
+//######################################################################################################################
+// Dependency graphs
+
+/// Identifies a file containing a serialized dependency graph
+const GRAPH_MAGIC: &[u8; 4] = b"RTSG";
+
+/// Version of the on-disk format of dependency graphs, needs to be incremented whenever the format changes
+const GRAPH_FORMAT_VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum DeserializationError {
+    UnexpectedEof,
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    ToolchainMismatch { found: String, expected: String },
+    ChecksumMismatch,
+    ConversionError(Utf8Error),
+    IndexOutOfBounds(u64),
+}
+
+impl Display for DeserializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "unexpected end of file"),
+            Self::InvalidMagic => write!(f, "not a dependency graph"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {version}, expected {GRAPH_FORMAT_VERSION}"
+            ),
+            Self::ToolchainMismatch { found, expected } => {
+                write!(f, "created by toolchain {found}, expected {expected}")
+            }
+            Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+            Self::ConversionError(err) => write!(f, "invalid node name: {err}"),
+            Self::IndexOutOfBounds(index) => write!(f, "node index {index} out of bounds"),
+        }
+    }
+}
+
+impl std::error::Error for DeserializationError {}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&u32::try_from(s.len()).unwrap().to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'input> {
+    input: &'input [u8],
+}
+
+impl<'input> Reader<'input> {
+    fn take(&mut self, len: usize) -> Result<&'input [u8], DeserializationError> {
+        if self.input.len() < len {
+            return Err(DeserializationError::UnexpectedEof);
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, DeserializationError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DeserializationError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, DeserializationError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn read_len(&mut self) -> Result<usize, DeserializationError> {
+        usize::try_from(self.read_u64()?).map_err(|_| DeserializationError::UnexpectedEof)
+    }
+
+    fn read_str(&mut self) -> Result<&'input str, DeserializationError> {
+        let len = self.read_u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(DeserializationError::ConversionError)
+    }
+}
+
+/// A dependency graph in the form in which it is stored, where nodes are referred to by their index
+pub(crate) struct RawGraph<'data> {
+    pub(crate) nodes: Vec<&'data str>,
+    /// Index of the end node, together with the index of the start node and the types of all of its ingoing edges
+    pub(crate) edges: Vec<(usize, Vec<(usize, u16)>)>,
+}
+
+impl<'data> RawGraph<'data> {
+    /// Every serialized graph consists of
+    /// 1. a header: magic, format version (`u16`), toolchain (string)
+    /// 2. the length of the payload (`u64`)
+    /// 3. the payload: nodes (`u64` count, followed by strings) and edges
+    ///    (`u64` count of end nodes, each followed by its index (`u64`), the number of ingoing edges (`u64`)
+    ///    and every edge as index of the start node (`u64`) and types (`u16`))
+    /// 4. a checksum of the payload (`u64`)
+    ///
+    /// Integers are little-endian, strings are prefixed by their length (`u32`).
+    /// Multiple graphs may be concatenated in a single file.
+    #[allow(dead_code)] // Graphs are only read by the `rustyrts-cache` crate
+    pub(crate) fn serialize(&self, toolchain: &str) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+
+        // 1. Nodes
+        payload.extend_from_slice(&(self.nodes.len() as u64).to_le_bytes());
+        for node in &self.nodes {
+            write_str(&mut payload, node);
+        }
+
+        // 2. Edges
+        payload.extend_from_slice(&(self.edges.len() as u64).to_le_bytes());
+        for (end, ingoing) in &self.edges {
+            payload.extend_from_slice(&(*end as u64).to_le_bytes());
+            payload.extend_from_slice(&(ingoing.len() as u64).to_le_bytes());
+
+            for (start, types) in ingoing {
+                payload.extend_from_slice(&(*start as u64).to_le_bytes());
+                payload.extend_from_slice(&types.to_le_bytes());
+            }
+        }
+
+        let mut out: Vec<u8> = Vec::new();
+        out.extend_from_slice(GRAPH_MAGIC);
+        out.extend_from_slice(&GRAPH_FORMAT_VERSION.to_le_bytes());
+        write_str(&mut out, toolchain);
+        out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        out.extend_from_slice(&payload);
+        out.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+
+        out
+    }
+
+    /// Reads all graphs that have been concatenated in `input`
+    ///
+    /// If `toolchain` is given, graphs that have been created by another toolchain are rejected.
+    pub(crate) fn deserialize(
+        input: &'data [u8],
+        toolchain: Option<&str>,
+    ) -> Result<Vec<Self>, DeserializationError> {
+        let mut reader = Reader { input };
+        let mut graphs = Vec::new();
+
+        while !reader.input.is_empty() {
+            // 0. Read header
+            if reader.take(GRAPH_MAGIC.len())? != GRAPH_MAGIC {
+                return Err(DeserializationError::InvalidMagic);
+            }
+            let version = reader.read_u16()?;
+            if version != GRAPH_FORMAT_VERSION {
+                return Err(DeserializationError::UnsupportedVersion(version));
+            }
+            let found = reader.read_str()?;
+            if let Some(expected) = toolchain.filter(|expected| *expected != found) {
+                return Err(DeserializationError::ToolchainMismatch {
+                    found: found.to_string(),
+                    expected: expected.to_string(),
+                });
+            }
+
+            let payload_len = reader.read_len()?;
+            let payload = reader.take(payload_len)?;
+            if reader.read_u64()? != fnv1a(payload) {
+                return Err(DeserializationError::ChecksumMismatch);
+            }
+            let mut payload = Reader { input: payload };
+
+            // 1. Read nodes
+            let num_nodes = payload.read_len()?;
+            let mut nodes = Vec::new();
+            for _ in 0..num_nodes {
+                nodes.push(payload.read_str()?);
+            }
+            let index = |index: u64| {
+                usize::try_from(index)
+                    .ok()
+                    .filter(|i| *i < nodes.len())
+                    .ok_or(DeserializationError::IndexOutOfBounds(index))
+            };
+
+            // 2. Read edges
+            let num_ends = payload.read_len()?;
+            let mut edges = Vec::new();
+            for _ in 0..num_ends {
+                let end = index(payload.read_u64()?)?;
+                let num = payload.read_len()?;
+
+                let mut ingoing = Vec::new();
+                for _ in 0..num {
+                    let start = index(payload.read_u64()?)?;
+                    let types = payload.read_u16()?;
+                    ingoing.push((start, types));
+                }
+                edges.push((end, ingoing));
+            }
+
+            graphs.push(Self { nodes, edges });
+        }
+
+        Ok(graphs)
+    }
+}
+
+/// A dependency graph that has been created by static RTS
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Graph {
+    /// For every node, the nodes that depend on it, together with the types of the edges
+    dependents: BTreeMap<String, BTreeMap<String, u16>>,
+}
+
+impl Graph {
+    /// Reads a serialized graph, regardless of the toolchain that it has been created by
+    pub fn deserialize(input: &[u8]) -> Result<Self, DeserializationError> {
+        let mut graph = Self::default();
+        for raw in RawGraph::deserialize(input, None)? {
+            for node in &raw.nodes {
+                graph.dependents.entry(node.to_string()).or_default();
+            }
+            for (end, ingoing) in raw.edges {
+                let dependents = graph
+                    .dependents
+                    .entry(raw.nodes[end].to_string())
+                    .or_default();
+                for (start, types) in ingoing {
+                    *dependents.entry(raw.nodes[start].to_string()).or_default() |= types;
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &str> {
+        self.dependents.keys().map(String::as_str)
+    }
+
+    /// Nodes that depend on `node`, together with the types of the edges as bitmap of `EdgeType`s
+    pub fn dependents<'a>(&'a self, node: &str) -> impl Iterator<Item = (&'a str, u16)> {
+        self.dependents
+            .get(node)
+            .into_iter()
+            .flatten()
+            .map(|(start, types)| (start.as_str(), *types))
+    }
+
+    /// Adds the nodes and edges of another graph, e.g. the graph of a dependency
+    pub fn extend(&mut self, other: Graph) {
+        for (end, ingoing) in other.dependents {
+            let dependents = self.dependents.entry(end).or_default();
+            for (start, types) in ingoing {
+                *dependents.entry(start).or_default() |= types;
+            }
+        }
+    }
+
+    /// Computes all nodes that transitively depend on any of the starting points, including the starting points
+    pub fn reachable_nodes<'a>(
+        &self,
+        starting_points: impl IntoIterator<Item = &'a str>,
+    ) -> BTreeSet<String> {
+        let mut queue: VecDeque<&str> = starting_points.into_iter().collect();
+        let mut reached = BTreeSet::new();
+
+        while let Some(node) = queue.pop_front() {
+            if reached.insert(node.to_string()) {
+                queue.extend(self.dependents(node).map(|(start, _)| start));
+            }
+        }
+
+        reached
+    }
+}
+
+//######################################################################################################################
+// Merged dependency graph of a workspace
+
+/// Identifies a file containing a merged dependency graph
+const WORKSPACE_GRAPH_MAGIC: &[u8; 4] = b"RTSW";
+
+/// Version of the on-disk format of merged dependency graphs, needs to be incremented whenever the format changes
+const WORKSPACE_GRAPH_FORMAT_VERSION: u16 = 2;
+
+/// Identifies the version of a serialized dependency graph by its length, its modification time
+/// and the checksums of its payloads, without reading the payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: u64,
+    checksum: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        // Files that are not valid graphs are rejected when they are read
+        let checksum = payload_checksums(&mut file, metadata.len()).unwrap_or_default();
+        Ok(Self {
+            len: metadata.len(),
+            modified,
+            checksum,
+        })
+    }
+}
+
+/// Combines the checksums of all graphs that have been concatenated in a file, skipping their payloads
+///
+/// The format is described at [`RawGraph::serialize`].
+fn payload_checksums(file: &mut File, len: u64) -> std::io::Result<u64> {
+    let read_u64 = |file: &mut File| {
+        let mut buf = [0; 8];
+        file.read_exact(&mut buf).map(|()| u64::from_le_bytes(buf))
+    };
+    let skip = |file: &mut File, len: u64| {
+        let len = i64::try_from(len).map_err(|_| ErrorKind::InvalidData)?;
+        file.seek(SeekFrom::Current(len))
+    };
+
+    let mut combined = Vec::new();
+    let mut position = 0;
+    while position < len {
+        skip(file, GRAPH_MAGIC.len() as u64 + 2)?;
+        let mut toolchain_len = [0; 4];
+        file.read_exact(&mut toolchain_len)?;
+        skip(file, u64::from(u32::from_le_bytes(toolchain_len)))?;
+        let payload_len = read_u64(file)?;
+        skip(file, payload_len)?;
+        combined.extend_from_slice(&read_u64(file)?.to_le_bytes());
+        position = file.stream_position()?;
+    }
+    Ok(fnv1a(&combined))
+}
+
+/// The edges that the graph of a single unit contributes to a merged graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UnitEdges {
+    /// Version of the file that the edges have been read from
+    stamp: FileStamp,
+    /// Index of the start node, index of the end node and types of the edge
+    edges: Vec<(u32, u32, u16)>,
+}
+
+/// The dependency graphs of all units of a workspace, merged into a single graph whose nodes are referred to by their id
+///
+/// The edges of every unit are kept separately, such that they can be replaced when the unit has been compiled again,
+/// without reading the graphs of all other units.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WorkspaceGraph {
+    nodes: Vec<String>,
+    ids: HashMap<String, u32>,
+    /// Edges of every unit, keyed by the stem of the names of its cache files
+    units: BTreeMap<String, UnitEdges>,
+}
+
+impl WorkspaceGraph {
+    fn id(&mut self, node: &str) -> u32 {
+        if let Some(id) = self.ids.get(node) {
+            return *id;
+        }
+        let id = u32::try_from(self.nodes.len()).expect("Too many nodes in dependency graph");
+        self.nodes.push(node.to_string());
+        self.ids.insert(node.to_string(), id);
+        id
+    }
+
+    /// Checks whether the edges of `unit` have been read from the current version of its graph
+    pub fn is_current(&self, unit: &str, stamp: FileStamp) -> bool {
+        self.units
+            .get(unit)
+            .is_some_and(|edges| edges.stamp == stamp)
+    }
+
+    /// Replaces the edges of `unit` by those of a serialized graph
+    ///
+    /// If `toolchain` is given, graphs that have been created by another toolchain are rejected.
+    pub fn replace(
+        &mut self,
+        unit: &str,
+        stamp: FileStamp,
+        input: &[u8],
+        toolchain: Option<&str>,
+    ) -> Result<(), DeserializationError> {
+        let mut edges = Vec::new();
+        for raw in RawGraph::deserialize(input, toolchain)? {
+            let ids = raw
+                .nodes
+                .iter()
+                .map(|node| self.id(node))
+                .collect::<Vec<_>>();
+            for (end, ingoing) in raw.edges {
+                for (start, types) in ingoing {
+                    edges.push((ids[start], ids[end], types));
+                }
+            }
+        }
+        self.units
+            .insert(unit.to_string(), UnitEdges { stamp, edges });
+        Ok(())
+    }
+
+    /// Removes the edges of `unit`, returns whether it has been part of the graph
+    pub fn remove(&mut self, unit: &str) -> bool {
+        self.units.remove(unit).is_some()
+    }
+
+    /// Keeps only the units for which `f` returns `true`, returns whether any unit has been removed
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) -> bool {
+        let len = self.units.len();
+        self.units.retain(|unit, _| f(unit));
+        self.units.len() != len
+    }
+
+    /// Names of the units whose edges are part of the graph
+    pub fn units(&self) -> impl Iterator<Item = &str> {
+        self.units.keys().map(String::as_str)
+    }
+
+    /// Computes all nodes that transitively depend on any of the starting points, including the starting points,
+    /// considering only the edges of `units` that have any of `edge_types` (a bitmap of `EdgeType`s)
+    pub fn reachable_nodes<'a>(
+        &self,
+        units: impl IntoIterator<Item = &'a str>,
+        starting_points: impl IntoIterator<Item = &'a str>,
+        edge_types: u16,
+    ) -> HashSet<String> {
+        let dependents = self.dependents(units, edge_types);
+
+        let mut reached = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        for node in starting_points {
+            // Starting points that are not part of the graph are reached nevertheless
+            reached.insert(node.to_string());
+            if let Some(id) = self.ids.get(node) {
+                queue.push_back(*id);
+            }
+        }
+
+        while let Some(id) = queue.pop_front() {
+            if visited.insert(id) {
+                reached.insert(self.nodes[id as usize].clone());
+                queue.extend(dependents.get(&id).into_iter().flat_map(HashMap::keys));
+            }
+        }
+
+        reached
+    }
+
+    /// Finds one shortest sequence of nodes leading from any of the starting points to `target`,
+    /// following the same edges as `reachable_nodes()`
+    ///
+    /// The first element of the result is the starting point, every further element is accompanied by
+    /// the types of the edges (restricted to `edge_types`) that lead to it from its predecessor.
+    pub fn shortest_path<'a>(
+        &self,
+        units: impl IntoIterator<Item = &'a str>,
+        starting_points: impl IntoIterator<Item = &'a str>,
+        target: &str,
+        edge_types: u16,
+    ) -> Option<Vec<(String, Option<u16>)>> {
+        let starting_points: Vec<&str> = starting_points.into_iter().collect();
+        // Like in `reachable_nodes()`, starting points that are not part of the graph are reached nevertheless
+        if starting_points.contains(&target) {
+            return Some(vec![(target.to_string(), None)]);
+        }
+
+        let target = *self.ids.get(target)?;
+        let dependents = self.dependents(units, edge_types);
+
+        let mut predecessors: HashMap<u32, Option<u32>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for node in starting_points {
+            if let Some(id) = self.ids.get(node) {
+                if predecessors.insert(*id, None).is_none() {
+                    queue.push_back(*id);
+                }
+            }
+        }
+
+        while let Some(id) = queue.pop_front() {
+            if id == target {
+                let mut path = vec![(self.nodes[id as usize].clone(), None)];
+                let mut current = id;
+                while let Some(Some(predecessor)) = predecessors.get(&current) {
+                    path.last_mut().unwrap().1 = Some(dependents[predecessor][&current]);
+                    path.push((self.nodes[*predecessor as usize].clone(), None));
+                    current = *predecessor;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for next in dependents.get(&id).into_iter().flat_map(HashMap::keys) {
+                predecessors.entry(*next).or_insert_with(|| {
+                    queue.push_back(*next);
+                    Some(id)
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Maps the id of every node to the ids of the nodes that depend on it, together with the types of the edges,
+    /// considering only the edges of `units` that have any of `edge_types`
+    fn dependents<'a>(
+        &self,
+        units: impl IntoIterator<Item = &'a str>,
+        edge_types: u16,
+    ) -> HashMap<u32, HashMap<u32, u16>> {
+        let mut dependents: HashMap<u32, HashMap<u32, u16>> = HashMap::new();
+        for unit in units {
+            for (start, end, types) in self.units.get(unit).map_or(&[][..], |edges| &edges.edges) {
+                if types & edge_types != 0 {
+                    *dependents
+                        .entry(*end)
+                        .or_default()
+                        .entry(*start)
+                        .or_default() |= types & edge_types;
+                }
+            }
+        }
+        dependents
+    }
+
+    /// Every merged graph consists of
+    /// 1. a header: magic, format version (`u16`)
+    /// 2. the length of the payload (`u64`)
+    /// 3. the payload: nodes (`u64` count, followed by strings) and units
+    ///    (`u64` count, each followed by its name (string), the length, modification time and checksum of its graph (three `u64`),
+    ///    the number of edges (`u64`) and every edge as ids of the start and end node (two `u32`) and types (`u16`))
+    /// 4. a checksum of the payload (`u64`)
+    ///
+    /// Integers are little-endian, strings are prefixed by their length (`u32`).
+    /// Nodes that are not referred to by any edge anymore are omitted.
+    pub fn serialize(&self) -> Vec<u8> {
+        // Nodes of units that have been replaced or removed are dropped, the remaining ones are renumbered
+        let mut ids: HashMap<u32, u32> = HashMap::new();
+        let mut nodes = Vec::new();
+        for (start, end, _) in self.units.values().flat_map(|unit| &unit.edges) {
+            for id in [start, end] {
+                ids.entry(*id).or_insert_with(|| {
+                    nodes.push(self.nodes[*id as usize].as_str());
+                    (nodes.len() - 1) as u32
+                });
+            }
+        }
+
+        let mut payload: Vec<u8> = Vec::new();
+
+        // 1. Nodes
+        payload.extend_from_slice(&(nodes.len() as u64).to_le_bytes());
+        for node in nodes {
+            write_str(&mut payload, node);
+        }
+
+        // 2. Units
+        payload.extend_from_slice(&(self.units.len() as u64).to_le_bytes());
+        for (name, unit) in &self.units {
+            write_str(&mut payload, name);
+            payload.extend_from_slice(&unit.stamp.len.to_le_bytes());
+            payload.extend_from_slice(&unit.stamp.modified.to_le_bytes());
+            payload.extend_from_slice(&unit.stamp.checksum.to_le_bytes());
+            payload.extend_from_slice(&(unit.edges.len() as u64).to_le_bytes());
+            for (start, end, types) in &unit.edges {
+                payload.extend_from_slice(&ids[start].to_le_bytes());
+                payload.extend_from_slice(&ids[end].to_le_bytes());
+                payload.extend_from_slice(&types.to_le_bytes());
+            }
+        }
+
+        let mut out: Vec<u8> = Vec::new();
+        out.extend_from_slice(WORKSPACE_GRAPH_MAGIC);
+        out.extend_from_slice(&WORKSPACE_GRAPH_FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        out.extend_from_slice(&payload);
+        out.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+
+        out
+    }
+
+    /// Reads a merged graph, the format is described at [`WorkspaceGraph::serialize`]
+    pub fn deserialize(input: &[u8]) -> Result<Self, DeserializationError> {
+        let mut reader = Reader { input };
+
+        // 0. Read header
+        if reader.take(WORKSPACE_GRAPH_MAGIC.len())? != WORKSPACE_GRAPH_MAGIC {
+            return Err(DeserializationError::InvalidMagic);
+        }
+        let version = reader.read_u16()?;
+        if version != WORKSPACE_GRAPH_FORMAT_VERSION {
+            return Err(DeserializationError::UnsupportedVersion(version));
+        }
+        let payload_len = reader.read_len()?;
+        let payload = reader.take(payload_len)?;
+        if reader.read_u64()? != fnv1a(payload) {
+            return Err(DeserializationError::ChecksumMismatch);
+        }
+        let mut payload = Reader { input: payload };
+
+        // 1. Read nodes
+        let mut graph = Self::default();
+        let num_nodes = payload.read_len()?;
+        for _ in 0..num_nodes {
+            let node = payload.read_str()?;
+            graph.id(node);
+        }
+        let num_nodes = graph.nodes.len() as u32;
+        let id = |id: u32| {
+            Some(id)
+                .filter(|id| *id < num_nodes)
+                .ok_or(DeserializationError::IndexOutOfBounds(u64::from(id)))
+        };
+
+        // 2. Read units
+        let num_units = payload.read_len()?;
+        for _ in 0..num_units {
+            let name = payload.read_str()?.to_string();
+            let stamp = FileStamp {
+                len: payload.read_u64()?,
+                modified: payload.read_u64()?,
+                checksum: payload.read_u64()?,
+            };
+            let num_edges = payload.read_len()?;
+            let mut edges = Vec::new();
+            for _ in 0..num_edges {
+                let start = id(payload.read_u32()?)?;
+                let end = id(payload.read_u32()?)?;
+                let types = payload.read_u16()?;
+                edges.push((start, end, types));
+            }
+            graph.units.insert(name, UnitEdges { stamp, edges });
+        }
+
+        Ok(graph)
+    }
+}
+
+//######################################################################################################################
+// Locations of functions
+
+/// Lines of a file that the body of a function spans
+///
+/// Files are named as passed to rustc, which is relative to the root of the workspace for its members.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Encodes the span of a function as a single line of a `.spans` file
+    pub fn encode(&self, name: &str) -> String {
+        format!("{name}\t{}\t{}\t{}", self.file, self.start, self.end)
+    }
+
+    /// Decodes a single line of a `.spans` file into the name of a function and its span
+    pub fn decode(line: &str) -> Option<(&str, Self)> {
+        let mut parts = line.rsplitn(4, '\t');
+        let end = parts.next()?.parse().ok()?;
+        let start = parts.next()?.parse().ok()?;
+        let file = parts.next()?.to_string();
+        let name = parts.next()?;
+        Some((name, Self { file, start, end }))
+    }
+
+    /// Whether `line` of `file` lies within this span
+    ///
+    /// Files match if one of them is a suffix of the other one, e.g. `src/lib.rs` and `foo/src/lib.rs`.
+    pub fn contains(&self, file: &str, line: usize) -> bool {
+        let same_file =
+            Path::new(&self.file).ends_with(file) || Path::new(file).ends_with(&self.file);
+        same_file && self.start <= line && line <= self.end
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.start)
+    }
+}
+
+//######################################################################################################################
+// Querying the cache
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(PathBuf, std::io::Error),
+    Checksums(PathBuf, ChecksumsError),
+    Graph(PathBuf, DeserializationError),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            Self::Checksums(path, err) => write!(f, "{}: {err}", path.display()),
+            Self::Graph(path, err) => write!(f, "{}: {err}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// The technique of regression test selection, each of which keeps its own changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Technique {
+    Static,
+    Dynamic,
+}
+
+impl Technique {
+    fn cache_kind(self) -> CacheKind {
+        match self {
+            Technique::Static => CacheKind::Static,
+            Technique::Dynamic => CacheKind::Dynamic,
+        }
+    }
+}
+
+/// A unit whose tests have been executed,
+/// identified by the stem of the names of its cache files, e.g. `Test_lib_foo-0123456789abcdef`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CachedUnit {
+    name: String,
+}
+
+impl CachedUnit {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The compile mode of the unit, e.g. `Test`
+    pub fn compile_mode(&self) -> &str {
+        self.name.split('_').next().unwrap_or_default()
+    }
+
+    /// The kind of target of the unit, e.g. `lib` or `integration-test`
+    pub fn target(&self) -> &str {
+        self.name.split('_').nth(1).unwrap_or_default()
+    }
+
+    /// The name of the crate, without the hash that distinguishes targets of the same name
+    pub fn crate_name(&self) -> &str {
+        let crate_name = self.name.splitn(3, '_').nth(2).unwrap_or_default();
+        crate_name
+            .rsplit_once('-')
+            .map_or(crate_name, |(crate_name, _)| crate_name)
+    }
+}
+
+impl Display for CachedUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Tests that would be selected, if tests were executed now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Affected {
+    All,
+    Tests(BTreeSet<String>),
+}
+
+/// The metadata of RustyRTS in a single target directory
+pub struct Cache {
+    target_dir: PathBuf,
+}
+
+impl Cache {
+    /// `target_dir` is the target directory that RustyRTS has been using,
+    /// i.e. `target/static` or `target/dynamic` unless the target directory has been set explicitly
+    pub fn new(target_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            target_dir: target_dir.into(),
+        }
+    }
+
+    fn path(&self, kind: CacheKind, name: &str, file_kind: CacheFileKind) -> PathBuf {
+        let mut path = kind.map(self.target_dir.clone());
+        CacheFileDescr::new(name, None, None, None, file_kind).apply(&mut path);
+        path
+    }
+
+    /// Units whose tests have been executed, sorted by their names
+    pub fn units(&self) -> Result<Vec<CachedUnit>, CacheError> {
+        self.units_with(CacheKind::General, CacheFileKind::Tests)
+    }
+
+    /// Units that have a dependency graph, including units that do not contain tests, sorted by their names
+    pub fn graph_units(&self) -> Result<Vec<CachedUnit>, CacheError> {
+        self.units_with(CacheKind::Static, CacheFileKind::Graph)
+    }
+
+    /// Locations of the functions of all units, as of their latest compilation
+    pub fn spans(&self) -> Result<HashMap<String, Span>, CacheError> {
+        let mut spans = HashMap::new();
+        for unit in self.units_with(CacheKind::General, CacheFileKind::Spans)? {
+            let path = self.path(CacheKind::General, unit.name(), CacheFileKind::Spans);
+            for line in read_lines(&path)? {
+                if let Some((name, span)) = Span::decode(&line) {
+                    spans.insert(name.to_string(), span);
+                }
+            }
+        }
+        Ok(spans)
+    }
+
+    /// Tests of a unit, named as in the traces and graphs, e.g. `foo::tests::test_bar`
+    pub fn tests(&self, unit: &CachedUnit) -> Result<Vec<String>, CacheError> {
+        read_lines(&self.path(CacheKind::General, unit.name(), CacheFileKind::Tests))
+    }
+
+    /// Tests of a unit that did not pass in the previous run, and are therefore selected again
+    pub fn failed_tests(&self, unit: &CachedUnit) -> Result<Vec<String>, CacheError> {
+        read_lines(&self.path(CacheKind::General, unit.name(), CacheFileKind::Failed))
+    }
+
+    /// Nodes of a unit that have changed since the tests have been executed for the last time
+    ///
+    /// Changes are only present after the unit has been compiled, but before tests have been executed.
+    pub fn changes(
+        &self,
+        technique: Technique,
+        unit: &CachedUnit,
+    ) -> Result<BTreeSet<String>, CacheError> {
+        let path = self.path(technique.cache_kind(), unit.name(), CacheFileKind::Changes);
+        Ok(read_lines(&path)?.into_iter().collect())
+    }
+
+    /// The dependency graph of a unit, as created by static RTS
+    pub fn graph(&self, unit: &CachedUnit) -> Result<Graph, CacheError> {
+        let path = self.path(CacheKind::Static, unit.name(), CacheFileKind::Graph);
+        match read(&path) {
+            Ok(content) => Graph::deserialize(&content).map_err(|err| CacheError::Graph(path, err)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Graph::default()),
+            Err(err) => Err(CacheError::Io(path, err)),
+        }
+    }
+
+    /// Checksums of the nodes of a unit, as of the latest compilation
+    pub fn checksums(
+        &self,
+        unit: &CachedUnit,
+        kind: ChecksumKind,
+    ) -> Result<Checksums, CacheError> {
+        let path = self.path(
+            CacheKind::General,
+            unit.name(),
+            CacheFileKind::Checksums(kind),
+        );
+        match read(&path) {
+            Ok(content) => Checksums::try_from(content.as_slice())
+                .map_err(|err| CacheError::Checksums(path, err)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Checksums::new()),
+            Err(err) => Err(CacheError::Io(path, err)),
+        }
+    }
+
+    /// Nodes that have been traced while executing a test in dynamic mode, or `None` if the test has not been traced
+    ///
+    /// Besides names of nodes, traces contain the files and environment variables that a test has accessed.
+    pub fn trace(&self, test: &str) -> Result<Option<BTreeSet<String>>, CacheError> {
+        let path = self.path(CacheKind::Dynamic, test, CacheFileKind::Traces);
+        match read_to_string(&path) {
+            Ok(content) => Ok(Some(content.lines().map(ToString::to_string).collect())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(CacheError::Io(path, err)),
+        }
+    }
+
+    /// Computes the tests of a unit that are affected by the changes that are currently present
+    ///
+    /// Since the dependencies between units are not known here, the changes and graphs of all units are considered.
+    /// Thus, the result may comprise more tests than the selection of RustyRTS, but never fewer.
+    /// If a file that has been declared by the unit itself has changed, all of its tests are affected.
+    /// A changed file of another unit is considered as a change of every node of that unit instead.
+    /// Changes of the build configuration and of files that tests access at runtime are not considered.
+    pub fn affected_tests(
+        &self,
+        technique: Technique,
+        unit: &CachedUnit,
+    ) -> Result<Affected, CacheError> {
+        let is_file = |node: &String| node.starts_with(PREFIX_FILE);
+
+        if self.changes(technique, unit)?.iter().any(is_file) {
+            return Ok(Affected::All);
+        }
+
+        let mut changes = BTreeSet::new();
+        for other in self.units_with(technique.cache_kind(), CacheFileKind::Changes)? {
+            let (files, nodes): (Vec<_>, Vec<_>) = self
+                .changes(technique, &other)?
+                .into_iter()
+                .partition(is_file);
+            changes.extend(nodes);
+            if !files.is_empty() {
+                changes.extend(
+                    self.checksums(&other, ChecksumKind::Checksum)?
+                        .keys()
+                        .cloned(),
+                );
+            }
+        }
+
+        let tests = self.tests(unit)?;
+        let mut affected: BTreeSet<String> = self.failed_tests(unit)?.into_iter().collect();
+
+        match technique {
+            Technique::Static => {
+                let mut graph = Graph::default();
+                for other in self.units_with(CacheKind::Static, CacheFileKind::Graph)? {
+                    graph.extend(self.graph(&other)?);
+                }
+                let reached = graph.reachable_nodes(changes.iter().map(String::as_str));
+                affected.extend(tests.into_iter().filter(|test| reached.contains(test)));
+            }
+            Technique::Dynamic => {
+                for test in tests {
+                    // Tests that have not been traced yet are always affected
+                    let is_affected = match self.trace(&test)? {
+                        Some(trace) => !trace.is_disjoint(&changes),
+                        None => true,
+                    };
+                    if is_affected {
+                        affected.insert(test);
+                    }
+                }
+            }
+        }
+
+        Ok(Affected::Tests(affected))
+    }
+
+    /// All units that have a cache file of a specific kind, sorted by their names
+    fn units_with(
+        &self,
+        kind: CacheKind,
+        file_kind: CacheFileKind,
+    ) -> Result<Vec<CachedUnit>, CacheError> {
+        let dir = kind.map(self.target_dir.clone());
+        let entries = match read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(CacheError::Io(dir, err)),
+        };
+
+        let mut units = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|err| CacheError::Io(dir.clone(), err))?
+                .path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == file_kind.as_ref())
+            {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    units.push(CachedUnit::new(name));
+                }
+            }
+        }
+        units.sort();
+        Ok(units)
+    }
+}
+
+/// Reads the non-empty lines of a file, a missing file is considered to be empty
+fn read_lines(path: &Path) -> Result<Vec<String>, CacheError> {
+    match read_to_string(path) {
+        Ok(content) => Ok(content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(ToString::to_string)
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(CacheError::Io(path.to_path_buf(), err)),
+    }
+}
@@ -10,19 +10,21 @@ use rustc_middle::{
 };
 use rustc_span::{def_id::DefId, sym, Symbol};
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fs::read,
     string::ToString,
     sync::{atomic::AtomicUsize, Mutex},
 };
 use std::{env, fs::remove_file};
 use std::{mem::transmute, path::Path};
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use crate::{
-    checksums::{get_checksum_body, insert_hashmap},
+    cache::Span,
+    checksums::{get_checksum_body, get_checksum_file, insert_hashmap},
     const_visitor::ResolvingConstVisitor,
-    constants::{ENV_TARGET, ENV_TARGET_HASH},
+    constants::{ENV_TARGET, ENV_TARGET_HASH, PREFIX_FILE},
+    files::{body_span, dep_info_files, package_relative, IncludedFilesVisitor},
     fs_utils::append_to_file,
 };
 use crate::{
@@ -32,7 +34,7 @@ use crate::{
     names::def_id_name,
 };
 use crate::{
-    constants::{ENV_COMPILE_MODE, ENV_SKIP_ANALYSIS},
+    constants::{ENV_COMPILE_MODE, ENV_DRY_RUN, ENV_SKIP_ANALYSIS},
     fs_utils::CacheFileDescr,
 };
 
@@ -207,6 +209,8 @@ pub trait AnalysisCallback<'tcx>: ChecksumsCallback {
             ..
         } = self.context_mut();
 
+        let mut spans = BTreeMap::new();
+
         {
             let _prof_timer = tcx.prof.generic_activity("RUSTYRTS_checksum_collection");
 
@@ -232,9 +236,15 @@ pub trait AnalysisCallback<'tcx>: ChecksumsCallback {
             //##########################################################################################################
             // Calculate checksum of every MIR body and the consts that it uses
 
+            let mut attributed_files = HashSet::new();
+
             for body in &bodies {
                 let name = def_id_name(tcx, body.source.def_id(), false, true);
 
+                if let Some(span) = body_span(tcx, body) {
+                    spans.entry(name.clone()).or_insert(span);
+                }
+
                 let checksums_const = ResolvingConstVisitor::find_consts(tcx, body);
                 for checksum in checksums_const {
                     insert_hashmap(
@@ -246,8 +256,67 @@ pub trait AnalysisCallback<'tcx>: ChecksumsCallback {
 
                 let checksum = get_checksum_body(tcx, body);
                 insert_hashmap(&mut *new_checksums.get_mut().unwrap(), &name, checksum);
+
+                // Files that are embedded into a function are considered to be part of it
+                for file in IncludedFilesVisitor::find_files(tcx, body) {
+                    insert_hashmap(
+                        &mut *new_checksums.get_mut().unwrap(),
+                        &name,
+                        get_checksum_file(&file),
+                    );
+                    attributed_files.insert(package_relative(&file));
+                }
+            }
+
+            //##########################################################################################################
+            // Calculate checksum of every other file that the crate depends on
+
+            for file in dep_info_files(tcx) {
+                let relative = package_relative(&file);
+                if !attributed_files.contains(&relative) {
+                    let name = format!("{PREFIX_FILE}{}", relative.display());
+                    insert_hashmap(
+                        &mut *new_checksums.get_mut().unwrap(),
+                        &name,
+                        get_checksum_file(&file),
+                    );
+                }
             }
         }
+
+        if self.context().compile_mode != CompileMode::Doctest {
+            self.export_spans(&spans);
+        }
+    }
+
+    /// Stores the location of every function whose checksum has been calculated,
+    /// such that changed functions can be traced back to the source code
+    fn export_spans(&self, spans: &BTreeMap<String, Span>) {
+        let RTSContext {
+            crate_name,
+            compile_mode,
+            target,
+            ..
+        } = self.context();
+
+        write_to_file(
+            spans
+                .iter()
+                .map(|(name, span)| span.encode(name) + "\n")
+                .collect::<String>(),
+            CacheKind::General.map(self.path().to_path_buf()),
+            |buf| {
+                CacheFileDescr::new(
+                    crate_name,
+                    Some(compile_mode.as_ref()),
+                    Some(target.as_ref()),
+                    None,
+                    CacheFileKind::Spans,
+                )
+                .apply(buf);
+            },
+            false,
+        );
     }
 
     fn run_analysis_tests(&self, tcx: TyCtxt<'tcx>) {
@@ -373,10 +442,19 @@ pub trait ChecksumsCallback {
             let maybe_checksums = read(checksums_path_buf.as_path());
 
             if let Ok(checksums) = maybe_checksums {
-                if remove {
+                if remove && env::var(ENV_DRY_RUN).is_err() {
                     remove_file(checksums_path_buf.as_path()).unwrap();
                 }
-                Checksums::from(checksums.as_slice())
+                Checksums::try_from(checksums.as_slice()).unwrap_or_else(|err| {
+                    // Without valid checksums, everything is considered to be changed
+                    warn!(
+                        "Discarding {:?} at {}: {}",
+                        kind,
+                        checksums_path_buf.display(),
+                        err
+                    );
+                    Checksums::new()
+                })
             } else {
                 Checksums::new()
             }
@@ -439,6 +517,11 @@ pub trait ChecksumsCallback {
     }
 
     fn export_checksums(&self, kind: ChecksumKind, checksums: &Checksums, append: bool) {
+        if env::var(ENV_DRY_RUN).is_ok() {
+            // The changes are kept until the tests are executed, so are the checksums they are relative to
+            return;
+        }
+
         let path = CacheKind::General.map(self.path().to_path_buf());
         let RTSContext {
             crate_name,
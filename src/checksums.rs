@@ -1,106 +1,14 @@
 use crate::rustc_data_structures::stable_hasher::HashStable;
-use lazy_static::lazy_static;
-use regex::bytes::Regex;
 use rustc_data_structures::stable_hasher::StableHasher;
 use rustc_middle::mir::interpret::ConstAllocation;
 use rustc_middle::mir::Body;
 use rustc_middle::ty::{ScalarInt, TyCtxt, VtblEntry};
 use rustc_span::sym;
-use std::hash::Hash;
-use std::ops::{Deref, DerefMut};
-use std::{
-    collections::{HashMap, HashSet},
-    hash::Hasher,
-};
-
-/// Wrapper of `HashMap` to provide serialization and deserialization of checksums
-/// (Newtype Pattern)
-#[derive(Eq, PartialEq, Debug, Clone)]
-pub struct Checksums {
-    inner: HashMap<String, HashSet<(u64, u64)>>, // key: name of node - value: checksum(s) of length 128 bit in two u64s
-}
-
-//##### Explanation of why we have multiple checksums per node:
-// There may in fact be multiple bodies with the same def_path and different checksums
-// (def_paths only differ in a disambiguator that is unfortunately NOT stable across compiler sessions)
-// The only solution is to aggregate all available checksums
-
-impl Checksums {
-    pub fn new() -> Self {
-        Checksums {
-            inner: HashMap::new(),
-        }
-    }
-}
-
-impl Default for Checksums {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl Deref for Checksums {
-    type Target = HashMap<String, HashSet<(u64, u64)>>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.inner
-    }
-}
+use std::hash::Hasher;
+use std::path::Path;
 
-impl DerefMut for Checksums {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner
-    }
-}
-
-impl From<&Checksums> for Vec<u8> {
-    fn from(val: &Checksums) -> Self {
-        let mut output = Vec::new();
-
-        for (name, checksums) in &val.inner {
-            for (first, second) in checksums {
-                output.extend(name.as_bytes());
-                output.extend(" - ".as_bytes());
-                output.extend(Vec::from(first.to_ne_bytes()));
-                output.extend(Vec::from(second.to_ne_bytes()));
-                output.push(b'\n');
-            }
-        }
-        output
-    }
-}
-
-impl From<&[u8]> for Checksums {
-    fn from(value: &[u8]) -> Self {
-        let mut output = Self::new();
-
-        lazy_static! {
-            static ref REGEX_LINE: Regex = Regex::new(r"(?-u).* - [\s\S]{16}\n").unwrap();
-        }
-
-        REGEX_LINE
-            .find_iter(value)
-            .map(|m| m.as_bytes())
-            .map(|line| {
-                // spilt into name and checksum
-                (
-                    line.get(..line.len() - 20).unwrap(), // name ends 20 byte before end of line
-                    line.get(line.len() - 17..line.len() - 1).unwrap(), // checksums begin 17 bytes before end + removing \n at the end
-                )
-            })
-            .map(|(name, checksum)| (std::str::from_utf8(name).unwrap(), checksum.split_at(8))) // parse name and split checksum
-            .map(|(name, (first_str, second_str))| {
-                // Parse checksums from two [u8,8] in two u64s
-                let first = u64::from_ne_bytes(first_str.try_into().unwrap());
-                let second = u64::from_ne_bytes(second_str.try_into().unwrap());
-                (name, (first, second))
-            })
-            .for_each(|(name, checksum)| {
-                insert_hashmap(&mut output.inner, &name.to_string(), checksum);
-            });
-        output
-    }
-}
+pub(crate) use crate::cache::insert_hashmap;
+pub use crate::cache::{Checksums, ChecksumsError};
 
 /// Function to obtain a stable checksum of a MIR body
 pub(crate) fn get_checksum_body(tcx: TyCtxt<'_>, body: &Body) -> (u64, u64) {
@@ -131,6 +39,21 @@ pub(crate) fn get_checksum_body(tcx: TyCtxt<'_>, body: &Body) -> (u64, u64) {
     hash
 }
 
+/// Function to obtain a checksum of the content of a file
+///
+/// Files that cannot be read obtain a distinct checksum, such that deleting a file is recognized as a change.
+pub(crate) fn get_checksum_file(path: &Path) -> (u64, u64) {
+    let mut hasher = StableHasher::new();
+    match std::fs::read(path) {
+        Ok(content) => {
+            hasher.write_u8(1);
+            hasher.write(&content);
+        }
+        Err(_) => hasher.write_u8(0),
+    }
+    hasher.finalize()
+}
+
 /// Function to obtain a stable checksum of a vtable entry
 pub(crate) fn get_checksum_vtbl_entry<'tcx>(
     tcx: TyCtxt<'tcx>,
@@ -197,21 +120,10 @@ pub(crate) fn get_checksum_scalar_int(tcx: TyCtxt<'_>, scalar_int: &ScalarInt) -
     hash
 }
 
-pub(crate) fn insert_hashmap<K: Hash + Eq + Clone, V: Hash + Eq>(
-    map: &mut HashMap<K, HashSet<V>>,
-    key: &K,
-    value: V,
-) {
-    if map.get(key).is_none() {
-        map.insert(key.clone(), HashSet::new()).unwrap_or_default();
-    }
-    map.get_mut(key).unwrap().insert(value);
-}
-
 #[cfg(test)]
 mod test {
 
-    use super::Checksums;
+    use super::{Checksums, ChecksumsError};
     use crate::checksums::insert_hashmap;
 
     #[test]
@@ -242,7 +154,7 @@ mod test {
         insert_hashmap(&mut checksums, &"node6".to_string(), (6, u64::MAX));
 
         let serialized: Vec<u8> = (&checksums).into();
-        let deserialized = Checksums::from(serialized.as_slice());
+        let deserialized = Checksums::try_from(serialized.as_slice()).unwrap();
 
         assert_eq!(checksums, deserialized);
     }
@@ -252,8 +164,41 @@ mod test {
         let checksums = Checksums::new();
 
         let serialized: Vec<u8> = (&checksums).into();
-        let deserialized = Checksums::from(serialized.as_slice());
+        let deserialized = Checksums::try_from(serialized.as_slice()).unwrap();
 
         assert_eq!(checksums, deserialized);
     }
+
+    #[test]
+    pub fn test_checksum_deserialization_special_characters() {
+        let mut checksums = Checksums::new();
+
+        insert_hashmap(&mut checksums, &"a - b\nc".to_string(), (10, 0x0a0a_0a0a));
+        insert_hashmap(&mut checksums, &"a - b\nc".to_string(), (11, 0x0a0a_0a0a));
+        insert_hashmap(&mut checksums, &String::new(), (u64::MAX, 0));
+
+        let mut serialized: Vec<u8> = (&checksums).into();
+        serialized.extend(Into::<Vec<u8>>::into(&checksums));
+        let deserialized = Checksums::try_from(serialized.as_slice()).unwrap();
+
+        assert_eq!(checksums, deserialized);
+    }
+
+    #[test]
+    pub fn test_checksum_deserialization_errors() {
+        let mut checksums = Checksums::new();
+        insert_hashmap(&mut checksums, &"node".to_string(), (1, 2));
+
+        let serialized: Vec<u8> = (&checksums).into();
+        assert_eq!(
+            Checksums::try_from(&serialized[..serialized.len() - 1]),
+            Err(ChecksumsError::UnexpectedEof)
+        );
+
+        let old_format = b"node - 1234567890123456\n";
+        assert_eq!(
+            Checksums::try_from(old_format.as_slice()),
+            Err(ChecksumsError::InvalidMagic)
+        );
+    }
 }
@@ -24,18 +24,29 @@ pub const ENV_SKIP_ANALYSIS: &str = "RUSTYRTS_SKIP_ANALYSIS";
 pub const ENV_SKIP_INSTRUMENTATION: &str = "RUSTYRTS_SKIP_INSTRUMENTATION";
 
 pub const ENV_BLACKBOX_TEST: &str = "RUSTYRTS_BLACKBOX_TEST";
+/// Only honored in blackbox tests, whose fixtures introduce changes by enabling features,
+/// to exclude enabled features from the build configuration that is compared to the previous run
+pub const ENV_BLACKBOX_IGNORE_FEATURES: &str = "RUSTYRTS_BLACKBOX_IGNORE_FEATURES";
 
 /// Indicates whether the crate that is currently compiled is doctested
 pub const ENV_DOCTESTED: &str = "RUSTYRTS_DOCTESTED";
 
+/// Is set while selected tests are only listed, such that checksums are not updated
+pub const ENV_DRY_RUN: &str = "RUSTYRTS_DRY_RUN";
+
 //######################################################################################################################
 // File endings or names
 
 pub const DIR_STATIC: &str = ".rts_static";
 pub const DIR_DYNAMIC: &str = ".rts_dynamic";
 pub const DIR_GENERAL: &str = ".rts";
+pub const DIR_SNAPSHOTS: &str = ".rts_snapshots";
 
 pub const FILE_COMPLETE_GRAPH: &str = "!complete_graph.dot";
+/// Merged dependency graph of all units of the workspace, updated by static RTS
+pub const FILE_WORKSPACE_GRAPH: &str = "!workspace_graph";
+/// Marks that units have been compiled while only listing selected tests, without updating their checksums
+pub const FILE_DRY_RUN: &str = "!dry_run";
 
 pub const ENDING_TRACE: &str = "trace";
 pub const ENDING_CHANGES: &str = "changes"; // TODO: actively use extension in pathbuf
@@ -46,6 +57,12 @@ pub const ENDING_CHECKSUM_VTBL_OLD: &str = "checksum_vtbl_old";
 pub const ENDING_CHECKSUM_CONST: &str = "checksum_const";
 pub const ENDING_CHECKSUM_CONST_OLD: &str = "checksum_const_old";
 pub const ENDING_TEST: &str = "test";
+pub const ENDING_FAILED: &str = "failed";
+pub const ENDING_FINGERPRINT: &str = "fingerprint";
+pub const ENDING_FILES: &str = "files";
+pub const ENDING_SPANS: &str = "spans";
+pub const ENDING_OUTCOMES: &str = "outcomes";
+pub const ENDING_DURATION: &str = "duration";
 pub const ENDING_GRAPH: &str = "graph";
 pub const ENDING_PRETTY_GRAPH: &str = "dot";
 
@@ -56,6 +73,9 @@ pub const ENDING_PROCESS_TRACE: &str = "process_trace";
 // Edge cases that need special treatment
 
 pub const SUFFIX_DYN: &str = "!dyn";
+pub const PREFIX_FILE: &str = "!file:";
+pub const PREFIX_ACCESSED_FILE: &str = "!fs:";
+pub const PREFIX_ACCESSED_ENV: &str = "!env:";
 
 pub const EDGE_CASES_NO_TRACE: &[&str] = &[
     "__rg_alloc",
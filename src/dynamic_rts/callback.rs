@@ -97,6 +97,7 @@ impl InstrumentingCallback for InstrumentingRTSCallbacks {
             }
         }
 
+        body.check_calls_to_access(tcx, &mut cache_ret);
         body.insert_trace(tcx, &outer, &mut cache_ret);
 
         #[cfg(unix)]
@@ -5,6 +5,7 @@ use rustc_middle::{
     middle::exported_symbols::{ExportedSymbol, SymbolExportInfo},
     ty::TyCtxt,
 };
+use rustc_span::sym;
 use tracing::warn;
 
 const RLIB_CRATE_NAME: &str = "rustyrts_dynamic_rlib";
@@ -15,6 +16,22 @@ const STD_CRATE_NAME: &str = "std";
 const PRE_TEST_FN_NAME: &str = "pre_test";
 const POST_TEST_FN_NAME: &str = "post_test";
 const TRACE_FN_NAME: &str = "trace";
+const TRACE_FILE_FN_NAME: &str = "trace_file";
+const TRACE_ENV_FN_NAME: &str = "trace_env";
+
+/// Functions of std that open a file or list a directory, together with the index of the argument that is the path
+const ACCESS_FILE_FN_NAMES: &[(&str, usize)] = &[
+    ("std::fs::read", 0),
+    ("std::fs::read_to_string", 0),
+    ("std::fs::read_dir", 0),
+    ("std::fs::metadata", 0),
+    ("std::fs::symlink_metadata", 0),
+    ("std::fs::File::open", 0),
+    ("std::fs::OpenOptions::open", 1),
+];
+
+/// Functions of std that query an environment variable, given its name as first argument
+const ACCESS_ENV_FN_NAMES: &[&str] = &["std::env::var", "std::env::var_os"];
 
 #[cfg(unix)]
 const POST_MAIN_FN_NAME: &str = "post_main";
@@ -36,6 +53,8 @@ static PRE_FN_TEST_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
 static PRE_FN_MAIN_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
 
 static TRACE_FN_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
+static TRACE_FILE_FN_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
+static TRACE_ENV_FN_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
 
 static POST_FN_TEST_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
 static POST_FN_MAIN_DEF_ID: OnceCell<Option<DefId>> = OnceCell::new();
@@ -115,6 +134,34 @@ pub(crate) fn get_def_id_trace_fn(tcx: TyCtxt) -> Option<DefId> {
     *TRACE_FN_DEF_ID.get_or_init(|| get_def_id_from_rlib_crate(tcx, TRACE_FN_NAME))
 }
 
+pub(crate) fn get_def_id_trace_file_fn(tcx: TyCtxt) -> Option<DefId> {
+    *TRACE_FILE_FN_DEF_ID.get_or_init(|| get_def_id_from_rlib_crate(tcx, TRACE_FILE_FN_NAME))
+}
+
+pub(crate) fn get_def_id_trace_env_fn(tcx: TyCtxt) -> Option<DefId> {
+    *TRACE_ENV_FN_DEF_ID.get_or_init(|| get_def_id_from_rlib_crate(tcx, TRACE_ENV_FN_NAME))
+}
+
+/// If `def_id` is a function that accesses a file or an environment variable,
+/// returns the function of the rlib that traces this access and the index of the argument that is passed to it
+pub(crate) fn get_def_id_trace_access_fn(tcx: TyCtxt, def_id: DefId) -> Option<(DefId, usize)> {
+    if tcx.crate_name(def_id.krate) != sym::std {
+        return None;
+    }
+
+    let def_path_str = tcx.def_path_str(def_id);
+    if let Some((_, index)) = ACCESS_FILE_FN_NAMES
+        .iter()
+        .find(|(name, _)| *name == def_path_str)
+    {
+        get_def_id_trace_file_fn(tcx).map(|def_id| (def_id, *index))
+    } else if ACCESS_ENV_FN_NAMES.contains(&def_path_str.as_str()) {
+        get_def_id_trace_env_fn(tcx).map(|def_id| (def_id, 0))
+    } else {
+        None
+    }
+}
+
 pub(crate) fn get_def_id_pre_test_fn(tcx: TyCtxt) -> Option<DefId> {
     *PRE_FN_TEST_DEF_ID.get_or_init(|| get_def_id_from_rlib_crate(tcx, PRE_TEST_FN_NAME))
 }
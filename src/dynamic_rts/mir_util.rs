@@ -1,6 +1,9 @@
 use std::mem::transmute;
 
-use super::defid_util::{get_def_id_post_test_fn, get_def_id_pre_test_fn, get_def_id_trace_fn};
+use super::defid_util::{
+    get_def_id_post_test_fn, get_def_id_pre_test_fn, get_def_id_trace_access_fn,
+    get_def_id_trace_fn,
+};
 use crate::constants::EDGE_CASES_NO_TRACE;
 use rustc_abi::HasDataLayout;
 use rustc_abi::Size;
@@ -15,10 +18,10 @@ use rustc_middle::{
 use rustc_middle::{
     mir::{
         interpret::{Allocation, Pointer, Scalar},
-        BasicBlock, BasicBlockData, Body, Local, LocalDecl, Operand, Place, ProjectionElem,
-        SourceInfo, Terminator, TerminatorKind,
+        BasicBlock, BasicBlockData, Body, BorrowKind, CastKind, Local, LocalDecl, Operand, Place,
+        ProjectionElem, Rvalue, SourceInfo, Statement, StatementKind, Terminator, TerminatorKind,
     },
-    ty::{List, RegionKind, Ty, TyCtxt, TyKind, TypeAndMut, UintTy},
+    ty::{adjustment::PointerCoercion, List, RegionKind, Ty, TyCtxt, TyKind, TypeAndMut, UintTy},
 };
 use rustc_span::Span;
 use tracing::{error, trace};
@@ -38,6 +41,21 @@ fn insert_local_ret<'tcx>(tcx: TyCtxt<'tcx>, body: &mut Body<'tcx>) -> Local {
     local_decls.push(local_decl_1)
 }
 
+fn insert_local_assign<'tcx>(
+    body: &mut Body<'tcx>,
+    statements: &mut Vec<Statement<'tcx>>,
+    ty: Ty<'tcx>,
+    rvalue: Rvalue<'tcx>,
+    span: Span,
+) -> Place<'tcx> {
+    let local = body.local_decls.push(LocalDecl::new(ty, span).immutable());
+    statements.push(Statement {
+        source_info: SourceInfo::outermost(span),
+        kind: StatementKind::Assign(Box::new((Place::from(local), rvalue))),
+    });
+    Place::from(local)
+}
+
 fn ty_bool(tcx: TyCtxt<'_>) -> Ty<'_> {
     let ty_bool = tcx.mk_ty_from_kind(TyKind::Bool);
     ty_bool
@@ -215,6 +233,8 @@ pub trait Traceable<'tcx> {
         append: bool,
     );
 
+    fn check_calls_to_access(&mut self, tcx: TyCtxt<'tcx>, cache_ret: &mut Option<Local>);
+
     #[cfg(unix)]
     fn check_calls_to_exit(&mut self, tcx: TyCtxt<'tcx>, cache_ret: &mut Option<Local>);
 
@@ -433,6 +453,116 @@ impl<'tcx> Traceable<'tcx> for Body<'tcx> {
         }
     }
 
+    fn check_calls_to_access(&mut self, tcx: TyCtxt<'tcx>, cache_ret: &mut Option<Local>) {
+        let len = self.basic_blocks.len();
+        for i in 0..len {
+            let bb = BasicBlock::from_usize(i);
+            let basic_block = self.basic_blocks.get(bb).unwrap();
+            if basic_block.is_cleanup {
+                continue;
+            }
+
+            let TerminatorKind::Call {
+                func,
+                args,
+                fn_span,
+                unwind,
+                ..
+            } = &basic_block.terminator().kind
+            else {
+                continue;
+            };
+            let Some((def_id_trace_fn, index)) = func
+                .const_fn_def()
+                .and_then(|(def_id, _)| get_def_id_trace_access_fn(tcx, def_id))
+            else {
+                continue;
+            };
+            let Some(arg) = args.get(index).cloned() else {
+                continue;
+            };
+            // We found a call to a function that accesses a file or queries an environment variable
+
+            trace!(
+                "Inserting call to {:?} into {:?}",
+                def_id_trace_fn,
+                self.source.def_id()
+            );
+
+            let span = *fn_span;
+            let unwind = *unwind;
+            let local_ret = *cache_ret.get_or_insert_with(|| insert_local_ret(tcx, self));
+            let place_elem_list = tcx.mk_place_elems(&[]);
+
+            let region = Region::new_from_kind(tcx, RegionKind::ReErased);
+            let ty_arg = arg.ty(&self.local_decls, tcx);
+            let ty_ref_arg = tcx.mk_ty_from_kind(TyKind::Ref(region, ty_arg, Mutability::Not));
+            let ty_ref_dyn = tcx
+                .instantiate_bound_regions_with_erased(
+                    tcx.fn_sig(def_id_trace_fn).instantiate_identity(),
+                )
+                .inputs()[0];
+
+            //*******************************************************
+            // Create statements that coerce a reference to the argument into a trait object,
+            // e.g. (_53 <= &_12) (_54 <= move _53 as &dyn AsRef<Path>)
+
+            let mut statements = Vec::new();
+
+            let place_arg = match arg {
+                Operand::Copy(place) | Operand::Move(place) => place,
+                constant @ Operand::Constant(_) => {
+                    insert_local_assign(self, &mut statements, ty_arg, Rvalue::Use(constant), span)
+                }
+            };
+            let place_ref_arg = insert_local_assign(
+                self,
+                &mut statements,
+                ty_ref_arg,
+                Rvalue::Ref(region, BorrowKind::Shared, place_arg),
+                span,
+            );
+            let place_ref_dyn = insert_local_assign(
+                self,
+                &mut statements,
+                ty_ref_dyn,
+                Rvalue::Cast(
+                    CastKind::PointerCoercion(PointerCoercion::Unsize),
+                    Operand::Move(place_ref_arg),
+                    ty_ref_dyn,
+                ),
+                span,
+            );
+
+            //*******************************************************
+            // Move the original call into a new basic block and call the rlib right before
+
+            let basic_blocks = self.basic_blocks.as_mut();
+
+            let original_terminator = basic_blocks[bb].terminator.take();
+            let index = basic_blocks.push(BasicBlockData::new(original_terminator));
+
+            let mut terminator = create_call(
+                tcx,
+                def_id_trace_fn,
+                span,
+                vec![Operand::Move(place_ref_dyn)],
+                local_ret,
+                place_elem_list,
+                Some(index),
+            );
+            if let TerminatorKind::Call {
+                unwind: new_unwind, ..
+            } = &mut terminator.kind
+            {
+                *new_unwind = unwind;
+            }
+
+            basic_blocks[bb].statements.extend(statements);
+            basic_blocks[bb].terminator = Some(terminator);
+        }
+    }
+
     #[cfg(unix)]
     fn check_calls_to_exit(&mut self, tcx: TyCtxt<'tcx>, cache_ret: &mut Option<Local>) {
         use super::defid_util::get_def_id_exit_fn;
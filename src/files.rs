@@ -0,0 +1,203 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use rustc_middle::{
+    mir::{visit::Visitor, Body},
+    ty::TyCtxt,
+};
+use rustc_span::{sym, ExpnKind, FileName, MacroKind, RealFileName, Span};
+
+use crate::cache;
+
+/// Collects the files that are embedded into a MIR body using `include_str!` or `include_bytes!`
+pub struct IncludedFilesVisitor<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    visited: HashSet<Span>,
+    acc: HashSet<PathBuf>,
+}
+
+impl<'tcx> IncludedFilesVisitor<'tcx> {
+    pub(crate) fn find_files(tcx: TyCtxt<'tcx>, body: &'tcx Body<'tcx>) -> HashSet<PathBuf> {
+        let mut visitor = IncludedFilesVisitor {
+            tcx,
+            visited: HashSet::new(),
+            acc: HashSet::new(),
+        };
+
+        visitor.visit_body(body);
+        for body in tcx.promoted_mir(body.source.def_id()) {
+            visitor.visit_body(body);
+        }
+        visitor.acc
+    }
+
+    /// Resolves the path of an included file in the same way as rustc does,
+    /// i.e. relative to the file that contains the invocation of the macro
+    fn resolve(&self, call_site: Span) -> Option<PathBuf> {
+        let source_map = self.tcx.sess.source_map();
+
+        // We only support a single string literal as argument of the macro,
+        // any other file is still tracked since it is part of the dep-info of the crate
+        let snippet = source_map.span_to_snippet(call_site).ok()?;
+        let argument = Path::new(string_literal_argument(&snippet)?);
+
+        if argument.is_absolute() {
+            return Some(argument.to_path_buf());
+        }
+
+        let FileName::Real(RealFileName::LocalPath(file)) =
+            source_map.span_to_filename(call_site.source_callsite())
+        else {
+            return None;
+        };
+        Some(file.parent()?.join(argument))
+    }
+}
+
+/// Extracts the argument of a macro invocation like `include_str!("foo.txt")`, if it is a single string literal
+fn string_literal_argument(invocation: &str) -> Option<&str> {
+    let (_, arguments) = invocation.split_once('!')?;
+    let arguments = arguments.trim();
+    let arguments = arguments
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .or_else(|| arguments.strip_prefix('[')?.strip_suffix(']'))
+        .or_else(|| arguments.strip_prefix('{')?.strip_suffix('}'))?;
+    let argument = arguments.trim();
+    let argument = argument.strip_suffix(',').unwrap_or(argument).trim_end();
+
+    // Raw string literals may contain quotes, but no escapes
+    let (literal, raw) = match argument.strip_prefix('r') {
+        Some(raw) => {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            let delimiter = "#".repeat(hashes);
+            let literal = raw
+                .strip_prefix(delimiter.as_str())?
+                .strip_suffix(delimiter.as_str())?;
+            (literal, true)
+        }
+        None => (argument, false),
+    };
+    let content = literal.strip_prefix('"')?.strip_suffix('"')?;
+
+    if !raw && (content.contains('"') || content.contains('\\')) {
+        return None;
+    }
+    Some(content)
+}
+
+impl<'tcx> Visitor<'tcx> for IncludedFilesVisitor<'tcx> {
+    fn visit_span(&mut self, span: Span) {
+        if !span.from_expansion() || !self.visited.insert(span) {
+            return;
+        }
+
+        for expn_data in span.macro_backtrace() {
+            if let ExpnKind::Macro(MacroKind::Bang, name) = expn_data.kind {
+                if name == sym::include_str || name == sym::include_bytes {
+                    if let Some(path) = self.resolve(expn_data.call_site) {
+                        self.acc.insert(path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Collects the non-Rust files that rustc would list in the dep-info of the current crate
+///
+/// Files that have been loaded by `include_str!` or `include_bytes!` are part of this,
+/// as well as any file tracked by a procedural macro.
+pub(crate) fn dep_info_files(tcx: TyCtxt<'_>) -> HashSet<PathBuf> {
+    let mut files: HashSet<PathBuf> = tcx
+        .sess
+        .source_map()
+        .files()
+        .iter()
+        .filter(|file| !file.is_imported())
+        .filter_map(|file| match &file.name {
+            FileName::Real(RealFileName::LocalPath(path)) => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+
+    files.extend(
+        tcx.sess
+            .parse_sess
+            .file_depinfo
+            .borrow()
+            .iter()
+            .map(|path| PathBuf::from(path.as_str())),
+    );
+
+    files.retain(|path| path.extension().map_or(true, |ext| ext != "rs"));
+    files
+}
+
+/// Determines the lines that a MIR body spans, if it is located in a file on the local filesystem
+///
+/// Bodies that are generated by macros are attributed to the invocation of the macro.
+pub(crate) fn body_span(tcx: TyCtxt<'_>, body: &Body<'_>) -> Option<cache::Span> {
+    let source_map = tcx.sess.source_map();
+    let span = body.span.source_callsite();
+
+    let FileName::Real(RealFileName::LocalPath(file)) = source_map.span_to_filename(span) else {
+        return None;
+    };
+    Some(cache::Span {
+        file: file.to_string_lossy().into_owned(),
+        start: source_map.lookup_char_pos(span.lo()).line,
+        end: source_map.lookup_char_pos(span.hi()).line,
+    })
+}
+
+/// Expresses a path relative to the package that is compiled, if possible,
+/// such that it does not depend on the location of the package
+pub(crate) fn package_relative(path: &Path) -> PathBuf {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    std::env::var("CARGO_MANIFEST_DIR")
+        .ok()
+        .and_then(|root| {
+            let root = Path::new(&root);
+            let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+            path.strip_prefix(root).ok().map(Path::to_path_buf)
+        })
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::string_literal_argument;
+
+    #[test]
+    fn test_string_literal_argument() {
+        assert_eq!(
+            string_literal_argument(r#"include_str!("data.txt")"#),
+            Some("data.txt")
+        );
+        assert_eq!(
+            string_literal_argument(r#"include_bytes! [ "../data.bin", ]"#),
+            Some("../data.bin")
+        );
+        assert_eq!(
+            string_literal_argument(r##"include_str!(r#"quoted "data".txt"#)"##),
+            Some(r#"quoted "data".txt"#)
+        );
+
+        assert_eq!(
+            string_literal_argument(r#"include_str!(concat!(env!("OUT_DIR"), "/data.txt"))"#),
+            None
+        );
+        assert_eq!(
+            string_literal_argument(r#"include_str!(concat!("a", "b"))"#),
+            None
+        );
+        assert_eq!(
+            string_literal_argument(r#"include_str!("escaped\"quote.txt")"#),
+            None
+        );
+        assert_eq!(string_literal_argument("include_str!(PATH)"), None);
+    }
+}
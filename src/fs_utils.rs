@@ -2,14 +2,19 @@
 
 use crate::constants::{
     DIR_DYNAMIC, DIR_GENERAL, DIR_STATIC, ENDING_CHANGES, ENDING_CHECKSUM, ENDING_CHECKSUM_CONST,
-    ENDING_CHECKSUM_VTBL, ENDING_GRAPH, ENDING_PRETTY_GRAPH, ENDING_TEST, ENDING_TRACE,
-    ENV_TARGET_DIR,
+    ENDING_CHECKSUM_VTBL, ENDING_DURATION, ENDING_FAILED, ENDING_FILES, ENDING_FINGERPRINT,
+    ENDING_GRAPH, ENDING_OUTCOMES, ENDING_PRETTY_GRAPH, ENDING_SPANS, ENDING_TEST, ENDING_TRACE,
+    ENV_TARGET_DIR, PREFIX_ACCESSED_ENV, PREFIX_ACCESSED_FILE,
 };
 use std::io::Write;
 use std::path::PathBuf;
 
+use std::{
+    ffi::OsString,
+    hash::{DefaultHasher, Hash, Hasher},
+    str::FromStr,
+};
 use std::{fs::OpenOptions, path::Path};
-use std::{hash::Hash, str::FromStr};
 
 #[cfg(unix)]
 use crate::constants::ENDING_PROCESS_TRACE;
@@ -48,6 +53,12 @@ pub enum CacheFileParsingError {
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub enum CacheFileKind {
     Tests,
+    Failed,
+    Fingerprint,
+    Files,
+    Spans,
+    Outcomes,
+    Duration,
     Changes,
     Checksums(ChecksumKind),
     Graph,
@@ -92,6 +103,12 @@ impl AsRef<str> for CacheFileKind {
     fn as_ref(&self) -> &str {
         match self {
             Self::Tests => ENDING_TEST,
+            Self::Failed => ENDING_FAILED,
+            Self::Fingerprint => ENDING_FINGERPRINT,
+            Self::Files => ENDING_FILES,
+            Self::Spans => ENDING_SPANS,
+            Self::Outcomes => ENDING_OUTCOMES,
+            Self::Duration => ENDING_DURATION,
             Self::Changes => ENDING_CHANGES,
             Self::Checksums(kind) => kind.as_ref(),
             Self::Graph => ENDING_GRAPH,
@@ -110,8 +127,15 @@ impl FromStr for CacheFileKind {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             ENDING_TEST => Ok(Self::Tests),
+            ENDING_FAILED => Ok(Self::Failed),
+            ENDING_FINGERPRINT => Ok(Self::Fingerprint),
+            ENDING_FILES => Ok(Self::Files),
+            ENDING_SPANS => Ok(Self::Spans),
+            ENDING_OUTCOMES => Ok(Self::Outcomes),
+            ENDING_DURATION => Ok(Self::Duration),
             ENDING_CHANGES => Ok(Self::Changes),
             ENDING_GRAPH => Ok(Self::Graph),
+            ENDING_PRETTY_GRAPH => Ok(Self::PrettyGraph),
             ENDING_TRACE => Ok(Self::Traces),
 
             #[cfg(unix)]
@@ -122,7 +146,7 @@ impl FromStr for CacheFileKind {
     }
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Debug)]
 pub struct CacheFileDescr<'data> {
     pub crate_name: &'data str,
     pub compile_mode: Option<&'data str>,
@@ -172,34 +196,27 @@ impl<'data> CacheFileDescr<'data> {
 impl<'data> TryFrom<&'data Path> for CacheFileDescr<'data> {
     type Error = CacheFileParsingError;
 
+    /// Parses the name of a cache file, i.e. the inverse of `apply`
+    ///
+    /// Files of units are prefixed by the compile mode and the kind of target, e.g. `Test_lib_foo-0123456789abcdef.test`,
+    /// whereas files of single tests are named after the test, e.g. `foo::tests::test_bar.trace`.
+    /// The name of a doctest is not split off the crate name, see `with_crate_names`.
     fn try_from(value: &'data Path) -> Result<Self, Self::Error> {
-        let mut path_str = value
+        let stem = value
             .file_stem()
             .ok_or(CacheFileParsingError::FoundDirectory)?
             .to_str()
             .ok_or(CacheFileParsingError::InvalidFileName)?;
 
-        let compile_mode = path_str.split_once('_').map(|(compile_mode, remainder)| {
-            path_str = remainder;
-            compile_mode
-        });
-
-        let target = path_str.split_once('_').map(|(target, remainder)| {
-            path_str = remainder;
-            target
-        });
-
-        let doctest_name = path_str
-            .rsplit_once('_')
-            .map(|(remainder, crate_id)| {
-                path_str = remainder;
-                crate_id
-                    .strip_suffix(']')
-                    .ok_or(CacheFileParsingError::InvalidFileName)
-            })
-            .transpose()?;
-
-        let crate_name = path_str;
+        let mut parts = stem.splitn(3, '_');
+        let (compile_mode, target, crate_name) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(compile_mode), Some(target), Some(crate_name))
+                if is_compile_mode(compile_mode) && !crate_name.is_empty() =>
+            {
+                (Some(compile_mode), Some(target), crate_name)
+            }
+            _ => (None, None, stem),
+        };
 
         let ending = value
             .extension()
@@ -211,12 +228,56 @@ impl<'data> TryFrom<&'data Path> for CacheFileDescr<'data> {
             crate_name,
             compile_mode,
             target,
-            doctest_name,
+            doctest_name: None,
             kind,
         })
     }
 }
 
+impl<'data> CacheFileDescr<'data> {
+    /// Splits the name of a doctest off the crate name, e.g. `foo_bar_src_lib_rs` into `foo_bar` and `src_lib_rs`
+    ///
+    /// Since crate names may contain underscores as well, this requires the names of the crates that may have been tested.
+    /// The longest matching crate name is chosen.
+    pub fn with_crate_names<'a>(self, crate_names: impl IntoIterator<Item = &'a str>) -> Self {
+        if self.compile_mode != Some("Doctest") || self.doctest_name.is_some() {
+            return self;
+        }
+
+        let crate_name = self.crate_name;
+        let len = crate_names
+            .into_iter()
+            .filter(|name| {
+                crate_name
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.len() > 1 && rest.starts_with('_'))
+            })
+            .map(str::len)
+            .max();
+
+        match len {
+            Some(len) => Self {
+                crate_name: &crate_name[..len],
+                doctest_name: Some(&crate_name[len + 1..]),
+                ..self
+            },
+            None => self,
+        }
+    }
+}
+
+/// Whether a part of the name of a cache file is a compile mode, e.g. `Test` or `Doctest`
+fn is_compile_mode(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_uppercase()) && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// 64-bit FNV-1a, a hash function that is stable across platforms and versions of Rust
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 /// Computes the location of a file from a closure
 /// and overwrites the content of this file
 ///
@@ -341,3 +402,131 @@ where
         }
     }
 }
+
+/// A file or environment variable that has been accessed by a test at runtime
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Accessed<'data> {
+    /// Path of a file, relative to the root of the package
+    File(&'data str),
+    /// Name of an environment variable
+    Env(&'data str),
+}
+
+impl<'data> Accessed<'data> {
+    /// Computes the checksum of the current content of the file or value of the variable,
+    /// or `None` if there is no such file or variable
+    ///
+    /// The content of a directory is the list of names of its entries.
+    /// Variables are looked up using `var`, since the tests may be executed with a different environment.
+    pub fn checksum(self, root: &Path, var: impl Fn(&str) -> Option<OsString>) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            Accessed::File(path) if root.join(path).is_dir() => {
+                let mut entries = std::fs::read_dir(root.join(path))
+                    .ok()?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+                    .collect::<Vec<_>>();
+                entries.sort();
+                entries.hash(&mut hasher)
+            }
+            Accessed::File(path) => std::fs::read(root.join(path)).ok()?.hash(&mut hasher),
+            Accessed::Env(name) => var(name)?.hash(&mut hasher),
+        }
+        Some(hasher.finish())
+    }
+
+    /// Renders the access as a line of a trace, e.g. `!env:00ab12cd00ab12cd:HOME`
+    pub fn to_trace(self, checksum: Option<u64>) -> String {
+        let (prefix, name) = match self {
+            Accessed::File(path) => (PREFIX_ACCESSED_FILE, path),
+            Accessed::Env(name) => (PREFIX_ACCESSED_ENV, name),
+        };
+        let checksum = checksum.map_or("-".to_string(), |checksum| format!("{checksum:016x}"));
+        format!("{prefix}{checksum}:{name}")
+    }
+
+    /// Parses a line of a trace, if it refers to an accessed file or environment variable
+    pub fn from_trace(line: &'data str) -> Option<(Self, Option<u64>)> {
+        let (constructor, rest): (fn(&'data str) -> Self, _) =
+            if let Some(rest) = line.strip_prefix(PREFIX_ACCESSED_FILE) {
+                (Accessed::File, rest)
+            } else if let Some(rest) = line.strip_prefix(PREFIX_ACCESSED_ENV) {
+                (Accessed::Env, rest)
+            } else {
+                return None;
+            };
+        let (checksum, name) = rest.split_once(':')?;
+        let checksum = match checksum {
+            "-" => None,
+            checksum => Some(u64::from_str_radix(checksum, 16).ok()?),
+        };
+        Some((constructor(name), checksum))
+    }
+
+    /// Whether the file or variable is different from when it was traced
+    pub fn has_changed(line: &str, root: &Path, var: impl Fn(&str) -> Option<OsString>) -> bool {
+        Accessed::from_trace(line)
+            .is_some_and(|(accessed, checksum)| accessed.checksum(root, var) != checksum)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{CacheFileDescr, CacheFileKind, ChecksumKind};
+
+    #[test]
+    pub fn test_cache_file_descr_parsing() {
+        let cases = [
+            (
+                "Test_integration-test_foo_bar-0123456789abcdef.checksum_vtbl",
+                CacheFileDescr::new(
+                    "foo_bar-0123456789abcdef",
+                    Some("Test"),
+                    Some("integration-test"),
+                    None,
+                    CacheFileKind::Checksums(ChecksumKind::VtblChecksum),
+                ),
+            ),
+            (
+                "Doctest_lib_foo_bar_src_lib_rs.changes",
+                CacheFileDescr::new(
+                    "foo_bar",
+                    Some("Doctest"),
+                    Some("lib"),
+                    Some("src_lib_rs"),
+                    CacheFileKind::Changes,
+                ),
+            ),
+            (
+                "Build_lib_foo_bar-0123456789abcdef.spans",
+                CacheFileDescr::new(
+                    "foo_bar-0123456789abcdef",
+                    Some("Build"),
+                    Some("lib"),
+                    None,
+                    CacheFileKind::Spans,
+                ),
+            ),
+            (
+                "foo_bar::tests::test_baz.trace",
+                CacheFileDescr::new(
+                    "foo_bar::tests::test_baz",
+                    None,
+                    None,
+                    None,
+                    CacheFileKind::Traces,
+                ),
+            ),
+        ];
+
+        for (file_name, expected) in cases {
+            let parsed = CacheFileDescr::try_from(Path::new(file_name))
+                .ok()
+                .unwrap()
+                .with_crate_names(["foo", "foo_bar"]);
+            assert_eq!(parsed, expected);
+        }
+    }
+}
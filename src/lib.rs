@@ -35,10 +35,12 @@ extern crate rustc_resolve;
 pub mod dynamic_rts;
 pub mod static_rts;
 
+pub mod cache;
 pub mod callbacks_shared;
 pub mod checksums;
 pub mod const_visitor;
 pub mod constants;
+pub mod files;
 pub mod format;
 pub mod fs_utils;
 pub mod info;
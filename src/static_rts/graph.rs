@@ -8,7 +8,7 @@ use std::{
     io::Write,
 };
 use std::{fmt::Debug, ops::Deref};
-use std::{fmt::Display, iter::IntoIterator, vec::Vec};
+use std::{fmt::Display, iter::IntoIterator, str::FromStr, vec::Vec};
 use std::{hash::Hash, ops::AddAssign};
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
@@ -30,12 +30,45 @@ pub enum EdgeType {
     Trimmed = 1 << 11,
 }
 
+impl EdgeType {
+    pub const ALL: [EdgeType; 12] = [
+        EdgeType::Call,
+        EdgeType::Unsize,
+        EdgeType::Contained,
+        EdgeType::Drop,
+        EdgeType::Static,
+        EdgeType::ReifyPtr,
+        EdgeType::FnPtr,
+        EdgeType::Asm,
+        EdgeType::ClosurePtr,
+        EdgeType::Intrinsic,
+        EdgeType::LangItem,
+        EdgeType::Trimmed,
+    ];
+}
+
 impl Display for EdgeType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("{self:?}"))
     }
 }
 
+impl FromStr for EdgeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EdgeType::ALL
+            .into_iter()
+            .find(|ty| ty.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown edge type `{s}`, expected one of {}",
+                    EdgeType::ALL.iter().join(", ")
+                )
+            })
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct EdgeTypes {
     bitmap: u16,
@@ -46,9 +79,53 @@ impl EdgeTypes {
         Self { bitmap: 0 }
     }
 
-    fn from_raw(raw: u16) -> Self {
+    pub fn from_raw(raw: u16) -> Self {
         Self { bitmap: raw }
     }
+
+    pub fn all() -> Self {
+        let mut types = Self::empty();
+        for ty in EdgeType::ALL {
+            types += ty;
+        }
+        types
+    }
+
+    /// Determines the types of edges that are followed when computing reachable nodes
+    ///
+    /// Every entry is either the name of a type, `all`, or a name prefixed by `!` to exclude this type.
+    /// If there is no entry that includes any type, all types except the excluded ones are followed.
+    pub fn from_policy<'a>(entries: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut included = Self::empty();
+        let mut excluded = Self::empty();
+        let mut any_included = false;
+
+        for entry in entries.into_iter().map(str::trim).filter(|e| !e.is_empty()) {
+            if let Some(name) = entry.strip_prefix('!') {
+                excluded += name.trim().parse()?;
+            } else if entry.eq_ignore_ascii_case("all") {
+                included = Self::all();
+                any_included = true;
+            } else {
+                included += entry.parse()?;
+                any_included = true;
+            }
+        }
+
+        if !any_included {
+            included = Self::all();
+        }
+        Ok(Self::from_raw(*included & !*excluded))
+    }
+}
+
+/// Parses a policy of comma-separated entries, as described at [`EdgeTypes::from_policy`]
+impl FromStr for EdgeTypes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_policy(s.split([',', '|']))
+    }
 }
 
 impl Deref for EdgeTypes {
@@ -172,6 +249,28 @@ impl<'arena, T: Eq + Hash> DependencyGraph<'arena, T> {
 
         reached
     }
+
+    /// Removes all edges that do not have any of `edge_types`, the remaining ones only keep these types
+    pub fn retain_edges(&mut self, edge_types: EdgeTypes) {
+        for ingoing in self.backwards_edges.values_mut() {
+            ingoing.retain(|_, types| {
+                types.bitmap &= edge_types.bitmap;
+                types.bitmap != 0
+            });
+        }
+    }
+
+    /// Returns the types of the edge from `start` to `end`, if it exists
+    pub fn edge_types(
+        &self,
+        start: ArenaIntern<'arena, T>,
+        end: ArenaIntern<'arena, T>,
+    ) -> Option<EdgeTypes> {
+        self.backwards_edges
+            .get(&end)
+            .and_then(|edges| edges.get(&start))
+            .copied()
+    }
 }
 
 impl<'arena> DependencyGraph<'arena, String> {
@@ -186,23 +285,18 @@ impl<'arena> DependencyGraph<'arena, String> {
 }
 
 pub mod serialize {
-    use std::{
-        collections::{HashMap, HashSet},
-        str::Utf8Error,
-    };
+    use std::collections::{HashMap, HashSet};
 
     use internment::Arena;
-    use itertools::Itertools;
 
-    use crate::static_rts::graph::EdgeTypes;
+    pub use crate::cache::DeserializationError;
+    use crate::{cache::RawGraph, static_rts::graph::EdgeTypes};
 
     use super::DependencyGraph;
 
-    #[derive(Debug)]
-    pub enum DeserializationError {
-        SplitError,
-        ConversionError(Utf8Error),
-        IndexOutOfBounds(usize),
+    /// Graphs are only compatible if they have been created by the same toolchain
+    pub fn toolchain() -> &'static str {
+        rustc_interface::util::rustc_version_str().unwrap_or("unknown")
     }
 
     pub trait ArenaSerializable<'arena, I> {
@@ -218,44 +312,32 @@ pub mod serialize {
         ) -> Result<DependencyGraph<'arena, String>, Self::Error>;
     }
 
+    /// The format is described at [`RawGraph::serialize`]
     impl<'arena> ArenaSerializable<'arena, String> for DependencyGraph<'arena, String> {
         fn serialize(self) -> Vec<u8> {
-            let mut out: Vec<u8> = Vec::new();
-
             let mut nodes_map = HashMap::new();
 
             // 1. Nodes
-            let nodes = self
-                .nodes
-                .into_iter()
-                .enumerate()
-                .inspect(|(i, s)| {
-                    nodes_map.insert(*s, *i);
-                })
-                .map(|(_i, s)| s)
-                .join("|");
-            out.extend_from_slice(nodes.as_bytes());
-
-            out.push(b'~');
+            let mut nodes = Vec::new();
+            for (i, node) in self.nodes.into_iter().enumerate() {
+                nodes_map.insert(node, i);
+                nodes.push(node.into_ref().as_str());
+            }
 
             // 2. Edges
-            out.extend_from_slice(&self.backwards_edges.len().to_ne_bytes());
-            for (end, edges) in self.backwards_edges {
-                let i_end = *nodes_map.get(&end).unwrap();
-                let num = edges.len();
-
-                out.extend_from_slice(&i_end.to_ne_bytes());
-                out.extend_from_slice(&num.to_ne_bytes());
-
-                for (start, types) in edges {
-                    let i_start = nodes_map.get(&start).unwrap();
-
-                    out.extend_from_slice(&i_start.to_ne_bytes());
-                    out.extend_from_slice(&(*types).to_ne_bytes());
-                }
-            }
+            let edges = self
+                .backwards_edges
+                .into_iter()
+                .map(|(end, ingoing)| {
+                    let ingoing = ingoing
+                        .into_iter()
+                        .map(|(start, types)| (*nodes_map.get(&start).unwrap(), *types))
+                        .collect();
+                    (*nodes_map.get(&end).unwrap(), ingoing)
+                })
+                .collect();
 
-            out
+            RawGraph { nodes, edges }.serialize(toolchain())
         }
     }
 
@@ -263,66 +345,25 @@ pub mod serialize {
         type Error = DeserializationError;
 
         fn deserialize(arena: &'arena Arena<String>, input: &[u8]) -> Result<Self, Self::Error> {
-            let mut input = input;
-
             let mut nodes = HashSet::new();
             let mut backwards_edges = HashMap::new();
 
-            while !input.is_empty() {
-                let (nodes_raw, rest) = input
-                    .split_once(|c| *c == b'~')
-                    .ok_or(DeserializationError::SplitError)?;
-                input = rest;
-
-                // 1. Read nodes
-                let mut nodes_map = HashMap::new();
-                for (i, n) in nodes_raw.split(|c| *c == b'|').enumerate() {
-                    let node =
-                        std::str::from_utf8(n).map_err(DeserializationError::ConversionError)?;
-                    let interned = arena.intern(node.to_string());
-                    nodes_map.insert(i, interned);
-                    nodes.insert(interned);
-                }
-
-                // 2. Read edges
-                let num_edges = {
-                    let (num_raw, r) = input.split_at(std::mem::size_of::<usize>());
-                    input = r;
-
-                    usize::from_ne_bytes(num_raw.try_into().unwrap())
-                };
-
-                for _ in 0..num_edges {
-                    let (end_raw, r) = input.split_at(std::mem::size_of::<usize>());
-                    input = r;
-                    let (num_raw, r) = input.split_at(std::mem::size_of::<usize>());
-                    input = r;
-
-                    let end_index = usize::from_ne_bytes(end_raw.try_into().unwrap());
-                    let end = *nodes_map
-                        .get(&end_index)
-                        .ok_or(DeserializationError::IndexOutOfBounds(end_index))?;
-
-                    let num = usize::from_ne_bytes(num_raw.try_into().unwrap());
-
-                    backwards_edges.insert(end, HashMap::new());
-                    let inner = backwards_edges.get_mut(&end).unwrap();
-
-                    for _ in 0..num {
-                        let (start_raw, r) = input.split_at(std::mem::size_of::<usize>());
-                        input = r;
-                        let (types_raw, r) = input.split_at(std::mem::size_of::<EdgeTypes>());
-                        input = r;
-
-                        let start_index = usize::from_ne_bytes(start_raw.try_into().unwrap());
-                        let start = *nodes_map
-                            .get(&start_index)
-                            .ok_or(DeserializationError::IndexOutOfBounds(start_index))?;
-
-                        let bitmap = u16::from_ne_bytes(types_raw.try_into().unwrap());
-                        let types = EdgeTypes::from_raw(bitmap);
-
-                        inner.insert(start, types);
+            for raw in RawGraph::deserialize(input, Some(toolchain()))? {
+                // 1. Nodes
+                let nodes_map = raw
+                    .nodes
+                    .into_iter()
+                    .map(|node| arena.intern(node.to_string()))
+                    .collect::<Vec<_>>();
+                nodes.extend(nodes_map.iter().copied());
+
+                // 2. Edges
+                for (end, ingoing) in raw.edges {
+                    let inner: &mut HashMap<_, _> =
+                        backwards_edges.entry(nodes_map[end]).or_default();
+
+                    for (start, types) in ingoing {
+                        inner.insert(nodes_map[start], EdgeTypes::from_raw(types));
                     }
                 }
             }
@@ -468,11 +509,16 @@ pub mod pretty {
 
 #[cfg(test)]
 mod test {
+    use std::path::Path;
+
     use internment::Arena;
 
-    use crate::static_rts::graph::{DependencyGraph, EdgeType};
+    use crate::{
+        cache::{FileStamp, WorkspaceGraph},
+        static_rts::graph::{DependencyGraph, EdgeType, EdgeTypes},
+    };
 
-    use super::serialize::{ArenaDeserializable, ArenaSerializable};
+    use super::serialize::{ArenaDeserializable, ArenaSerializable, DeserializationError};
 
     #[test]
     pub fn test_graph_deserialization() {
@@ -491,4 +537,256 @@ mod test {
 
         assert_eq!(graph, deserialized);
     }
+
+    #[test]
+    pub fn test_graph_deserialization_special_characters() {
+        let arena = Arena::new();
+        let mut graph: DependencyGraph<String> = DependencyGraph::new(&arena);
+
+        graph.add_edge(
+            "foo::{closure#0}::<fn(|u8| ~ bool)>".to_string(),
+            "bar::<for<'a> fn(&'a str)>".to_string(),
+            EdgeType::FnPtr,
+        );
+        graph.add_node(String::new());
+
+        let mut serialized = graph.clone().serialize();
+        serialized.extend(graph.clone().serialize());
+
+        let deserialized = DependencyGraph::deserialize(&arena, &serialized).unwrap();
+        assert_eq!(graph, deserialized);
+    }
+
+    #[test]
+    pub fn test_graph_deserialization_errors() {
+        let arena = Arena::new();
+        let mut graph: DependencyGraph<String> = DependencyGraph::new(&arena);
+        graph.add_edge("start".to_string(), "end".to_string(), EdgeType::Call);
+
+        let serialized = graph.serialize();
+
+        let truncated = &serialized[..serialized.len() - 1];
+        assert_eq!(
+            DependencyGraph::deserialize(&arena, truncated),
+            Err(DeserializationError::UnexpectedEof)
+        );
+
+        let mut corrupt = serialized.clone();
+        let len = corrupt.len();
+        corrupt[len - 10] ^= 0xff;
+        assert_eq!(
+            DependencyGraph::deserialize(&arena, &corrupt),
+            Err(DeserializationError::ChecksumMismatch)
+        );
+
+        assert_eq!(
+            DependencyGraph::deserialize(&arena, b"start|end~"),
+            Err(DeserializationError::InvalidMagic)
+        );
+    }
+
+    #[test]
+    pub fn test_workspace_graph_shortest_path() {
+        let arena = Arena::new();
+        let stamp = FileStamp::of(Path::new(file!())).unwrap();
+        let all = *EdgeTypes::all();
+
+        let mut graph: DependencyGraph<String> = DependencyGraph::new(&arena);
+        graph.add_edge("test".to_string(), "outer".to_string(), EdgeType::Call);
+        graph.add_edge("outer".to_string(), "inner".to_string(), EdgeType::Call);
+        graph.add_edge("outer".to_string(), "inner".to_string(), EdgeType::Drop);
+        graph.add_edge("test".to_string(), "detour".to_string(), EdgeType::Call);
+        graph.add_edge("detour".to_string(), "outer".to_string(), EdgeType::Unsize);
+        graph.add_edge("other".to_string(), "unrelated".to_string(), EdgeType::Call);
+
+        let mut workspace = WorkspaceGraph::default();
+        workspace
+            .replace("test", stamp, &graph.serialize(), None)
+            .unwrap();
+
+        let path = workspace
+            .shortest_path(["test"], ["inner"], "test", all)
+            .unwrap();
+        let call = u16::from(EdgeType::Call);
+        let drop = u16::from(EdgeType::Drop);
+        assert_eq!(
+            path,
+            vec![
+                ("inner".to_string(), None),
+                ("outer".to_string(), Some(call | drop)),
+                ("test".to_string(), Some(call)),
+            ]
+        );
+
+        // Only edges of the given types are followed
+        let path = workspace
+            .shortest_path(["test"], ["inner"], "test", drop)
+            .unwrap_or_default();
+        assert!(path.is_empty());
+
+        assert_eq!(
+            workspace.shortest_path(["test"], ["test"], "test", all),
+            Some(vec![("test".to_string(), None)])
+        );
+        assert_eq!(
+            workspace.shortest_path(["test"], ["unrelated"], "test", all),
+            None
+        );
+        assert_eq!(workspace.shortest_path([], ["inner"], "test", all), None);
+    }
+
+    #[test]
+    pub fn test_workspace_graph() {
+        let arena = Arena::new();
+        let stamp = FileStamp::of(Path::new(file!())).unwrap();
+        let all = *EdgeTypes::all();
+
+        let mut lib: DependencyGraph<String> = DependencyGraph::new(&arena);
+        lib.add_edge(
+            "lib::outer".to_string(),
+            "lib::inner".to_string(),
+            EdgeType::Call,
+        );
+        let mut test: DependencyGraph<String> = DependencyGraph::new(&arena);
+        test.add_edge(
+            "test::test_outer".to_string(),
+            "lib::outer".to_string(),
+            EdgeType::Call,
+        );
+        test.add_edge(
+            "test::test_other".to_string(),
+            "test::other".to_string(),
+            EdgeType::Call,
+        );
+
+        let mut graph = WorkspaceGraph::default();
+        graph.replace("lib", stamp, &lib.serialize(), None).unwrap();
+        graph
+            .replace("test", stamp, &test.clone().serialize(), None)
+            .unwrap();
+        assert!(graph.is_current("lib", stamp));
+        assert!(!graph.is_current("other", stamp));
+
+        let reached = graph.reachable_nodes(["lib", "test"], ["lib::inner", "unknown"], all);
+        let expected = ["lib::inner", "lib::outer", "test::test_outer", "unknown"];
+        assert_eq!(reached, expected.iter().map(ToString::to_string).collect());
+
+        // Only the edges of the given units are considered
+        let reached = graph.reachable_nodes(["lib"], ["lib::inner"], all);
+        assert!(!reached.contains("test::test_outer"));
+
+        // Replacing the graph of a unit drops its previous edges
+        let mut lib: DependencyGraph<String> = DependencyGraph::new(&arena);
+        lib.add_edge(
+            "lib::other".to_string(),
+            "lib::inner".to_string(),
+            EdgeType::Call,
+        );
+        graph.replace("lib", stamp, &lib.serialize(), None).unwrap();
+        let reached = graph.reachable_nodes(["lib", "test"], ["lib::inner"], all);
+        assert!(!reached.contains("lib::outer"));
+
+        let deserialized = WorkspaceGraph::deserialize(&graph.serialize()).unwrap();
+        assert_eq!(
+            deserialized.units().collect::<Vec<_>>(),
+            vec!["lib", "test"]
+        );
+        assert_eq!(
+            deserialized.reachable_nodes(["lib", "test"], ["lib::inner", "test::other"], all),
+            graph.reachable_nodes(["lib", "test"], ["lib::inner", "test::other"], all)
+        );
+
+        assert!(graph.remove("test"));
+        assert!(!graph.retain(|unit| unit == "lib"));
+        assert_eq!(
+            WorkspaceGraph::deserialize(&graph.serialize()[1..]),
+            Err(DeserializationError::InvalidMagic)
+        );
+    }
+
+    #[test]
+    pub fn test_file_stamp() {
+        let arena = Arena::new();
+        let dir = tempdir::TempDir::new("stamp").unwrap();
+        let path = dir.path().join("lib.graph");
+
+        let mut lib: DependencyGraph<String> = DependencyGraph::new(&arena);
+        lib.add_edge(
+            "lib::outer".to_string(),
+            "lib::inner".to_string(),
+            EdgeType::Call,
+        );
+        std::fs::write(&path, lib.serialize()).unwrap();
+        let stamp = FileStamp::of(&path).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        // Same length and modification time, but different content
+        let mut lib: DependencyGraph<String> = DependencyGraph::new(&arena);
+        lib.add_edge(
+            "lib::other".to_string(),
+            "lib::inner".to_string(),
+            EdgeType::Call,
+        );
+        std::fs::write(&path, lib.serialize()).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+        assert_ne!(FileStamp::of(&path).unwrap(), stamp);
+    }
+
+    #[test]
+    pub fn test_edge_type_policy() {
+        let policy: EdgeTypes = "Call, Drop|static".parse().unwrap();
+        assert_eq!(
+            policy.into_iter().collect::<Vec<_>>(),
+            vec![EdgeType::Call, EdgeType::Drop, EdgeType::Static]
+        );
+
+        let policy: EdgeTypes = "!Contained".parse().unwrap();
+        assert!(!policy.into_iter().any(|ty| ty == EdgeType::Contained));
+        assert_eq!(policy.into_iter().count(), EdgeType::ALL.len() - 1);
+
+        assert_eq!("".parse::<EdgeTypes>(), Ok(EdgeTypes::all()));
+        assert_eq!("all".parse::<EdgeTypes>(), Ok(EdgeTypes::all()));
+        assert!("Call,Foo".parse::<EdgeTypes>().is_err());
+
+        let arena = Arena::new();
+        let mut graph: DependencyGraph<String> = DependencyGraph::new(&arena);
+        graph.add_edge("test".to_string(), "outer".to_string(), EdgeType::Call);
+        graph.add_edge(
+            "outer".to_string(),
+            "inner".to_string(),
+            EdgeType::Contained,
+        );
+        graph.add_edge(
+            "outer".to_string(),
+            "other".to_string(),
+            EdgeType::Contained,
+        );
+        graph.add_edge("outer".to_string(), "other".to_string(), EdgeType::Drop);
+
+        graph.retain_edges("!Contained".parse().unwrap());
+
+        let inner = arena.intern("inner".to_string());
+        let other = arena.intern("other".to_string());
+        let test = arena.intern("test".to_string());
+        assert!(!graph.reachable_nodes([inner]).contains(&test));
+        assert!(graph.reachable_nodes([other]).contains(&test));
+        let outer = arena.intern("outer".to_string());
+        assert_eq!(
+            graph
+                .edge_types(outer, other)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![EdgeType::Drop]
+        );
+    }
 }
@@ -0,0 +1,52 @@
+use std::{fs::OpenOptions, io::Read, path::Path};
+
+pub fn config() -> String {
+    let mut content = String::new();
+    OpenOptions::new()
+        .read(true)
+        .open("data/config.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+pub fn fixtures() -> usize {
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    std::fs::read_dir(Path::new(&root).join("data"))
+        .unwrap()
+        .count()
+}
+
+pub fn mode() -> Option<String> {
+    std::env::var("ACCESSED_MODE").ok()
+}
+
+pub fn unrelated() -> u32 {
+    42
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_config() {
+        assert_eq!(config().trim(), "config");
+    }
+
+    #[test]
+    fn test_mode() {
+        assert_ne!(mode().as_deref(), Some("changed"));
+    }
+
+    #[test]
+    fn test_fixtures() {
+        assert_eq!(fixtures(), 1);
+    }
+
+    #[test]
+    fn test_unrelated() {
+        assert_eq!(unrelated(), 42);
+    }
+}
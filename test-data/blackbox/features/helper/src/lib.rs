@@ -0,0 +1,8 @@
+pub fn answer() -> u32 {
+    42
+}
+
+#[cfg(feature = "extra")]
+pub fn extra() -> u32 {
+    43
+}
@@ -0,0 +1,13 @@
+pub fn answer() -> u32 {
+    helper::answer()
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::*;
+
+    #[test]
+    fn test_answer() {
+        assert_eq!(answer(), 42);
+    }
+}
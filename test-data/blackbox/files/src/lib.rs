@@ -0,0 +1,31 @@
+pub fn embedded() -> &'static str {
+    include_str!("../data/embedded.txt")
+}
+
+pub fn runtime() -> String {
+    std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/data/runtime.txt")).unwrap()
+}
+
+pub fn unrelated() -> u32 {
+    42
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_embedded() {
+        assert_eq!(embedded().trim(), "embedded");
+    }
+
+    #[test]
+    fn test_runtime() {
+        assert_eq!(runtime().trim(), "runtime");
+    }
+
+    #[test]
+    fn test_unrelated() {
+        assert_eq!(unrelated(), 42);
+    }
+}
@@ -1,9 +1,13 @@
 use lazy_static::lazy_static;
-use std::{fs::create_dir_all, path::PathBuf};
+use std::{collections::BTreeMap, fs::create_dir_all, path::PathBuf};
 use std::{path::Path, process::Command};
 use test_case::test_case;
 
-use rustyrts::constants::{ENV_BLACKBOX_TEST, ENV_TARGET_DIR};
+use rustyrts::cache::{Affected, Cache, Technique};
+use rustyrts::constants::{
+    DIR_DYNAMIC, DIR_GENERAL, DIR_STATIC, ENDING_GRAPH, ENDING_TEST, ENV_BLACKBOX_IGNORE_FEATURES,
+    ENV_BLACKBOX_TEST, ENV_RETEST_ALL, ENV_TARGET_DIR, FILE_WORKSPACE_GRAPH,
+};
 use tempdir::TempDir;
 
 enum Mode {
@@ -23,8 +27,12 @@ impl From<&Mode> for &str {
 }
 
 fn command(mode: &Mode, dir: &PathBuf, target_dir: &Path, feature: Option<&str>) -> Command {
+    subcommand(Into::<&str>::into(mode), dir, target_dir, feature)
+}
+
+fn subcommand(name: &str, dir: &PathBuf, target_dir: &Path, feature: Option<&str>) -> Command {
     let mut ret = Command::new(env!("CARGO_BIN_EXE_cargo-rustyrts"));
-    ret.arg("rustyrts").arg(Into::<&str>::into(mode));
+    ret.arg("rustyrts").arg(name);
     ret.current_dir(dir);
 
     if let Some(name) = feature {
@@ -35,6 +43,8 @@ fn command(mode: &Mode, dir: &PathBuf, target_dir: &Path, feature: Option<&str>)
 
     ret.env(ENV_TARGET_DIR, target_dir);
     ret.env(ENV_BLACKBOX_TEST, "true");
+    // Enabling features is used to simulate changes of the code
+    ret.env(ENV_BLACKBOX_IGNORE_FEATURES, "true");
 
     ret
 }
@@ -75,9 +85,16 @@ fn check_same_crate_id(mode: Mode) {
         let result = command(&mode, &dir, target_dir.path(), None)
             .output()
             .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
         println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
-        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
-        assert!(result.status.success());
+        println!("Stderr: {stderr}");
+
+        // No test is affected, but the failing test is selected again since it failed previously
+        assert!(stderr
+            .lines()
+            .filter(|l| l.starts_with("Affected:"))
+            .all(|l| l == "Affected: []"));
+        assert!(!result.status.success());
     }
 }
 
@@ -338,19 +355,22 @@ fn blackbox_test_affected(mode: Mode, name: &str, features_baseline: &str, featu
     Mode::Dynamic,
     "threading",
     "test1_panic",
-    "test1_panic, changes_test2"
+    "test1_panic, changes_test2",
+    "test1"
 )]
 #[test_case(
     Mode::Dynamic,
     "threading",
     "test2_panic",
-    "test2_panic, changes_test1"
+    "test2_panic, changes_test1",
+    "test2"
 )]
 fn blackbox_test_not_affected(
     mode: Mode,
     name: &str,
     features_baseline: &str,
     features_changes: &str,
+    failing: &str,
 ) {
     let mut dir = PATH.clone();
     dir.push(name);
@@ -377,8 +397,1820 @@ fn blackbox_test_not_affected(
         let result = command(&mode, &dir, target_dir.path(), Some(features_changes))
             .output()
             .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {stderr}");
+
+        // The failing test is not affected by the changes, but selected again since it failed previously
+        assert!(!stderr
+            .lines()
+            .any(|l| l.starts_with("Affected:") && l.contains(failing)));
+        assert!(stdout.contains(&format!("test {failing} ... FAILED")));
+        assert!(!result.status.success());
+    }
+}
+
+#[test_case(false; "workspace_graph_updated")]
+#[test_case(true; "workspace_graph_recreated")]
+fn blackbox_test_workspace_graph(remove: bool) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+    let workspace_graph = target_dir
+        .path()
+        .join(DIR_STATIC)
+        .join(FILE_WORKSPACE_GRAPH);
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        assert!(workspace_graph.is_file());
+    }
+
+    if remove {
+        std::fs::remove_file(&workspace_graph).unwrap();
+    }
+
+    {
+        println!("-------- with changes --------");
+        let result = command(
+            &Mode::Static,
+            &dir,
+            target_dir.path(),
+            Some("changes_display"),
+        )
+        .output()
+        .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+
+        assert!(stdout.contains("test test::test_display ... FAILED"));
+        assert!(is_deselected(&stdout, "test::test_debug"));
+        assert!(workspace_graph.is_file());
+    }
+}
+
+#[test_case("all", true; "all_edge_types")]
+#[test_case("Asm", false; "only_asm_edges")]
+fn blackbox_test_edge_types(edge_types: &str, affected: bool) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- invalid policy --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .arg("--edge-types")
+            .arg("Call,Foo")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(stderr.contains("unknown edge type `Foo`"));
+    }
+
+    {
+        println!("-------- with changes --------");
+        let result = command(
+            &Mode::Static,
+            &dir,
+            target_dir.path(),
+            Some("changes_display"),
+        )
+        .arg("--edge-types")
+        .arg(edge_types)
+        .output()
+        .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {stderr}");
+
+        if affected {
+            assert!(stderr.contains("following all edges"));
+            assert!(stdout.contains("test test::test_display ... FAILED"));
+        } else {
+            assert!(stderr.contains("following only edges of types Asm"));
+            assert!(is_deselected(&stdout, "test::test_display"));
+        }
+    }
+}
+
+#[test_case("adt", "changes_display", "test_display", true)]
+#[test_case("adt", "changes_display", "test_debug", false)]
+fn blackbox_test_explain(name: &str, features_changes: &str, test: &str, affected: bool) {
+    let mut dir = PATH.clone();
+    dir.push(name);
+
+    let target_dir = TempDir::new_in(
+        env!("CARGO_TARGET_TMPDIR"),
+        dir.file_name().unwrap().to_str().unwrap(),
+    )
+    .unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    let checksums = checksum_files(target_dir.path());
+    assert!(!checksums.is_empty());
+
+    // Explaining does not consume the changes, neither for further explanations nor for the next run
+    for run in ["explain", "explain again"] {
+        println!("-------- {run} --------");
+        let result = subcommand("explain", &dir, target_dir.path(), Some(features_changes))
+            .arg(test)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+
+        let expected = if affected {
+            "is affected"
+        } else {
+            "is not affected"
+        };
+        assert!(stderr.contains(expected));
+        assert_eq!(checksum_files(target_dir.path()), checksums);
+    }
+
+    {
+        println!("-------- with changes --------");
+        let result = command(
+            &Mode::Static,
+            &dir,
+            target_dir.path(),
+            Some(features_changes),
+        )
+        .output()
+        .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(stdout.contains("test test::test_display ... FAILED"));
+    }
+}
+
+#[test_case(Mode::Dynamic, "adt", "changes_display", "test::test_display", "test::test_debug"; "dynamic_list_selected")]
+#[test_case(Mode::Static, "adt", "changes_display", "test::test_display", "test::test_debug"; "static_list_selected")]
+fn blackbox_test_list_selected(
+    mode: Mode,
+    name: &str,
+    features_changes: &str,
+    selected: &str,
+    deselected: &str,
+) {
+    let mut dir = PATH.clone();
+    dir.push(name);
+
+    let target_dir = TempDir::new_in(
+        env!("CARGO_TARGET_TMPDIR"),
+        dir.file_name().unwrap().to_str().unwrap(),
+    )
+    .unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    let checksums = checksum_files(target_dir.path());
+    assert!(!checksums.is_empty());
+
+    {
+        println!("-------- list selected --------");
+        let result = command(&mode, &dir, target_dir.path(), Some(features_changes))
+            .arg("--list-selected")
+            .arg("--format")
+            .arg("json")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        let listed: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        let unit = listed
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|unit| unit["compile_mode"] == "Test")
+            .unwrap();
+        assert!(unit["selected"]
+            .as_array()
+            .unwrap()
+            .contains(&selected.into()));
+        assert!(unit["deselected"]
+            .as_array()
+            .unwrap()
+            .contains(&deselected.into()));
+
+        // Checksums are only updated once the selected tests are executed
+        assert_eq!(checksum_files(target_dir.path()), checksums);
+    }
+
+    {
+        println!("-------- with changes --------");
+        let result = command(&mode, &dir, target_dir.path(), Some(features_changes))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+
+        assert!(stdout.contains(&format!("test {selected} ... FAILED")));
+        assert!(is_deselected(&stdout, deselected));
+        assert_ne!(checksum_files(target_dir.path()), checksums);
+    }
+}
+
+/// Names of the tests that have been reported as deselected in the human-readable output
+fn deselected_tests(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .filter(|l| l.starts_with("deselected "))
+        .filter_map(|l| l.split_once(": "))
+        .flat_map(|(_, names)| names.split(", "))
+        .collect()
+}
+
+/// Whether a test whose name ends with `name` has been reported as deselected
+fn is_deselected(stdout: &str, name: &str) -> bool {
+    deselected_tests(stdout)
+        .iter()
+        .any(|test| test.ends_with(name))
+}
+
+/// Contents of all checksum files in `target_dir`, keyed by their names
+fn checksum_files(target_dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    std::fs::read_dir(target_dir.join(DIR_GENERAL))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.to_str().unwrap().starts_with("checksum"))
+        })
+        .map(|path| {
+            (
+                path.file_name().unwrap().to_str().unwrap().to_string(),
+                std::fs::read(&path).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test_case(Mode::Dynamic, Technique::Dynamic; "dynamic_cache_api")]
+#[test_case(Mode::Static, Technique::Static; "static_cache_api")]
+fn blackbox_test_cache_api(mode: Mode, technique: Technique) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- list selected --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("changes_display"))
+            .arg("--list-selected")
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- query cache --------");
+        let cache = Cache::new(target_dir.path());
+        let units = cache.units().unwrap();
+        let unit = units
+            .iter()
+            .find(|unit| unit.compile_mode() == "Test" && unit.target() == "lib")
+            .unwrap();
+        assert_eq!(unit.crate_name(), "adt");
+
+        let tests = cache.tests(unit).unwrap();
+        assert!(tests.contains(&"adt::test::test_display".to_string()));
+        assert!(!cache.changes(technique, unit).unwrap().is_empty());
+
+        let Affected::Tests(affected) = cache.affected_tests(technique, unit).unwrap() else {
+            panic!("Expected precise selection");
+        };
+        println!("Affected: {affected:?}");
+        assert!(affected.contains("adt::test::test_display"));
+        assert!(!affected.contains("adt::test::test_debug"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_inspect")]
+#[test_case(Mode::Static; "static_inspect")]
+fn blackbox_test_inspect(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    let tests_file = {
+        println!("-------- inspect --------");
+        let result = subcommand("inspect", &dir, target_dir.path(), None)
+            .arg("--format")
+            .arg("json")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {}", stdout);
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        let listing: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        let files = listing["files"].as_array().unwrap();
+        let tests_file = files
+            .iter()
+            .find(|file| {
+                file["kind"] == "test" && file["compile_mode"] == "Test" && file["target"] == "lib"
+            })
+            .unwrap();
+        assert_eq!(tests_file["crate"], "adt");
+
+        let units = listing["units"].as_array().unwrap();
+        let unit = units
+            .iter()
+            .find(|unit| unit["name"].as_str().unwrap().starts_with("Test_lib_adt-"))
+            .unwrap();
+        assert!(unit["tests"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("adt::test::test_display")));
+        if let Mode::Static = mode {
+            assert!(unit["nodes"].as_u64().unwrap() > 0);
+            assert!(unit["edge_types"]["Call"].as_u64().unwrap() > 0);
+        }
+
+        tests_file["path"].as_str().unwrap().to_string()
+    };
+
+    {
+        println!("-------- inspect file --------");
+        let result = subcommand("inspect", &dir, target_dir.path(), None)
+            .arg(&tests_file)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {}", stdout);
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        let decoded: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(decoded["kind"], "test");
+        assert!(decoded["content"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("adt::test::test_display")));
+    }
+}
+
+#[test]
+fn blackbox_test_graph() {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- with changes, not executing tests --------");
+        let result = command(
+            &Mode::Static,
+            &dir,
+            target_dir.path(),
+            Some("changes_display"),
+        )
+        .arg("--no-run")
+        .output()
+        .unwrap();
         println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
         println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
         assert!(result.status.success());
     }
+
+    {
+        println!("-------- neighborhood of changes --------");
+        let result = subcommand("graph", &dir, target_dir.path(), None)
+            .args(["--changed", "--hops", "1", "--format", "json"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        let graph: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        let nodes = graph["nodes"].as_array().unwrap();
+        assert!(nodes.contains(&serde_json::json!({
+            "name": "adt::<Foo<i32> as Display>::fmt",
+            "changed": true
+        })));
+        assert!(nodes.contains(&serde_json::json!({
+            "name": "core::Argument::new_display::<Foo<i32>>",
+            "changed": false
+        })));
+        assert!(!nodes
+            .iter()
+            .any(|node| node["name"] == "adt::test::test_debug"));
+        assert!(graph["edges"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!({
+                "from": "core::Argument::new_display::<Foo<i32>>",
+                "to": "adt::<Foo<i32> as Display>::fmt",
+                "types": ["ReifyPtr"]
+            })));
+    }
+
+    {
+        println!("-------- between test and function --------");
+        let result = subcommand("graph", &dir, target_dir.path(), None)
+            .args([
+                "--between",
+                "test::test_display",
+                "<Foo<i32> as Display>::fmt",
+            ])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        assert!(stdout.starts_with("digraph dependencies {"));
+        assert!(stdout.contains(r#""adt::test_display" [style=filled, fillcolor=lightcoral];"#));
+        assert!(stdout.contains(r#"label="ReifyPtr", color="blue""#));
+        assert!(!stdout.contains("test_debug"));
+    }
+
+    {
+        println!("-------- crates --------");
+        let result = subcommand("graph", &dir, target_dir.path(), None)
+            .args(["--crates", "--format", "graphml"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        assert!(stdout.contains(r#"<data key="name">adt</data><data key="changed">true</data>"#));
+        assert!(stdout.contains(r#"<data key="name">core</data><data key="changed">false</data>"#));
+        assert!(!stdout.contains("::"));
+    }
+
+    {
+        println!("-------- unknown function --------");
+        let result = subcommand("graph", &dir, target_dir.path(), None)
+            .args(["--between", "test::test_display", "does_not_exist"])
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(stderr.contains("Did not find any node named `does_not_exist`"));
+    }
+}
+
+#[test_case(Mode::Static; "static_who_tests")]
+#[test_case(Mode::Dynamic; "dynamic_who_tests")]
+fn blackbox_test_who_tests(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- who-tests --------");
+        let mut cmd = subcommand("who-tests", &dir, target_dir.path(), None);
+        if let Mode::Dynamic = mode {
+            cmd.arg("--dynamic");
+        }
+        let result = cmd.arg("<Foo<i32> as Display>::fmt").output().unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        assert!(stdout.contains("    adt::test::test_display\n"));
+        assert!(!stdout.contains("test_debug"));
+        if let Mode::Static = mode {
+            assert!(stdout.contains("function                 adt::<Foo<i32> as Display>::fmt"));
+            assert!(
+                stdout.contains("<- [ReifyPtr]            core::Argument::new_display::<Foo<i32>>")
+            );
+        }
+    }
+
+    if let Mode::Static = mode {
+        println!("-------- unknown function --------");
+        let result = subcommand("who-tests", &dir, target_dir.path(), None)
+            .arg("does_not_exist")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(stderr.contains("Did not find any function named `does_not_exist`"));
+    }
+
+    {
+        println!("-------- who-tests of a location --------");
+        let mut cmd = subcommand("who-tests", &dir, target_dir.path(), None);
+        if let Mode::Dynamic = mode {
+            cmd.arg("--dynamic");
+        }
+        let result = cmd.arg("src/lib.rs:11").output().unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        assert!(stdout.contains("    adt::test::test_display\n"));
+        assert!(!stdout.contains("test_debug"));
+    }
+
+    {
+        println!("-------- location outside of functions --------");
+        let result = subcommand("who-tests", &dir, target_dir.path(), None)
+            .arg("src/lib.rs:5")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(stderr.contains("Did not find any function spanning src/lib.rs:5"));
+    }
+}
+
+#[test_case(Mode::Static; "static_spans")]
+#[test_case(Mode::Dynamic; "dynamic_spans")]
+fn blackbox_test_spans(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- with changes --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("changes_display"))
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+
+        assert!(stderr.contains("    adt::<Foo<i32> as Display>::fmt (src/lib.rs:15)\n"));
+    }
+}
+
+#[test_case("src/lib.rs", 11, false, true; "from_diff_function")]
+#[test_case("src/lib.rs", 11, false, false; "from_diff_function_without_previous_build")]
+#[test_case("src/lib.rs", 5, true, true; "from_diff_outside_function")]
+#[test_case("Cargo.toml", 1, true, true; "from_diff_manifest")]
+fn blackbox_test_from_diff(file: &str, line: usize, retest_all: bool, previous_build: bool) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    if previous_build {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    // Absolute paths, since the test data is part of the enclosing git repository
+    let path = dir.join(file);
+    let content = std::fs::read_to_string(&path).unwrap();
+    let diff = format!(
+        "--- {0}\n+++ {0}\n@@ -{line},1 +{line},1 @@\n-changed\n+{1}\n",
+        path.display(),
+        content.lines().nth(line - 1).unwrap()
+    );
+    let diff_file = target_dir.path().join("changes.diff");
+    std::fs::write(&diff_file, diff).unwrap();
+
+    {
+        println!("-------- from diff --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .arg("--from-diff")
+            .arg(&diff_file)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        assert!(stdout.contains("test test::test_display ... ok"));
+        assert!(stdout.contains("test test::test_generic ... ok"));
+        for test in ["test_debug", "test_dyn"] {
+            assert_eq!(
+                stdout.contains(&format!("test test::{test} ... ok")),
+                retest_all
+            );
+            assert_eq!(is_deselected(&stdout, test), !retest_all);
+        }
+    }
+}
+
+#[test_case(Mode::Basic; "basic_failed_reselected")]
+#[test_case(Mode::Dynamic; "dynamic_failed_reselected")]
+#[test_case(Mode::Static; "static_failed_reselected")]
+fn blackbox_test_failed_reselected(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(
+        env!("CARGO_TARGET_TMPDIR"),
+        dir.file_name().unwrap().to_str().unwrap(),
+    )
+    .unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    for run in ["with changes", "without further changes"] {
+        println!("-------- {run} --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("changes_display"))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(stdout.contains("test::test_display ... FAILED"));
+    }
+
+    {
+        println!("-------- reverted --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        assert!(stdout.contains("test::test_display ... ok"));
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()));
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+fn git_commit(repo: &git2::Repository, message: &str) -> git2::Oid {
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("rustyrts", "rustyrts@example.com").unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+}
+
+#[test_case(Mode::Dynamic; "dynamic_baseline")]
+#[test_case(Mode::Static; "static_baseline")]
+fn blackbox_test_baseline(mode: Mode) {
+    let mut source = PATH.clone();
+    source.push("adt");
+
+    let project_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt_baseline").unwrap();
+    let dir = project_dir.path().to_path_buf();
+    copy_dir(&source, &dir);
+
+    let target_dir = TempDir::new_in(
+        env!("CARGO_TARGET_TMPDIR"),
+        dir.file_name().unwrap().to_str().unwrap(),
+    )
+    .unwrap();
+
+    let repo = git2::Repository::init(&dir).unwrap();
+    let base = git_commit(&repo, "base");
+    repo.branch("base", &repo.find_commit(base).unwrap(), false)
+        .unwrap();
+
+    {
+        println!("-------- baseline without snapshot --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .arg("--baseline")
+            .arg("base")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr.contains("Did not find a snapshot"));
+    }
+
+    std::fs::write(dir.join("notes.txt"), "feature").unwrap();
+    git_commit(&repo, "feature");
+
+    // Both runs are compared to the snapshot of `base`, such that the changes are found twice
+    for run in ["with changes", "with the same changes again"] {
+        println!("-------- {run} --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("changes_display"))
+            .arg("--baseline")
+            .arg("base")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(!stderr.contains("Did not find a snapshot"));
+        assert!(stderr
+            .lines()
+            .any(|l| l.starts_with("Affected:") && l.contains("test_display")));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_relocated")]
+#[test_case(Mode::Static; "static_relocated")]
+fn blackbox_test_relocated(mode: Mode) {
+    let mut source = PATH.clone();
+    source.push("adt");
+
+    let project_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt_relocated").unwrap();
+    let original_dir = project_dir.path().join("original");
+    let moved_dir = project_dir.path().join("moved");
+    copy_dir(&source, &original_dir);
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt_relocated").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &original_dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    std::fs::rename(&original_dir, &moved_dir).unwrap();
+
+    {
+        println!("-------- moved --------");
+        let result = command(&mode, &moved_dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr
+            .lines()
+            .filter(|l| l.starts_with("Affected:"))
+            .all(|l| l == "Affected: []"));
+    }
+
+    {
+        println!("-------- moved with changes --------");
+        let result = command(
+            &mode,
+            &moved_dir,
+            target_dir.path(),
+            Some("changes_display"),
+        )
+        .output()
+        .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_build_configuration")]
+#[test_case(Mode::Static; "static_build_configuration")]
+fn blackbox_test_build_configuration(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- release --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .arg("--release")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr.contains("Build configuration of"));
+        assert!(stderr.contains("opt-level"));
+    }
+
+    {
+        println!("-------- release again --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .arg("--release")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(!stderr.contains("Build configuration of"));
+        assert!(stderr
+            .lines()
+            .filter(|l| l.starts_with("Affected:"))
+            .all(|l| l == "Affected: []"));
+    }
+
+    {
+        println!("-------- features --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("changes_debug"))
+            .env_remove(ENV_BLACKBOX_IGNORE_FEATURES)
+            .arg("--release")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(stderr.contains("Build configuration of"));
+        assert!(stderr.contains("features"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_dependency_features")]
+#[test_case(Mode::Static; "static_dependency_features")]
+fn blackbox_test_dependency_features(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("features");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "features").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .env_remove(ENV_BLACKBOX_IGNORE_FEATURES)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- feature of dependency --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("helper/extra"))
+            .env_remove(ENV_BLACKBOX_IGNORE_FEATURES)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr.contains("Build configuration of"));
+        assert!(stderr.contains("(dependency-features)"));
+        assert!(stdout.contains("test test::test_answer ... ok"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_file_dependencies")]
+#[test_case(Mode::Static; "static_file_dependencies")]
+fn blackbox_test_file_dependencies(mode: Mode) {
+    let mut source = PATH.clone();
+    source.push("files");
+
+    let project_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "files").unwrap();
+    let dir = project_dir.path().join("files");
+    copy_dir(&source, &dir);
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "files").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    std::fs::write(dir.join("data/embedded.txt"), "changed").unwrap();
+
+    {
+        println!("-------- embedded file changed --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(stdout.contains("test_embedded ... FAILED"));
+        assert!(is_deselected(&stdout, "test_unrelated"));
+    }
+
+    std::fs::write(dir.join("data/embedded.txt"), "embedded").unwrap();
+    std::fs::write(dir.join("data/runtime.txt"), "changed").unwrap();
+
+    {
+        println!("-------- declared file changed --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(stdout.contains("test_embedded ... ok"));
+        assert!(stdout.contains("test_runtime ... FAILED"));
+        assert!(is_deselected(&stdout, "test_unrelated"));
+    }
+}
+
+#[test]
+fn blackbox_test_accessed_at_runtime() {
+    let mut source = PATH.clone();
+    source.push("accessed");
+
+    let project_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+    let dir = project_dir.path().join("accessed");
+    copy_dir(&source, &dir);
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- without changes --------");
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        // Variables that cargo sets for the test binary only, e.g. CARGO_MANIFEST_DIR, are compared as well
+        assert!(is_deselected(&stdout, "test_fixtures"));
+        assert_eq!(deselected_tests(&stdout).len(), 4);
+    }
+
+    std::fs::write(dir.join("data/config.txt"), "changed").unwrap();
+
+    {
+        println!("-------- file changed --------");
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(stdout.contains("test_config ... FAILED"));
+        assert!(is_deselected(&stdout, "test_fixtures"));
+        assert!(is_deselected(&stdout, "test_mode"));
+        assert!(is_deselected(&stdout, "test_unrelated"));
+    }
+
+    std::fs::write(dir.join("data/config.txt"), "config").unwrap();
+    std::fs::write(dir.join("data/extra.txt"), "extra").unwrap();
+
+    {
+        println!("-------- directory changed --------");
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(stdout.contains("test_config ... ok"));
+        assert!(stdout.contains("test_fixtures ... FAILED"));
+        assert!(is_deselected(&stdout, "test_mode"));
+        assert!(is_deselected(&stdout, "test_unrelated"));
+    }
+
+    std::fs::remove_file(dir.join("data/extra.txt")).unwrap();
+
+    std::fs::write(dir.join("data/config.txt"), "config").unwrap();
+
+    {
+        println!("-------- environment variable changed --------");
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), None)
+            .env("ACCESSED_MODE", "changed")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+        assert!(is_deselected(&stdout, "test_config"));
+        assert!(stdout.contains("test_fixtures ... ok"));
+        assert!(stdout.contains("test_mode ... FAILED"));
+        assert!(is_deselected(&stdout, "test_unrelated"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_history")]
+#[test_case(Mode::Static; "static_history")]
+fn blackbox_test_history(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("accessed");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- failing --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .env(ENV_RETEST_ALL, "true")
+            .env("ACCESSED_MODE", "changed")
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+    }
+
+    {
+        println!("-------- history --------");
+        let result = subcommand("history", &dir, target_dir.path(), None)
+            .arg("test_mode")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        assert!(stdout.contains("accessed::test::test_mode (Test lib of package `accessed`)"));
+        assert!(stdout.contains("2 runs: 1 passed, 1 failed, 0 ignored"));
+
+        // Durations are reported by the instrumented tests in dynamic mode only
+        assert_eq!(
+            stdout.contains("average duration"),
+            matches!(mode, Mode::Dynamic)
+        );
+    }
+
+    {
+        println!("-------- history of unknown test --------");
+        let result = subcommand("history", &dir, target_dir.path(), None)
+            .arg("test_unknown")
+            .output()
+            .unwrap();
+        assert!(!result.status.success());
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_deselected_json")]
+#[test_case(Mode::Static; "static_deselected_json")]
+fn blackbox_test_deselected_json(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("accessed");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- json --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .args(["--", "-Z", "unstable-options", "--format=json"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        let events: Vec<serde_json::Value> = stdout
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect();
+        let reason = format!(
+            "not affected by changes ({} RTS)",
+            Into::<&str>::into(&mode)
+        );
+        let deselected = events
+            .iter()
+            .find(|e| e["type"] == "deselected" && e["reason"] == *reason)
+            .unwrap();
+        assert_eq!(deselected["names"].as_array().unwrap().len(), 4);
+
+        // The output of libtest itself is left as is
+        let suite = events
+            .iter()
+            .find(|e| e["type"] == "suite" && e["event"] == "ok" && e["filtered_out"] == 4)
+            .unwrap();
+        assert_eq!(suite["ignored"], 0);
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_shard")]
+#[test_case(Mode::Static; "static_shard")]
+fn blackbox_test_shard(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("accessed");
+
+    let mut executed = Vec::new();
+
+    for shard in ["1/2", "2/2"] {
+        println!("-------- shard {shard} --------");
+        let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .args(["--shard", shard])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        assert!(deselected_tests(&stdout).is_empty());
+
+        executed.extend(
+            stdout
+                .lines()
+                .filter_map(|l| l.strip_prefix("test "))
+                .filter_map(|l| l.strip_suffix(" ... ok"))
+                .map(ToString::to_string),
+        );
+    }
+
+    executed.sort();
+    assert_eq!(
+        executed,
+        [
+            "test::test_config",
+            "test::test_fixtures",
+            "test::test_mode",
+            "test::test_unrelated"
+        ]
+    );
+
+    // Tests that RustyRTS does not know are listed using the test binary
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+    for entry in std::fs::read_dir(target_dir.path().join(DIR_GENERAL)).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(ENDING_TEST) {
+            std::fs::write(path, "").unwrap();
+        }
+    }
+
+    let mut executed = Vec::new();
+    for shard in ["1/2", "2/2"] {
+        println!("-------- shard {shard} of unknown tests --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .env(ENV_RETEST_ALL, "true")
+            .args(["--shard", shard])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        executed.extend(
+            stdout
+                .lines()
+                .filter_map(|l| l.strip_prefix("test "))
+                .filter_map(|l| l.strip_suffix(" ... ok"))
+                .map(ToString::to_string),
+        );
+    }
+
+    executed.sort();
+    assert_eq!(
+        executed,
+        [
+            "test::test_config",
+            "test::test_fixtures",
+            "test::test_mode",
+            "test::test_unrelated"
+        ]
+    );
+
+    {
+        println!("-------- invalid shard --------");
+        let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .args(["--shard", "3/2"])
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(stderr.contains("Invalid shard `3/2`"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_shard_failed")]
+#[test_case(Mode::Static; "static_shard_failed")]
+fn blackbox_test_shard_failed(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- failing --------");
+        let result = command(&mode, &dir, target_dir.path(), Some("changes_display"))
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(!result.status.success());
+    }
+
+    let failed_tests = |target_dir: &Path| {
+        let cache = Cache::new(target_dir);
+        let units = cache.units().unwrap();
+        let unit = units
+            .iter()
+            .find(|unit| unit.compile_mode() == "Test" && unit.target() == "lib")
+            .unwrap();
+        cache.failed_tests(unit).unwrap()
+    };
+    let failed = failed_tests(target_dir.path());
+    assert!(failed.contains(&"adt::test::test_display".to_string()));
+
+    // Every shard starts off the same cache and only forgets about the failed tests that it has executed itself
+    let mut executed = Vec::new();
+    for shard in ["1/2", "2/2"] {
+        println!("-------- shard {shard} --------");
+        let shard_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+        copy_dir(target_dir.path(), shard_dir.path());
+
+        let result = command(&mode, &dir, shard_dir.path(), None)
+            .args(["--shard", shard])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        let (passed, remaining): (Vec<_>, Vec<_>) = failed.iter().cloned().partition(|test| {
+            stdout.contains(&format!("{} ... ok", test.trim_start_matches("adt::")))
+        });
+        assert_eq!(failed_tests(shard_dir.path()), remaining);
+        executed.extend(passed);
+    }
+    executed.sort();
+    assert_eq!(executed, failed);
+}
+
+#[test]
+fn blackbox_test_corrupt_graph() {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    let mut corrupted = 0;
+    for entry in std::fs::read_dir(target_dir.path().join(DIR_STATIC)).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(ENDING_GRAPH) {
+            let mut content = std::fs::read(&path).unwrap();
+            content.truncate(content.len() / 2);
+            std::fs::write(&path, content).unwrap();
+            corrupted += 1;
+        }
+    }
+    assert!(corrupted > 0);
+
+    {
+        println!("-------- corrupt graph --------");
+        let result = command(&Mode::Static, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr.contains("Failed to read dependency graph"));
+        assert!(stderr.contains("retesting all tests"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_corrupt_checksums")]
+#[test_case(Mode::Static; "static_corrupt_checksums")]
+fn blackbox_test_corrupt_checksums(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    let mut corrupted = 0;
+    for path in checksum_files(target_dir.path()).keys() {
+        if path.starts_with("Test_") {
+            std::fs::write(target_dir.path().join(DIR_GENERAL).join(path), "corrupt").unwrap();
+            corrupted += 1;
+        }
+    }
+    assert!(corrupted > 0);
+    // Checksums are only read when the crate is recompiled
+    std::fs::remove_dir_all(target_dir.path().join("debug").join(".fingerprint")).unwrap();
+
+    {
+        println!("-------- corrupt checksums --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        assert!(stdout.contains("test test::test_debug ... ok"));
+        assert!(stdout.contains("test test::test_display ... ok"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_cache_export_import")]
+#[test_case(Mode::Static; "static_cache_export_import")]
+fn blackbox_test_cache_export_import(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let exporting_target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+    let importing_target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+    let archive = exporting_target_dir.path().join("rustyrts.tar.gz");
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, exporting_target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- export --------");
+        let result = subcommand("cache", &dir, exporting_target_dir.path(), None)
+            .arg("export")
+            .arg(&archive)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr.contains("Cache key rustyrts-"));
+    }
+
+    {
+        println!("-------- import --------");
+        let result = subcommand("cache", &dir, importing_target_dir.path(), None)
+            .arg("import")
+            .arg(&archive)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- with changes --------");
+        let result = command(
+            &mode,
+            &dir,
+            importing_target_dir.path(),
+            Some("changes_display"),
+        )
+        .output()
+        .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+
+        // Selection is based on the imported metadata instead of executing all tests
+        let affected = stderr
+            .lines()
+            .filter(|l| l.starts_with("Affected:"))
+            .collect::<Vec<_>>();
+        assert!(affected.iter().any(|l| l.contains("test_display")));
+        assert!(affected.iter().all(|l| !l.contains("test_debug")));
+    }
+}
+
+#[test]
+fn blackbox_test_cache_merge() {
+    let mut dir = PATH.clone();
+    dir.push("accessed");
+
+    let merged_target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+    let shard_target_dirs = ["1/2", "2/2"].map(|shard| {
+        println!("-------- shard {shard} --------");
+        let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "accessed").unwrap();
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), None)
+            .args(["--shard", shard])
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+        target_dir
+    });
+
+    {
+        println!("-------- merge --------");
+        let result = subcommand("cache", &dir, merged_target_dir.path(), None)
+            .arg("merge")
+            .args(shard_target_dirs.iter().map(TempDir::path))
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(result.status.success());
+        assert!(stderr.contains("from 2 target directories"));
+    }
+
+    {
+        println!("-------- without changes --------");
+        let result = command(&Mode::Dynamic, &dir, merged_target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {stdout}");
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        // Every test has been traced by one of the shards
+        assert_eq!(deselected_tests(&stdout).len(), 4);
+    }
+}
+
+#[test]
+fn blackbox_test_cache_merge_conflict() {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let merged_target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+    let source_target_dirs = [None, Some("changes_display")].map(|feature| {
+        println!("-------- {feature:?} --------");
+        let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+        let result = command(&Mode::Dynamic, &dir, target_dir.path(), feature)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        target_dir
+    });
+
+    {
+        println!("-------- merge --------");
+        let result = subcommand("cache", &dir, merged_target_dir.path(), None)
+            .arg("merge")
+            .args(source_target_dirs.iter().map(TempDir::path))
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {stderr}");
+        assert!(!result.status.success());
+        assert!(stderr.contains("have not been built from the same sources"));
+        assert!(stderr.contains(".checksum"));
+    }
+}
+
+#[test_case(Mode::Dynamic; "dynamic_cache_gc")]
+#[test_case(Mode::Static; "static_cache_gc")]
+fn blackbox_test_cache_gc(mode: Mode) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    {
+        println!("-------- baseline --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    // Files of a test that has been removed, of a target whose path has changed and of a removed doctest
+    let stale_trace = target_dir
+        .path()
+        .join(DIR_DYNAMIC)
+        .join("adt::test::test_removed.trace");
+    let stale_unit = target_dir
+        .path()
+        .join(DIR_GENERAL)
+        .join("Test_lib_adt-0000000000000000.checksum");
+    let stale_doctest = target_dir
+        .path()
+        .join(DIR_GENERAL)
+        .join("Doctest_lib_adt_src_removed_rs.checksum");
+    create_dir_all(target_dir.path().join(DIR_DYNAMIC)).unwrap();
+    for path in [&stale_trace, &stale_unit, &stale_doctest] {
+        std::fs::write(path, "stale").unwrap();
+    }
+
+    {
+        println!("-------- cache gc --------");
+        let result = subcommand("cache", &dir, target_dir.path(), None)
+            .arg("gc")
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {}", stderr);
+        assert!(result.status.success());
+        assert!(stderr.contains("Removed 2 stale files, 10B total"));
+
+        assert!(!stale_trace.exists());
+        assert!(!stale_unit.exists());
+        // Doctests are not known without compiling them
+        assert!(stale_doctest.exists());
+    }
+
+    {
+        println!("-------- no changes --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {}", stdout);
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        println!("Stderr: {}", stderr);
+        assert!(result.status.success());
+        assert!(stderr.contains("Removed 1 stale file"));
+        assert!(!stale_doctest.exists());
+
+        // The metadata of the current tests has been kept
+        assert_eq!(deselected_tests(&stdout).len(), 4);
+    }
+}
+
+#[test_case(Mode::Dynamic, None; "dynamic_clean_metadata_only")]
+#[test_case(Mode::Static, None; "static_clean_metadata_only")]
+#[test_case(Mode::Dynamic, Some("adt"); "dynamic_clean_metadata_only_package")]
+#[test_case(Mode::Static, Some("adt"); "static_clean_metadata_only_package")]
+fn blackbox_test_clean_metadata_only(mode: Mode, package: Option<&str>) {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    for name in ["baseline", "no changes"] {
+        println!("-------- {name} --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+    }
+
+    {
+        println!("-------- clean --------");
+        let mut cmd = subcommand("clean", &dir, target_dir.path(), None);
+        cmd.arg("--metadata-only");
+        if let Some(package) = package {
+            cmd.arg("-p").arg(package);
+        }
+        let result = cmd.output().unwrap();
+        println!("Stdout: {}", String::from_utf8(result.stdout).unwrap());
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        // Compiled artifacts are kept
+        assert!(target_dir.path().join("debug").join("deps").is_dir());
+        let general = target_dir.path().join(DIR_GENERAL);
+        assert!(!general.is_dir() || std::fs::read_dir(general).unwrap().next().is_none());
+    }
+
+    {
+        println!("-------- after clean --------");
+        let result = command(&mode, &dir, target_dir.path(), None)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        println!("Stdout: {}", stdout);
+        println!("Stderr: {}", String::from_utf8(result.stderr).unwrap());
+        assert!(result.status.success());
+
+        // All tests are executed again
+        assert!(stdout.contains("test result: ok. 4 passed"));
+        assert!(deselected_tests(&stdout).is_empty());
+    }
+}
+
+#[test]
+fn blackbox_test_cache_key() {
+    let mut dir = PATH.clone();
+    dir.push("adt");
+
+    let target_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR"), "adt").unwrap();
+
+    let key = |feature: Option<&str>| {
+        let result = subcommand("cache", &dir, target_dir.path(), feature)
+            .arg("key")
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        String::from_utf8(result.stdout).unwrap()
+    };
+
+    assert_eq!(key(None), key(None));
+    assert_ne!(key(None), key(Some("changes_display")));
 }